@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sp1_vector_primitives::{decode_scale_compact_int, ByteCursor};
+
+// Decoding arbitrary bytes as a compact int must never panic, and must never report consuming
+// more bytes than were supplied.
+fuzz_target!(|data: &[u8]| {
+    if let Ok((_, len)) = decode_scale_compact_int(data) {
+        assert!(len <= data.len());
+    }
+
+    let mut cursor = ByteCursor::new(data);
+    while cursor.read_compact().is_ok() {}
+    assert!(cursor.position() <= data.len());
+});
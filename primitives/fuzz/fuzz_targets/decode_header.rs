@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sp1_vector_primitives::header_range::decode_header;
+
+// Decoding arbitrary header bytes must never panic.
+fuzz_target!(|data: &[u8]| {
+    let _ = decode_header(data);
+});
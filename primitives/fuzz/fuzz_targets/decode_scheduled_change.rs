@@ -0,0 +1,28 @@
+#![no_main]
+
+use alloy_primitives::B256;
+use libfuzzer_sys::fuzz_target;
+use sp1_vector_primitives::consts::{PUBKEY_LENGTH, VALIDATOR_LENGTH};
+use sp1_vector_primitives::rotate::{decode_scheduled_change_log, verify_encoding_epoch_end_header};
+
+// Parsing the consensus log of an arbitrary epoch end header must never panic. The first two bytes
+// select the position of the consensus log in the remaining bytes.
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 2 {
+        return;
+    }
+    let position = u16::from_le_bytes([data[0], data[1]]) as usize;
+    let header_bytes = &data[2..];
+
+    if let Ok((num_authorities, validators_cursor)) =
+        decode_scheduled_change_log(header_bytes, position)
+    {
+        // Use the encoded pubkeys as the expected pubkeys, so the validator checks are reached.
+        let pubkeys: Vec<B256> = header_bytes[validators_cursor..]
+            .chunks_exact(VALIDATOR_LENGTH)
+            .take(num_authorities.min(1024) as usize)
+            .map(|validator| B256::from_slice(&validator[..PUBKEY_LENGTH]))
+            .collect();
+        let _ = verify_encoding_epoch_end_header(header_bytes, position, num_authorities, &pubkeys);
+    }
+});
@@ -0,0 +1,178 @@
+use alloy_primitives::B256;
+use codec::{Compact, CompactLen, Decode};
+use core::fmt;
+
+/// Error returned when SCALE-encoded data cannot be decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input ended before `needed` bytes could be read at `position`.
+    UnexpectedEof {
+        position: usize,
+        needed: usize,
+        available: usize,
+    },
+    /// The bytes at `position` are not a valid SCALE compact int.
+    InvalidCompactInt { position: usize },
+    /// The bytes at `position` do not match the expected value of `field`.
+    UnexpectedValue {
+        field: &'static str,
+        position: usize,
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEof {
+                position,
+                needed,
+                available,
+            } => write!(
+                f,
+                "unexpected end of input at byte {}: needed {} bytes, {} available",
+                position, needed, available
+            ),
+            DecodeError::InvalidCompactInt { position } => {
+                write!(f, "invalid SCALE compact int at byte {}", position)
+            }
+            DecodeError::UnexpectedValue { field, position } => {
+                write!(f, "unexpected value for {} at byte {}", field, position)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// A bounds-checked cursor over a byte slice. Every read advances the cursor and returns an error
+/// instead of panicking when the input is too short.
+#[derive(Debug, Clone)]
+pub struct ByteCursor<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteCursor<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    /// Create a cursor starting at `position`. Fails if `position` is past the end of the input.
+    pub fn at(bytes: &'a [u8], position: usize) -> Result<Self, DecodeError> {
+        let mut cursor = Self::new(bytes);
+        cursor.skip(position)?;
+        Ok(cursor)
+    }
+
+    /// Current offset of the cursor from the start of the input.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Number of bytes left to read.
+    pub fn remaining(&self) -> usize {
+        self.bytes.len() - self.position
+    }
+
+    /// Advance the cursor by `len` bytes.
+    pub fn skip(&mut self, len: usize) -> Result<(), DecodeError> {
+        self.read_bytes(len).map(|_| ())
+    }
+
+    /// Read the next `len` bytes.
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if len > self.remaining() {
+            return Err(DecodeError::UnexpectedEof {
+                position: self.position,
+                needed: len,
+                available: self.remaining(),
+            });
+        }
+        let bytes = &self.bytes[self.position..self.position + len];
+        self.position += len;
+        Ok(bytes)
+    }
+
+    /// Read the next byte.
+    pub fn read_u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    /// Read the next 32 bytes as a hash.
+    pub fn read_b256(&mut self) -> Result<B256, DecodeError> {
+        Ok(B256::from_slice(self.read_bytes(32)?))
+    }
+
+    /// Read a SCALE-encoded compact int.
+    pub fn read_compact(&mut self) -> Result<u64, DecodeError> {
+        let (value, len) =
+            decode_scale_compact_int(&self.bytes[self.position..]).map_err(|_| {
+                DecodeError::InvalidCompactInt {
+                    position: self.position,
+                }
+            })?;
+        self.position += len;
+        Ok(value)
+    }
+
+    /// Read the next `expected.len()` bytes and check that they equal `expected`.
+    pub fn expect_bytes(
+        &mut self,
+        field: &'static str,
+        expected: &[u8],
+    ) -> Result<(), DecodeError> {
+        let position = self.position;
+        if self.read_bytes(expected.len())? != expected {
+            return Err(DecodeError::UnexpectedValue { field, position });
+        }
+        Ok(())
+    }
+}
+
+/// Decode a SCALE-encoded compact int and get the value and the number of bytes it took to encode.
+pub fn decode_scale_compact_int(bytes: &[u8]) -> Result<(u64, usize), DecodeError> {
+    let value = Compact::<u64>::decode(&mut &bytes[..])
+        .map_err(|_| DecodeError::InvalidCompactInt { position: 0 })?;
+    Ok((value.0, Compact::<u64>::compact_len(&value.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codec::Encode;
+
+    #[test]
+    fn test_truncated_reads_return_errors() {
+        let bytes = [1u8, 2, 3];
+        let mut cursor = ByteCursor::new(&bytes);
+        assert_eq!(cursor.read_u8(), Ok(1));
+        assert_eq!(
+            cursor.read_b256(),
+            Err(DecodeError::UnexpectedEof {
+                position: 1,
+                needed: 32,
+                available: 2
+            })
+        );
+        // A failed read does not advance the cursor.
+        assert_eq!(cursor.position(), 1);
+        assert!(ByteCursor::at(&bytes, 4).is_err());
+    }
+
+    #[test]
+    fn test_read_compact() {
+        let mut bytes = Compact(16384u32).encode();
+        bytes.push(7);
+        let mut cursor = ByteCursor::new(&bytes);
+        assert_eq!(cursor.read_compact(), Ok(16384));
+        assert_eq!(cursor.read_u8(), Ok(7));
+
+        // A four-byte compact int with only two bytes present.
+        let truncated = &Compact(1073741823u32).encode()[..2];
+        assert_eq!(
+            ByteCursor::new(truncated).read_compact(),
+            Err(DecodeError::InvalidCompactInt { position: 0 })
+        );
+        assert!(decode_scale_compact_int(&[]).is_err());
+    }
+}
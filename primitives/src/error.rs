@@ -0,0 +1,122 @@
+use alloy_primitives::B256;
+use core::fmt;
+
+use crate::{DecodeError, JustificationReport};
+
+/// Error returned when proof inputs fail the checks performed by the SP1 Vector program.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerificationError {
+    /// The target block is not after the trusted block.
+    InvalidBlockRange {
+        trusted_block: u32,
+        target_block: u32,
+    },
+    /// The number of encoded headers does not cover [trusted_block, target_block].
+    HeaderCountMismatch { expected: usize, found: usize },
+    /// The encoded header at `index` could not be decoded.
+    InvalidHeader { index: usize, error: DecodeError },
+    /// The first header does not hash to the trusted header hash.
+    TrustedHeaderHashMismatch { expected: B256, found: B256 },
+    /// The header at `index` does not have the expected block number.
+    BlockNumberMismatch {
+        index: usize,
+        expected: u32,
+        found: u32,
+    },
+    /// The parent hash of `block_number` does not match the hash of the previous header.
+    UnlinkedHeader {
+        block_number: u32,
+        expected_parent_hash: B256,
+        found_parent_hash: B256,
+    },
+    /// The merkle tree size is not a power of two or is smaller than the number of leaves.
+    InvalidMerkleTreeSize { tree_size: usize, num_leaves: usize },
+    /// The justification is not on the expected block.
+    JustificationBlockMismatch {
+        expected_number: u32,
+        expected_hash: B256,
+        found_number: u32,
+        found_hash: B256,
+    },
+    /// The authority set hash in the justification does not match its validator set.
+    AuthoritySetHashMismatch { expected: B256, found: B256 },
+    /// The justification is not signed by a supermajority or contains invalid signatures.
+    InvalidJustification(Box<JustificationReport>),
+    /// The consensus log in the epoch end header is malformed or does not match the new authorities.
+    InvalidEpochEndHeader(DecodeError),
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerificationError::InvalidBlockRange {
+                trusted_block,
+                target_block,
+            } => write!(
+                f,
+                "target block {} is not after trusted block {}",
+                target_block, trusted_block
+            ),
+            VerificationError::HeaderCountMismatch { expected, found } => {
+                write!(f, "expected {} encoded headers, found {}", expected, found)
+            }
+            VerificationError::InvalidHeader { index, error } => {
+                write!(f, "failed to decode header {}: {}", index, error)
+            }
+            VerificationError::TrustedHeaderHashMismatch { expected, found } => write!(
+                f,
+                "trusted header hash mismatch: expected {}, found {}",
+                expected, found
+            ),
+            VerificationError::BlockNumberMismatch {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "header {} has block number {}, expected {}",
+                index, found, expected
+            ),
+            VerificationError::UnlinkedHeader {
+                block_number,
+                expected_parent_hash,
+                found_parent_hash,
+            } => write!(
+                f,
+                "header of block {} is not linked to its parent: expected parent hash {}, found {}",
+                block_number, expected_parent_hash, found_parent_hash
+            ),
+            VerificationError::InvalidMerkleTreeSize {
+                tree_size,
+                num_leaves,
+            } => write!(
+                f,
+                "merkle tree size {} must be a power of two and at least {}",
+                tree_size, num_leaves
+            ),
+            VerificationError::JustificationBlockMismatch {
+                expected_number,
+                expected_hash,
+                found_number,
+                found_hash,
+            } => write!(
+                f,
+                "justification is on block {} ({}), expected block {} ({})",
+                found_number, found_hash, expected_number, expected_hash
+            ),
+            VerificationError::AuthoritySetHashMismatch { expected, found } => write!(
+                f,
+                "authority set hash mismatch: validator set hashes to {}, justification has {}",
+                expected, found
+            ),
+            VerificationError::InvalidJustification(report) => {
+                write!(f, "invalid justification: {}", report)
+            }
+            VerificationError::InvalidEpochEndHeader(error) => {
+                write!(f, "invalid epoch end header: {}", error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for VerificationError {}
@@ -1,10 +1,13 @@
 use alloy_primitives::B256;
 use alloy_sol_types::SolType;
 
-use crate::consts::HEADER_OUTPUTS_LENGTH;
+use crate::consts::{HASH_SIZE, HEADER_OUTPUTS_LENGTH};
 use crate::merkle::get_merkle_root_commitments;
 use crate::types::{DecodedHeaderData, HeaderRangeInputs, HeaderRangeOutputs};
-use crate::{decode_scale_compact_int, hash_encoded_header, verify_justification};
+use crate::{
+    check_justification, hash_encoded_header, verify_justification, ByteCursor, DecodeError,
+    VerificationError,
+};
 
 /// Verify the justification from the current authority set on target block and compute the
 /// state and data root commitments over the range [trusted_block + 1, target_block] inclusive.
@@ -20,7 +23,9 @@ pub fn verify_header_range(header_range_inputs: HeaderRangeInputs) -> [u8; HEADE
     // Decode the headers.
     let decoded_headers_data: Vec<DecodedHeaderData> = encoded_headers
         .iter()
-        .map(|header_bytes| decode_header(header_bytes.to_vec()))
+        .map(|header_bytes| {
+            decode_header(header_bytes).unwrap_or_else(|e| panic!("Failed to decode header: {}", e))
+        })
         .collect();
 
     // Get the hashes of all of the headers.
@@ -82,26 +87,162 @@ pub fn verify_header_range(header_range_inputs: HeaderRangeInputs) -> [u8; HEADE
     .unwrap()
 }
 
-/// Decode the header into a DecodedHeaderData struct manually.
-fn decode_header(header_bytes: Vec<u8>) -> DecodedHeaderData {
+/// Check the header range inputs and compute the header range outputs. Returns an error describing
+/// the first failed check instead of panicking, so the inputs can be checked natively before
+/// requesting a proof. Only used natively: besides the checks of `verify_header_range`, it checks
+/// the header count, the target of the justification, the authority set hash and the tree size,
+/// which the program does not.
+pub fn check_header_range(
+    header_range_inputs: &HeaderRangeInputs,
+) -> Result<[u8; HEADER_OUTPUTS_LENGTH], VerificationError> {
+    let encoded_headers = &header_range_inputs.encoded_headers;
+
+    // 1. Decode the headers using: https://github.com/succinctlabs/vectorx/blob/fb83641259aef1f5df33efa73c23d90973d64e24/circuits/builder/decoder.rs#L104-L157
+    // 2. Verify the chain of headers is connected from the trusted block to the target block.
+    // 3. Verify the justification is valid.
+    // 4. Compute the simple merkle tree commitment for the headers.
+
+    // Verify the headers cover [trusted_block, target_block] exactly.
+    if header_range_inputs.target_block <= header_range_inputs.trusted_block {
+        return Err(VerificationError::InvalidBlockRange {
+            trusted_block: header_range_inputs.trusted_block,
+            target_block: header_range_inputs.target_block,
+        });
+    }
+    let num_headers =
+        (header_range_inputs.target_block - header_range_inputs.trusted_block + 1) as usize;
+    if encoded_headers.len() != num_headers {
+        return Err(VerificationError::HeaderCountMismatch {
+            expected: num_headers,
+            found: encoded_headers.len(),
+        });
+    }
+
+    // Stage 1: Decode the headers.
+    // Decode the headers.
+    let decoded_headers_data: Vec<DecodedHeaderData> = encoded_headers
+        .iter()
+        .enumerate()
+        .map(|(index, header_bytes)| {
+            decode_header(header_bytes)
+                .map_err(|error| VerificationError::InvalidHeader { index, error })
+        })
+        .collect::<Result<_, _>>()?;
+
+    // Get the hashes of all of the headers.
+    let header_hashes = encoded_headers
+        .iter()
+        .map(|e| hash_encoded_header(e.as_slice()))
+        .collect::<Vec<_>>();
+
+    // Verify the first header hash matches the trusted header hash.
+    if header_hashes[0] != header_range_inputs.trusted_header_hash {
+        return Err(VerificationError::TrustedHeaderHashMismatch {
+            expected: header_range_inputs.trusted_header_hash,
+            found: header_hashes[0],
+        });
+    }
+
+    // Stage 2: Verify the chain of headers is connected from the trusted block to the target block
+    // by verifying the parent hashes are linked and the block numbers are sequential. Because the
+    // number of headers is fixed, this also verifies the last header is the target block.
+    for (i, decoded_header) in decoded_headers_data.iter().enumerate() {
+        // Verify the block numbers are sequential.
+        let expected_block_number = header_range_inputs.trusted_block + i as u32;
+        if decoded_header.block_number != expected_block_number {
+            return Err(VerificationError::BlockNumberMismatch {
+                index: i,
+                expected: expected_block_number,
+                found: decoded_header.block_number,
+            });
+        }
+        // Verify the headers are linked.
+        if i > 0 && decoded_header.parent_hash != header_hashes[i - 1] {
+            return Err(VerificationError::UnlinkedHeader {
+                block_number: decoded_header.block_number,
+                expected_parent_hash: header_hashes[i - 1],
+                found_parent_hash: decoded_header.parent_hash,
+            });
+        }
+    }
+    let target_header_hash = header_hashes[header_hashes.len() - 1];
+
+    // Stage 3: Verify the justification is valid and is on the target block.
+    let target_justification = &header_range_inputs.target_justification;
+    if target_justification.block_number != header_range_inputs.target_block
+        || target_justification.block_hash != target_header_hash
+    {
+        return Err(VerificationError::JustificationBlockMismatch {
+            expected_number: header_range_inputs.target_block,
+            expected_hash: target_header_hash,
+            found_number: target_justification.block_number,
+            found_hash: target_justification.block_hash,
+        });
+    }
+    check_justification(target_justification)?;
+
+    // Stage 4: Compute the simple Merkle tree commitment for the headers. Note: Does not include
+    // the trusted header in the commitment.
+    let merkle_tree_size = header_range_inputs.merkle_tree_size;
+    let num_leaves = decoded_headers_data.len() - 1;
+    if !merkle_tree_size.is_power_of_two() || merkle_tree_size < num_leaves {
+        return Err(VerificationError::InvalidMerkleTreeSize {
+            tree_size: merkle_tree_size,
+            num_leaves,
+        });
+    }
+    let (state_root_commitment, data_root_commitment) =
+        get_merkle_root_commitments(&decoded_headers_data[1..], merkle_tree_size);
+
+    Ok(HeaderRangeOutputs::abi_encode(&(
+        header_range_inputs.trusted_block,
+        header_range_inputs.trusted_header_hash,
+        target_justification.authority_set_id,
+        target_justification.current_authority_set_hash,
+        header_range_inputs.target_block,
+        target_header_hash,
+        state_root_commitment,
+        data_root_commitment,
+        merkle_tree_size as u32,
+    ))
+    .try_into()
+    .unwrap())
+}
+
+/// Decode the header into a DecodedHeaderData struct manually. Returns an error if the header is
+/// too short to contain the expected fields.
+pub fn decode_header(header_bytes: &[u8]) -> Result<DecodedHeaderData, DecodeError> {
+    let mut cursor = ByteCursor::new(header_bytes);
+
     // The first 32 bytes are the parent hash.
-    let mut cursor: usize = 32;
-    let parent_hash = B256::from_slice(&header_bytes[..cursor]);
+    let parent_hash = cursor.read_b256()?;
 
     // The next section is the variable-length encoded block number.
-    let (block_nb, num_bytes) = decode_scale_compact_int(header_bytes[cursor..cursor + 5].to_vec());
-    cursor += num_bytes;
+    let block_number_position = cursor.position();
+    let block_nb = cursor.read_compact()?;
+    let block_number = u32::try_from(block_nb).map_err(|_| DecodeError::UnexpectedValue {
+        field: "block number",
+        position: block_number_position,
+    })?;
 
     // After the block number is the state root.
-    let state_root = B256::from_slice(&header_bytes[cursor..cursor + 32]);
-
-    // The last 32 bytes are the data root.
-    let data_root = B256::from_slice(&header_bytes[header_bytes.len() - 32..header_bytes.len()]);
+    let state_root = cursor.read_b256()?;
+
+    // The last 32 bytes are the data root. The data root is part of the header extension, so it
+    // must come after the state root.
+    if cursor.remaining() < HASH_SIZE {
+        return Err(DecodeError::UnexpectedEof {
+            position: cursor.position(),
+            needed: HASH_SIZE,
+            available: cursor.remaining(),
+        });
+    }
+    let data_root = B256::from_slice(&header_bytes[header_bytes.len() - HASH_SIZE..]);
 
-    DecodedHeaderData {
-        block_number: block_nb as u32,
+    Ok(DecodedHeaderData {
+        block_number,
         parent_hash,
         state_root,
         data_root,
-    }
+    })
 }
@@ -1,7 +1,12 @@
-use crate::{hash_encoded_header, types::CircuitJustification};
+use crate::{
+    compute_authority_set_commitment, hash_encoded_header, types::CircuitJustification, ByteCursor,
+    VerificationError,
+};
 use codec::Encode;
+use core::fmt;
 use ed25519_consensus::{Signature, VerificationKey};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 
 use alloy_primitives::B256;
 
@@ -14,6 +19,16 @@ fn verify_signature(pubkey_bytes: [u8; 32], signed_message: &[u8], signature: [u
     }
 }
 
+/// Check whether a Ed25519 signature is valid, without panicking.
+fn is_valid_signature(pubkey_bytes: [u8; 32], signed_message: &[u8], signature: [u8; 64]) -> bool {
+    match VerificationKey::try_from(pubkey_bytes) {
+        Ok(pubkey) => pubkey
+            .verify(&Signature::from(signature), signed_message)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
 /// Confirm ancestry of a child block by traversing the ancestry_map until root_hash is reached.
 /// Sourced from https://github.com/availproject/avail-light/blob/main/core/src/finality.rs with some
 /// small refactors for readability.  
@@ -39,6 +54,172 @@ fn is_signed_by_supermajority(num_signatures: usize, validator_set_size: usize)
     num_signatures * 3 > validator_set_size * 2
 }
 
+/// Outcome of verifying a justification natively, with a per-validator breakdown of the vote.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JustificationReport {
+    /// Block number associated with the justification.
+    pub block_number: u32,
+    /// Set ID of the authority set the justification was checked against.
+    pub authority_set_id: u64,
+    /// Size of the authority set.
+    pub validator_set_size: usize,
+    /// Validators with a valid precommit whose target descends from the justified block.
+    pub signers: Vec<B256>,
+    /// Validators in the authority set that are not in `signers`.
+    pub absentees: Vec<B256>,
+    /// Signers of precommits with an invalid signature.
+    pub invalid_signatures: Vec<B256>,
+    /// Signers of valid precommits whose target could not be linked to the justified block.
+    pub ancestry_failures: Vec<B256>,
+    /// Signers of valid precommits that are not in the authority set.
+    pub non_members: Vec<B256>,
+}
+
+impl JustificationReport {
+    /// Minimum number of signers required for a supermajority of the authority set.
+    pub fn threshold(&self) -> usize {
+        self.validator_set_size * 2 / 3 + 1
+    }
+
+    /// Fraction of the authority set that signed the justification.
+    pub fn participation_ratio(&self) -> f64 {
+        if self.validator_set_size == 0 {
+            return 0.0;
+        }
+        self.signers.len() as f64 / self.validator_set_size as f64
+    }
+
+    /// Number of signers above (positive) or below (negative) the supermajority threshold.
+    pub fn threshold_margin(&self) -> i64 {
+        self.signers.len() as i64 - self.threshold() as i64
+    }
+
+    /// Whether more than 2/3 of the authority set signed the justification.
+    pub fn is_signed_by_supermajority(&self) -> bool {
+        is_signed_by_supermajority(self.signers.len(), self.validator_set_size)
+    }
+
+    /// Whether every signature is valid and a supermajority of distinct validators signed. This is
+    /// stricter than `verify_justification`, which counts every precommit of a validator, so a
+    /// justification with duplicate precommits can pass it without passing this check.
+    pub fn is_valid(&self) -> bool {
+        self.invalid_signatures.is_empty() && self.is_signed_by_supermajority()
+    }
+}
+
+impl fmt::Display for JustificationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "block {} (set {}): {}/{} signers ({:.2}%, threshold {}, margin {}), {} absent, {} invalid signatures, {} ancestry failures, {} non-members",
+            self.block_number,
+            self.authority_set_id,
+            self.signers.len(),
+            self.validator_set_size,
+            self.participation_ratio() * 100.0,
+            self.threshold(),
+            self.threshold_margin(),
+            self.absentees.len(),
+            self.invalid_signatures.len(),
+            self.ancestry_failures.len(),
+            self.non_members.len(),
+        )
+    }
+}
+
+/// Verify a justification on a block from the specified authority set and report which validators
+/// signed, which are missing, and why any precommits were not counted. Does not panic on an invalid
+/// justification. Only used natively, the program runs `verify_justification`, whose checks this
+/// follows except that each validator is counted once.
+pub fn verify_justification_with_report(
+    justification: &CircuitJustification,
+) -> JustificationReport {
+    // 1. Form an ancestry map from votes_ancestries in the justification. This maps header hashes to their parents' hashes.
+    // Since we only get encoded headers, ensure that the parent is contained in the encoded header, no need to decode it.
+    // Headers too short to contain a parent hash can't link any ancestry, so they are skipped.
+    let ancestry_map: HashMap<B256, B256> = justification
+        .ancestries_encoded
+        .iter()
+        .filter_map(|encoded_header| {
+            let parent_hash = ByteCursor::new(encoded_header).read_b256().ok()?;
+            let header_hash = hash_encoded_header(encoded_header);
+
+            Some((header_hash, parent_hash))
+        })
+        .collect();
+
+    let valset: HashSet<B256> = justification.valset_pubkeys.iter().copied().collect();
+
+    let mut signers = HashSet::new();
+    let mut invalid_signatures = Vec::new();
+    let mut ancestry_failures = Vec::new();
+    let mut non_members = Vec::new();
+
+    // 2. Sort the precommits by whether they have a valid signature, a confirmed ancestry, and a
+    // signer in the validator set of the justification.
+    for p in justification.precommits.iter() {
+        // Form the message which is signed in the Justification.
+        // Combination of the precommit flag, block data, round number and set_id.
+        let signed_message = Encode::encode(&(
+            1u8,
+            p.target_hash.0,
+            p.target_number,
+            &justification.round,
+            &justification.authority_set_id,
+        ));
+
+        if !is_valid_signature(p.pubkey.0, &signed_message, p.signature.0) {
+            invalid_signatures.push(p.pubkey);
+        } else if !confirm_ancestry(&p.target_hash, &justification.block_hash, &ancestry_map) {
+            ancestry_failures.push(p.pubkey);
+        } else if !valset.contains(&p.pubkey) {
+            non_members.push(p.pubkey);
+        } else {
+            signers.insert(p.pubkey);
+        }
+    }
+
+    // 3. Split the validator set into the accounts that signed and the accounts that did not,
+    // preserving the order of the validator set.
+    let (signers, absentees) = justification
+        .valset_pubkeys
+        .iter()
+        .partition(|pubkey| signers.contains(*pubkey));
+
+    JustificationReport {
+        block_number: justification.block_number,
+        authority_set_id: justification.authority_set_id,
+        validator_set_size: justification.valset_pubkeys.len(),
+        signers,
+        absentees,
+        invalid_signatures,
+        ancestry_failures,
+        non_members,
+    }
+}
+
+/// Check a justification on a block from the specified authority set. Confirms that the authority
+/// set hash matches the validator set, that every precommit has a valid signature, and that a
+/// supermajority of the validator set is achieved on the specific block.
+pub fn check_justification(
+    justification: &CircuitJustification,
+) -> Result<JustificationReport, VerificationError> {
+    // An empty validator set can never reach a supermajority.
+    let report = verify_justification_with_report(justification);
+    if !report.is_valid() {
+        return Err(VerificationError::InvalidJustification(Box::new(report)));
+    }
+
+    let authority_set_hash = compute_authority_set_commitment(&justification.valset_pubkeys);
+    if authority_set_hash != justification.current_authority_set_hash {
+        return Err(VerificationError::AuthoritySetHashMismatch {
+            expected: authority_set_hash,
+            found: justification.current_authority_set_hash,
+        });
+    }
+    Ok(report)
+}
+
 /// Verify a justification on a block from the specified authority set. Confirms that a supermajority
 /// of the validator set is achieved on the specific block. Sourced from
 /// https://github.com/availproject/avail-light/blob/main/core/src/finality.rs with some minor
@@ -46,15 +227,15 @@ fn is_signed_by_supermajority(num_signatures: usize, validator_set_size: usize)
 pub fn verify_justification(justification: &CircuitJustification) {
     // 1. Form an ancestry map from votes_ancestries in the justification. This maps header hashes to their parents' hashes.
     // Since we only get encoded headers, ensure that the parent is contained in the encoded header, no need to decode it.
+    // Headers too short to contain a parent hash can't link any ancestry, so they are skipped.
     let ancestry_map: HashMap<B256, B256> = justification
         .ancestries_encoded
         .iter()
-        .map(|encoded_header| {
-            let parent_hash_array: [u8; 32] = encoded_header[0..32].try_into().unwrap();
-            let parent_hash = B256::from(parent_hash_array);
+        .filter_map(|encoded_header| {
+            let parent_hash = ByteCursor::new(encoded_header).read_b256().ok()?;
             let header_hash = hash_encoded_header(encoded_header);
 
-            (header_hash, parent_hash.to_owned())
+            Some((header_hash, parent_hash))
         })
         .collect();
 
@@ -3,19 +3,23 @@ use blake2::{
     digest::{Update, VariableOutput},
     Blake2bVar,
 };
-use codec::{Compact, Decode, Encode};
 use sha2::{Digest as Sha256Digest, Sha256};
 
-use crate::consts::{PUBKEY_LENGTH, VALIDATOR_LENGTH};
-
 pub mod consts;
+pub mod cursor;
+mod error;
 pub mod header_range;
 mod justification;
 pub mod merkle;
 pub mod rotate;
 pub mod types;
 
-pub use justification::verify_justification;
+pub use cursor::{decode_scale_compact_int, ByteCursor, DecodeError};
+pub use error::VerificationError;
+pub use justification::{
+    check_justification, verify_justification, verify_justification_with_report,
+    JustificationReport,
+};
 
 /// Blake2B hash of an encoded header. Note: This is a generic hash fn for any data.
 pub(crate) fn hash_encoded_header(encoded_header: &[u8]) -> B256 {
@@ -40,29 +44,23 @@ pub fn compute_authority_set_commitment(pubkeys: &[B256]) -> B256 {
     B256::from_slice(&commitment_so_far)
 }
 
-/// Decode a SCALE-encoded compact int and get the value and the number of bytes it took to encode.
-pub(crate) fn decode_scale_compact_int(bytes: Vec<u8>) -> (u64, usize) {
-    let value = Compact::<u64>::decode(&mut bytes.as_slice())
-        .expect("Failed to decode SCALE-encoded compact int.");
-    (value.into(), value.encoded_size())
-}
-
 /// Verify that the encoded validators match the provided pubkeys, have the correct weight, and the
 /// delay is set to zero starting from the supplied cursor.
-pub fn verify_encoded_validators(header_bytes: &[u8], start_cursor: usize, pubkeys: &Vec<B256>) {
-    let mut cursor = start_cursor;
+pub fn verify_encoded_validators(
+    header_bytes: &[u8],
+    start_cursor: usize,
+    pubkeys: &[B256],
+) -> Result<(), DecodeError> {
+    let mut cursor = ByteCursor::at(header_bytes, start_cursor)?;
     for pubkey in pubkeys {
-        let extracted_pubkey = B256::from_slice(&header_bytes[cursor..cursor + PUBKEY_LENGTH]);
-        // Assert that the extracted pubkey matches the expected pubkey.
-        assert_eq!(extracted_pubkey, *pubkey);
-        let extracted_weight = &header_bytes[cursor + PUBKEY_LENGTH..cursor + VALIDATOR_LENGTH];
+        // Check that the extracted pubkey matches the expected pubkey.
+        cursor.expect_bytes("validator pubkey", pubkey.as_slice())?;
 
         // All validator voting weights in Avail are 1.
-        assert_eq!(extracted_weight, &[1u8, 0, 0, 0, 0, 0, 0, 0]);
-        cursor += VALIDATOR_LENGTH;
+        cursor.expect_bytes("validator weight", &[1u8, 0, 0, 0, 0, 0, 0, 0])?;
     }
-    // Assert the delay is 0.
-    assert_eq!(&header_bytes[cursor..cursor + 4], &[0u8, 0u8, 0u8, 0u8]);
+    // Check the delay is 0.
+    cursor.expect_bytes("scheduled change delay", &[0u8, 0u8, 0u8, 0u8])
 }
 
 #[cfg(test)]
@@ -92,11 +90,96 @@ mod tests {
         let encoded_nums: Vec<Vec<u8>> = nums.iter().map(|num| Compact(*num).encode()).collect();
         let zipped: Vec<(&Vec<u8>, &u32)> = encoded_nums.iter().zip(nums.iter()).collect();
         for (encoded_num, num) in zipped {
-            let (value, _) = decode_scale_compact_int(encoded_num.to_vec());
+            let (value, _) = decode_scale_compact_int(encoded_num).unwrap();
             assert_eq!(value, *num as u64);
         }
     }
 
+    #[test]
+    fn test_decode_truncated_header() {
+        let h = DaHeader {
+            parent_hash: H256::random(),
+            number: 645570,
+            state_root: H256::random(),
+            extrinsics_root: H256::zero(),
+            extension: V3(HeaderExtension {
+                ..Default::default()
+            }),
+            digest: Digest {
+                ..Default::default()
+            },
+        };
+        let encoded = h.encode();
+
+        let decoded = header_range::decode_header(&encoded).unwrap();
+        assert_eq!(decoded.block_number, 645570);
+        assert_eq!(decoded.parent_hash.0, h.parent_hash.0);
+        assert_eq!(decoded.state_root.0, h.state_root.0);
+
+        // Every strict prefix that cuts into the parent hash, number or state root is rejected.
+        for len in 0..32 + 4 + 32 {
+            assert!(header_range::decode_header(&encoded[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_check_header_range_rejects_unlinked_headers() {
+        use crate::types::{CircuitJustification, HeaderRangeInputs};
+
+        let encode_header = |parent_hash: H256, number: u32| {
+            DaHeader {
+                parent_hash,
+                number,
+                state_root: H256::zero(),
+                extrinsics_root: H256::zero(),
+                extension: V3(HeaderExtension {
+                    ..Default::default()
+                }),
+                digest: Digest {
+                    ..Default::default()
+                },
+            }
+            .encode()
+        };
+        let trusted_header = encode_header(H256::random(), 10);
+        let linked_header = encode_header(H256(hash_encoded_header(&trusted_header).0), 11);
+        let unlinked_header = encode_header(H256::random(), 12);
+
+        let mut inputs = HeaderRangeInputs {
+            trusted_block: 10,
+            trusted_header_hash: hash_encoded_header(&trusted_header),
+            target_block: 12,
+            merkle_tree_size: 2,
+            encoded_headers: vec![trusted_header, linked_header],
+            target_justification: CircuitJustification {
+                round: 0,
+                authority_set_id: 0,
+                valset_pubkeys: vec![],
+                precommits: vec![],
+                current_authority_set_hash: B256::ZERO,
+                block_number: 12,
+                block_hash: B256::ZERO,
+                ancestries_encoded: vec![],
+            },
+        };
+        assert_eq!(
+            header_range::check_header_range(&inputs),
+            Err(VerificationError::HeaderCountMismatch {
+                expected: 3,
+                found: 2
+            })
+        );
+
+        inputs.encoded_headers.push(unlinked_header);
+        assert!(matches!(
+            header_range::check_header_range(&inputs),
+            Err(VerificationError::UnlinkedHeader {
+                block_number: 12,
+                ..
+            })
+        ));
+    }
+
     #[test]
     fn test_header_parent_hash_extracting() {
         let hash = H256::random();
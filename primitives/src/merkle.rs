@@ -21,18 +21,59 @@ fn get_merkle_root(leaves: Vec<B256>) -> B256 {
     let mut nodes = leaves.clone();
     while nodes.len() > 1 {
         nodes = (0..nodes.len() / 2)
-            .map(|i| {
-                let mut hasher = Sha256::new();
-                hasher.update(nodes[2 * i]);
-                hasher.update(nodes[2 * i + 1]);
-                B256::from_slice(&hasher.finalize())
-            })
+            .map(|i| hash_pair(nodes[2 * i], nodes[2 * i + 1]))
             .collect();
     }
 
     nodes[0]
 }
 
+fn hash_pair(left: B256, right: B256) -> B256 {
+    let mut hasher = Sha256::new();
+    hasher.update(left);
+    hasher.update(right);
+    B256::from_slice(&hasher.finalize())
+}
+
+/// Computes the Merkle branch of the leaf at `index`, after padding the leaves with empty 32 byte
+/// arrays to `tree_size`. The branch holds the sibling of the node on the path to the root at each
+/// level, starting with the sibling of the leaf.
+pub fn get_merkle_branch(leaves: &[B256], tree_size: usize, index: usize) -> Vec<B256> {
+    assert!(tree_size.is_power_of_two());
+    assert!(tree_size >= leaves.len());
+    assert!(index < tree_size);
+
+    let mut nodes = leaves.to_vec();
+    nodes.resize(tree_size, B256::from([0u8; 32]));
+
+    let mut branch = Vec::new();
+    let mut index = index;
+    while nodes.len() > 1 {
+        branch.push(nodes[index ^ 1]);
+        nodes = (0..nodes.len() / 2)
+            .map(|i| hash_pair(nodes[2 * i], nodes[2 * i + 1]))
+            .collect();
+        index /= 2;
+    }
+    branch
+}
+
+/// Computes the Merkle root from the leaf at `index` and its branch, as returned by
+/// `get_merkle_branch`.
+pub fn get_merkle_root_from_branch(leaf: B256, branch: &[B256], index: usize) -> B256 {
+    let mut node = leaf;
+    let mut index = index;
+    for sibling in branch {
+        node = if index % 2 == 0 {
+            hash_pair(node, *sibling)
+        } else {
+            hash_pair(*sibling, node)
+        };
+        index /= 2;
+    }
+    node
+}
+
 /// Computes the simple Merkle root commitments for the state root and data root.
 pub fn get_merkle_root_commitments(
     decoded_headers: &[DecodedHeaderData],
@@ -66,3 +107,23 @@ pub fn get_merkle_root_commitments(
 
     (state_root_commitment, data_root_commitment)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merkle_branch_matches_root() {
+        let leaves: Vec<B256> = (1..=5u8).map(|i| B256::repeat_byte(i)).collect();
+        let root = get_merkle_root(leaves.clone());
+        for (index, leaf) in leaves.iter().enumerate() {
+            let branch = get_merkle_branch(&leaves, 8, index);
+            assert_eq!(branch.len(), 3);
+            assert_eq!(get_merkle_root_from_branch(*leaf, &branch, index), root);
+        }
+        // Padding leaves are in the tree too.
+        let branch = get_merkle_branch(&leaves, 8, 7);
+        assert_eq!(get_merkle_root_from_branch(B256::ZERO, &branch, 7), root);
+        assert_ne!(get_merkle_root_from_branch(leaves[0], &branch, 7), root);
+    }
+}
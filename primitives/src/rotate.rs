@@ -1,6 +1,7 @@
 use crate::{
-    compute_authority_set_commitment, consts::ROTATE_OUTPUTS_LENGTH, decode_scale_compact_int,
-    types::RotateInputs, types::RotateOutputs, verify_encoded_validators, verify_justification,
+    check_justification, compute_authority_set_commitment, consts::ROTATE_OUTPUTS_LENGTH,
+    hash_encoded_header, header_range::decode_header, types::RotateInputs, types::RotateOutputs,
+    verify_encoded_validators, verify_justification, ByteCursor, DecodeError, VerificationError,
 };
 use alloy_primitives::B256;
 use alloy_sol_types::SolType;
@@ -17,8 +18,9 @@ pub fn verify_rotate(rotate_inputs: RotateInputs) -> [u8; ROTATE_OUTPUTS_LENGTH]
         &rotate_inputs.header_rotate_data.header_bytes,
         rotate_inputs.header_rotate_data.consensus_log_position,
         rotate_inputs.header_rotate_data.num_authorities as u64,
-        rotate_inputs.header_rotate_data.pubkeys.clone(),
-    );
+        &rotate_inputs.header_rotate_data.pubkeys,
+    )
+    .unwrap_or_else(|e| panic!("Invalid epoch end header: {}", e));
 
     // Compute new authority set hash from the public keys that are encoded in the epoch end header.
     let new_authority_set_hash =
@@ -34,45 +36,114 @@ pub fn verify_rotate(rotate_inputs: RotateInputs) -> [u8; ROTATE_OUTPUTS_LENGTH]
     .unwrap()
 }
 
+/// Check the rotate inputs and compute the rotate outputs. Returns an error describing the first
+/// failed check instead of panicking, so the inputs can be checked natively before requesting a
+/// proof. Only used natively: besides the checks of `verify_rotate`, it checks that the
+/// justification is on the epoch end header and the authority set hash, which the program does not.
+pub fn check_rotate(
+    rotate_inputs: &RotateInputs,
+) -> Result<[u8; ROTATE_OUTPUTS_LENGTH], VerificationError> {
+    let header_rotate_data = &rotate_inputs.header_rotate_data;
+
+    // Verify the provided justification is valid and is on the epoch end header.
+    let justification = &rotate_inputs.justification;
+    let epoch_end_header = decode_header(&header_rotate_data.header_bytes)
+        .map_err(|error| VerificationError::InvalidHeader { index: 0, error })?;
+    let epoch_end_header_hash = hash_encoded_header(&header_rotate_data.header_bytes);
+    if justification.block_number != epoch_end_header.block_number
+        || justification.block_hash != epoch_end_header_hash
+    {
+        return Err(VerificationError::JustificationBlockMismatch {
+            expected_number: epoch_end_header.block_number,
+            expected_hash: epoch_end_header_hash,
+            found_number: justification.block_number,
+            found_hash: justification.block_hash,
+        });
+    }
+    check_justification(justification)?;
+
+    // Verify the encoded epoch end header is formatted correctly, and that the provided new pubkeys
+    // match the encoded ones.
+    verify_encoding_epoch_end_header(
+        &header_rotate_data.header_bytes,
+        header_rotate_data.consensus_log_position,
+        header_rotate_data.num_authorities as u64,
+        &header_rotate_data.pubkeys,
+    )
+    .map_err(VerificationError::InvalidEpochEndHeader)?;
+
+    // The encoding check accepts an empty authority set, which has no commitment.
+    if header_rotate_data.pubkeys.is_empty() {
+        return Err(VerificationError::InvalidEpochEndHeader(
+            DecodeError::UnexpectedValue {
+                field: "authority set size",
+                position: header_rotate_data.consensus_log_position,
+            },
+        ));
+    }
+
+    // Compute new authority set hash from the public keys that are encoded in the epoch end header.
+    let new_authority_set_hash = compute_authority_set_commitment(&header_rotate_data.pubkeys);
+
+    // Return the ABI encoded RotateOutputs.
+    Ok(RotateOutputs::abi_encode(&(
+        justification.authority_set_id,
+        justification.current_authority_set_hash,
+        new_authority_set_hash,
+    ))
+    .try_into()
+    .unwrap())
+}
+
 /// Verify the encoded epoch end header is formatted correctly, and that the new pubkeys used to compute
 /// the new authority set hash match the pubkeys encoded in the epoch end header.
-fn verify_encoding_epoch_end_header(
+pub fn verify_encoding_epoch_end_header(
     header_bytes: &[u8],
     start_cursor: usize,
     num_authorities: u64,
-    pubkeys: Vec<B256>,
-) {
+    pubkeys: &[B256],
+) -> Result<(), DecodeError> {
+    let (authority_set_size, validators_cursor) =
+        decode_scheduled_change_log(header_bytes, start_cursor)?;
+    if authority_set_size != num_authorities || pubkeys.len() as u64 != num_authorities {
+        return Err(DecodeError::UnexpectedValue {
+            field: "authority set size",
+            position: start_cursor,
+        });
+    }
+
+    // Verify that num_authorities validators are correctly encoded and match the pubkeys.
+    verify_encoded_validators(header_bytes, validators_cursor, pubkeys)
+}
+
+/// Decode the prefix of the GRANDPA scheduled change consensus log in the encoded epoch end header
+/// starting at `start_cursor`. Returns the encoded authority set size and the position of the first
+/// encoded validator.
+pub fn decode_scheduled_change_log(
+    header_bytes: &[u8],
+    start_cursor: usize,
+) -> Result<(u64, usize), DecodeError> {
     // Verify the epoch end header's consensus log is formatted correctly before the new authority set hash bytes.
-    let mut cursor = start_cursor;
+    let mut cursor = ByteCursor::at(header_bytes, start_cursor)?;
+
+    // Skip the digest item flag.
+    cursor.skip(1)?;
 
     // Verify consensus flag is 4.
-    assert_eq!(header_bytes[cursor + 1], 4u8);
+    cursor.expect_bytes("consensus flag", &[4u8])?;
 
     // Verify the consensus engine ID: 0x46524e4b [70, 82, 78, 75]
     // Consensus Id: https://github.com/availproject/avail/blob/188c20d6a1577670da65e0c6e1c2a38bea8239bb/avail-subxt/examples/download_digest_items.rs#L41-L56
-    assert_eq!(
-        header_bytes[cursor + 2..cursor + 6],
-        [70u8, 82u8, 78u8, 75u8]
-    );
-
-    cursor += 6;
+    cursor.expect_bytes("consensus engine id", &[70u8, 82u8, 78u8, 75u8])?;
 
     // Decode the encoded scheduled change message length.
-    let (_, decoded_byte_length) =
-        decode_scale_compact_int(header_bytes[cursor..cursor + 5].to_vec());
-    cursor += decoded_byte_length;
+    cursor.read_compact()?;
 
     // Verify the next byte after encoded scheduled change message is scheduled change enum flags.
-    assert_eq!(header_bytes[cursor], 1u8);
-
-    cursor += 1;
+    cursor.expect_bytes("scheduled change flag", &[1u8])?;
 
     // Decoded the encoded authority set size.
-    let (authority_set_size, decoded_byte_length) =
-        decode_scale_compact_int(header_bytes[cursor..cursor + 5].to_vec());
-    assert_eq!(authority_set_size, num_authorities);
-    cursor += decoded_byte_length;
+    let authority_set_size = cursor.read_compact()?;
 
-    // Verify that num_authorities validators are correctly encoded and match the pubkeys.
-    verify_encoded_validators(header_bytes, cursor, &pubkeys);
+    Ok((authority_set_size, cursor.position()))
 }
@@ -45,8 +45,16 @@ export async function GET(req: NextRequest) {
         });
     }
 
+    // Compact records only carry the JSON data if the indexer sets JUSTIFICATION_JSON_DATA.
+    if (response.Items[0].data === undefined) {
+        return NextResponse.json({
+            success: false,
+            error: 'Justification is stored in the compact format without JSON data'
+        });
+    }
+
     return NextResponse.json({
         success: true,
-        justification: response.Items![0].data
+        justification: response.Items[0].data
     });
 }
\ No newline at end of file
@@ -19,7 +19,7 @@ const HEADER_RANGE_COMMITMENT_TREE_SIZE: u32 = 1024;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let fetcher = RpcDataFetcher::new().await;
+    let fetcher = RpcDataFetcher::builder().from_env()?.build().await?;
     let client = ProverClient::new();
     let (_pk, vk) = client.setup(VECTORX_ELF);
 
@@ -27,15 +27,15 @@ async fn main() -> anyhow::Result<()> {
 
     let header;
     if let Some(block) = args.block {
-        header = fetcher.get_header(block).await;
+        header = fetcher.get_header(block).await?;
     } else {
-        header = fetcher.get_head().await;
+        header = fetcher.get_head().await?;
     }
     let header_hash = header.hash();
-    let authority_set_id = fetcher.get_authority_set_id(header.number).await;
+    let authority_set_id = fetcher.get_authority_set_id(header.number).await?;
     let authority_set_hash = fetcher
         .compute_authority_set_hash_for_block(header.number)
-        .await;
+        .await?;
 
     struct GenesisOutput {
         genesis_height: u32,
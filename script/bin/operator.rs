@@ -1,6 +1,6 @@
 use std::env;
+use std::sync::Arc;
 use std::time::Duration;
-use std::{cmp::min, sync::Arc};
 
 use alloy::{
     network::{Ethereum, EthereumWallet},
@@ -20,7 +20,8 @@ use services::input::RpcDataFetcher;
 use sp1_sdk::{
     HashableKey, ProverClient, SP1ProofWithPublicValues, SP1ProvingKey, SP1Stdin, SP1VerifyingKey,
 };
-use sp1_vector_primitives::types::ProofType;
+use sp1_vector_primitives::{types::ProofType, verify_justification_with_report};
+use sp1_vectorx_script::planner::find_block_to_step_to;
 use sp1_vectorx_script::relay::{self};
 const ELF: &[u8] = include_bytes!("../../program/elf/riscv32im-succinct-zkvm-elf");
 
@@ -51,6 +52,7 @@ type EthereumFillProvider = FillProvider<
 >;
 
 struct VectorXOperator {
+    fetcher: RpcDataFetcher,
     wallet_filler: Arc<EthereumFillProvider>,
     provider: Arc<RootProvider<Http<Client>>>,
     client: ProverClient,
@@ -72,6 +74,9 @@ struct HeaderRangeContractData {
 
 const NUM_RELAY_RETRIES: u32 = 3;
 
+/// Delay before the operator loop is restarted after an error.
+const ERROR_BACKOFF: tokio::time::Duration = tokio::time::Duration::from_secs(60);
+
 #[derive(Debug)]
 struct RotateContractData {
     current_block: u32,
@@ -82,6 +87,13 @@ impl VectorXOperator {
     async fn new() -> Self {
         dotenv::dotenv().ok();
 
+        let fetcher = RpcDataFetcher::builder()
+            .from_env()
+            .expect("Invalid Avail RPC configuration")
+            .build()
+            .await
+            .expect("Failed to connect to the Avail RPC");
+
         let client = ProverClient::new();
         let (pk, vk) = client.setup(ELF);
         let use_kms_relayer: bool = env::var("USE_KMS_RELAYER")
@@ -112,6 +124,7 @@ impl VectorXOperator {
             .on_http(rpc_url);
 
         Self {
+            fetcher,
             client,
             pk,
             vk,
@@ -131,8 +144,6 @@ impl VectorXOperator {
     ) -> Result<SP1ProofWithPublicValues> {
         let mut stdin: SP1Stdin = SP1Stdin::new();
 
-        let fetcher = RpcDataFetcher::new().await;
-
         let proof_type = ProofType::HeaderRangeProof;
         // Fetch the header range commitment tree size from the contract.
         let contract = SP1Vector::new(self.contract_address, self.provider.clone());
@@ -141,13 +152,19 @@ impl VectorXOperator {
             .call()
             .await
             .unwrap();
-        let header_range_inputs = fetcher
+        let header_range_inputs = self
+            .fetcher
             .get_header_range_inputs(
                 trusted_block,
                 target_block,
                 Some(output.headerRangeCommitmentTreeSize),
             )
-            .await;
+            .await?;
+
+        info!(
+            "Target justification: {}",
+            verify_justification_with_report(&header_range_inputs.target_justification)
+        );
 
         stdin.write(&proof_type);
         stdin.write(&header_range_inputs);
@@ -164,12 +181,18 @@ impl VectorXOperator {
         &self,
         current_authority_set_id: u64,
     ) -> Result<SP1ProofWithPublicValues> {
-        let fetcher = RpcDataFetcher::new().await;
-
         let mut stdin: SP1Stdin = SP1Stdin::new();
 
         let proof_type = ProofType::RotateProof;
-        let rotate_input = fetcher.get_rotate_inputs(current_authority_set_id).await;
+        let rotate_input = self
+            .fetcher
+            .get_rotate_inputs(current_authority_set_id)
+            .await?;
+
+        info!(
+            "Epoch end justification: {}",
+            verify_justification_with_report(&rotate_input.justification)
+        );
 
         stdin.write(&proof_type);
         stdin.write(&rotate_input);
@@ -186,15 +209,15 @@ impl VectorXOperator {
     async fn find_rotate(&self) -> Result<Option<u64>> {
         let rotate_contract_data = self.get_contract_data_for_rotate().await?;
 
-        let fetcher = RpcDataFetcher::new().await;
-        let head = fetcher.get_head().await;
+        let head = self.fetcher.get_head().await?;
         let head_block = head.number;
-        let head_authority_set_id = fetcher.get_authority_set_id(head_block - 1).await;
+        let head_authority_set_id = self.fetcher.get_authority_set_id(head_block - 1).await?;
 
         // The current authority set id is the authority set id of the block before the current block.
-        let current_authority_set_id = fetcher
+        let current_authority_set_id = self
+            .fetcher
             .get_authority_set_id(rotate_contract_data.current_block - 1)
-            .await;
+            .await?;
 
         if current_authority_set_id < head_authority_set_id
             && !rotate_contract_data.next_authority_set_hash_exists
@@ -208,15 +231,17 @@ impl VectorXOperator {
     async fn find_header_range(&self, ideal_block_interval: u32) -> Result<Option<(u32, u32)>> {
         let header_range_contract_data = self.get_contract_data_for_header_range().await?;
 
-        let fetcher = RpcDataFetcher::new().await;
-
         // The current authority set id is the authority set id of the block before the current block.
-        let current_authority_set_id = fetcher
+        let current_authority_set_id = self
+            .fetcher
             .get_authority_set_id(header_range_contract_data.vectorx_latest_block - 1)
-            .await;
+            .await?;
 
         // Get the last justified block by the current authority set id.
-        let last_justified_block = fetcher.last_justified_block(current_authority_set_id).await;
+        let last_justified_block = self
+            .fetcher
+            .last_justified_block(current_authority_set_id)
+            .await?;
 
         // If this is the last justified block, check for header range with next authority set.
         let mut request_authority_set_id = current_authority_set_id;
@@ -237,15 +262,15 @@ impl VectorXOperator {
 
         // Find the block to step to. If no block is returned, either 1) there is no block satisfying
         // the conditions that is available to step to or 2) something has gone wrong with the indexer.
-        let block_to_step_to = self
-            .find_block_to_step_to(
-                ideal_block_interval,
-                header_range_contract_data.header_range_commitment_tree_size,
-                header_range_contract_data.vectorx_latest_block,
-                header_range_contract_data.avail_current_block,
-                request_authority_set_id,
-            )
-            .await;
+        let block_to_step_to = find_block_to_step_to(
+            &self.fetcher,
+            ideal_block_interval,
+            header_range_contract_data.header_range_commitment_tree_size,
+            header_range_contract_data.vectorx_latest_block,
+            header_range_contract_data.avail_current_block,
+            request_authority_set_id,
+        )
+        .await?;
 
         println!("block_to_step_to: {:?}", block_to_step_to);
 
@@ -260,8 +285,6 @@ impl VectorXOperator {
 
     // Current block, step_range_max and whether next authority set hash exists.
     async fn get_contract_data_for_header_range(&self) -> Result<HeaderRangeContractData> {
-        let fetcher = RpcDataFetcher::new().await;
-
         let contract = SP1Vector::new(self.contract_address, self.provider.clone());
 
         let vectorx_latest_block = contract.latestBlock().call().await?.latestBlock;
@@ -271,10 +294,12 @@ impl VectorXOperator {
             .await?
             .headerRangeCommitmentTreeSize;
 
-        let avail_current_block = fetcher.get_head().await.number;
+        let avail_current_block = self.fetcher.get_head().await?.number;
 
-        let vectorx_current_authority_set_id =
-            fetcher.get_authority_set_id(vectorx_latest_block - 1).await;
+        let vectorx_current_authority_set_id = self
+            .fetcher
+            .get_authority_set_id(vectorx_latest_block - 1)
+            .await?;
         let next_authority_set_id = vectorx_current_authority_set_id + 1;
 
         let next_authority_set_hash = contract
@@ -321,78 +346,6 @@ impl VectorXOperator {
         })
     }
 
-    // The logic for finding the block to step to is as follows:
-    // 1. If the current epoch in the contract is not the latest epoch, step to the last justified block
-    // of the epoch.
-    // 2. If the block has a valid justification, return the block number.
-    // 3. If the block has no valid justification, return None.
-    async fn find_block_to_step_to(
-        &self,
-        ideal_block_interval: u32,
-        header_range_commitment_tree_size: u32,
-        vectorx_current_block: u32,
-        avail_current_block: u32,
-        authority_set_id: u64,
-    ) -> Option<u32> {
-        let fetcher = RpcDataFetcher::new().await;
-        let last_justified_block = fetcher.last_justified_block(authority_set_id).await;
-
-        // Step to the last justified block of the current epoch if it is in range. When the last
-        // justified block is 0, the SP1Vector contract's latest epoch is the current epoch on the
-        // Avail chain.
-        if last_justified_block != 0
-            && last_justified_block <= vectorx_current_block + header_range_commitment_tree_size
-        {
-            return Some(last_justified_block);
-        }
-
-        // The maximum valid block to step to is the either header_range_commitment_tree_size blocks
-        // ahead of the current block in the contract or the latest block on Avail.
-        let max_valid_block_to_step_to = min(
-            vectorx_current_block + header_range_commitment_tree_size,
-            avail_current_block,
-        );
-
-        println!("max_valid_block_to_step_to: {}", max_valid_block_to_step_to);
-        println!("avail_current_block: {}", avail_current_block);
-        println!("block interval: {}", ideal_block_interval);
-
-        // Find the closest block to the maximum valid block to step to that is a multiple of
-        // ideal_block_interval.
-        let mut block_to_step_to =
-            max_valid_block_to_step_to - (max_valid_block_to_step_to % ideal_block_interval);
-
-        // If block_to_step_to is <= to the current block, return None.
-        if block_to_step_to <= vectorx_current_block {
-            return None;
-        }
-
-        // Check that block_to_step_to has a valid justification. If not, iterate up until the maximum_vectorx_target_block
-        // to find a valid justification. If we're unable to find a justification, something has gone
-        // deeply wrong with the justification indexer.
-        loop {
-            if block_to_step_to > max_valid_block_to_step_to {
-                error!(
-                    "Unable to find any valid justifications after searching from block {} to block {}. This is likely caused by an issue with the justification indexer.",
-                    vectorx_current_block + ideal_block_interval,
-                    max_valid_block_to_step_to
-                );
-                return None;
-            }
-
-            if fetcher
-                .get_justification_data_for_block(block_to_step_to)
-                .await
-                .is_some()
-            {
-                break;
-            }
-            block_to_step_to += 1;
-        }
-
-        Some(block_to_step_to)
-    }
-
     /// Relay a header range proof to the SP1 SP1Vector contract.
     async fn relay_header_range(&self, proof: SP1ProofWithPublicValues) -> Result<B256> {
         // TODO: sp1_sdk should return empty bytes in mock mode.
@@ -551,13 +504,19 @@ impl VectorXOperator {
 
             // Request a rotate for the next authority set id.
             if let Some(current_authority_set_id) = current_authority_set_id {
-                let proof = self.request_rotate(current_authority_set_id).await?;
-                let tx_hash = self.relay_rotate(proof).await?;
-                info!(
-                    "Added authority set {}\nTransaction hash: {}",
-                    current_authority_set_id + 1,
-                    tx_hash
-                );
+                match self.request_rotate(current_authority_set_id).await {
+                    Ok(proof) => {
+                        let tx_hash = self.relay_rotate(proof).await?;
+                        info!(
+                            "Added authority set {}\nTransaction hash: {}",
+                            current_authority_set_id + 1,
+                            tx_hash
+                        );
+                    }
+                    Err(e) => {
+                        error!("Rotate proof generation failed: {}", e);
+                    }
+                }
             }
 
             println!("On the way for header range!");
@@ -629,6 +588,8 @@ async fn main() {
     loop {
         if let Err(e) = operator.run().await {
             error!("Error running operator: {}", e);
+            // Back off before running again, so a persistent failure does not hammer the RPCs.
+            tokio::time::sleep(ERROR_BACKOFF).await;
         }
     }
 }
@@ -22,7 +22,7 @@ async fn main() -> anyhow::Result<()> {
 
     let proof_type = ProofType::HeaderRangeProof;
 
-    let fetcher = RpcDataFetcher::new().await;
+    let fetcher = RpcDataFetcher::builder().from_env()?.build().await?;
     let mut stdin: SP1Stdin = SP1Stdin::new();
 
     // Fetch & write inputs to proof based on the proof type.
@@ -30,13 +30,13 @@ async fn main() -> anyhow::Result<()> {
         ProofType::HeaderRangeProof => {
             let header_range_inputs = fetcher
                 .get_header_range_inputs(trusted_block, target_block, Some(512))
-                .await;
+                .await?;
 
             stdin.write(&proof_type);
             stdin.write(&header_range_inputs);
         }
         ProofType::RotateProof => {
-            let rotate_input = fetcher.get_rotate_inputs(authority_set_id).await;
+            let rotate_input = fetcher.get_rotate_inputs(authority_set_id).await?;
 
             stdin.write(&proof_type);
             stdin.write(&rotate_input);
@@ -1,3 +1,4 @@
+pub mod planner;
 pub mod relay;
 
 #[cfg(test)]
@@ -7,7 +8,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_justification_query_service() -> Result<()> {
-        let client = RpcDataFetcher::new().await;
+        let client = RpcDataFetcher::new().await?;
         let justification = client.get_justification(337281).await?;
         println!("Justification: {:?}", justification);
         Ok(())
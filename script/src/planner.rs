@@ -0,0 +1,190 @@
+use std::cmp::min;
+
+use log::{error, info, warn};
+use services::error::FetchError;
+use services::input::RpcDataFetcher;
+use services::source::AvailDataSource;
+use services::store::Prefer;
+
+// The logic for finding the block to step to is as follows:
+// 1. If the current epoch in the contract is not the latest epoch, step to the last justified block
+// of the epoch.
+// 2. If the block has a valid justification, return the block number.
+// 3. If the block has no valid justification, return None.
+// Errors other than a missing justification are returned.
+pub async fn find_block_to_step_to<S: AvailDataSource>(
+    fetcher: &RpcDataFetcher<S>,
+    ideal_block_interval: u32,
+    header_range_commitment_tree_size: u32,
+    vectorx_current_block: u32,
+    avail_current_block: u32,
+    authority_set_id: u64,
+) -> Result<Option<u32>, FetchError> {
+    let last_justified_block = fetcher.last_justified_block(authority_set_id).await?;
+
+    // Step to the last justified block of the current epoch if it is in range. When the last
+    // justified block is 0, the SP1Vector contract's latest epoch is the current epoch on the
+    // Avail chain.
+    if last_justified_block != 0
+        && last_justified_block <= vectorx_current_block + header_range_commitment_tree_size
+    {
+        return Ok(Some(last_justified_block));
+    }
+
+    // The maximum valid block to step to is the either header_range_commitment_tree_size blocks
+    // ahead of the current block in the contract or the latest block on Avail.
+    let max_valid_block_to_step_to = min(
+        vectorx_current_block + header_range_commitment_tree_size,
+        avail_current_block,
+    );
+
+    info!(
+        "max_valid_block_to_step_to: {}, avail_current_block: {}, block interval: {}",
+        max_valid_block_to_step_to, avail_current_block, ideal_block_interval
+    );
+
+    // Find the closest block to the maximum valid block to step to that is a multiple of
+    // ideal_block_interval.
+    let block_to_step_to =
+        max_valid_block_to_step_to - (max_valid_block_to_step_to % ideal_block_interval);
+
+    // If block_to_step_to is <= to the current block, return None.
+    if block_to_step_to <= vectorx_current_block {
+        return Ok(None);
+    }
+
+    // Step to the first justified block at or after block_to_step_to whose justification can be
+    // converted with its authority set. Stored justifications that cannot are skipped, so a bad
+    // record does not stall the operator. If there is none up to the maximum valid block,
+    // something has gone deeply wrong with the justification indexer.
+    let mut search_from = block_to_step_to;
+    while let Some(candidate) = fetcher
+        .nearest_justified_block(search_from, max_valid_block_to_step_to, Prefer::Lowest)
+        .await?
+    {
+        match fetcher.get_justification_data_for_block(candidate).await {
+            Ok(_) => return Ok(Some(candidate)),
+            Err(e) if !e.is_retryable() => {
+                warn!("Skipping the justification for block {}: {}", candidate, e);
+                search_from = candidate + 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    error!(
+        "Unable to find any valid justifications after searching from block {} to block {}. This is likely caused by an issue with the justification indexer, see `indexer coverage` for the gaps.",
+        vectorx_current_block + ideal_block_interval,
+        max_valid_block_to_step_to
+    );
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::B256;
+    use avail_subxt::primitives::Header;
+    use serde_json::json;
+    use services::source::{Fixture, FixtureDataSource};
+    use services::types::GrandpaJustification;
+
+    const ZERO_HASH: &str = "0x0000000000000000000000000000000000000000000000000000000000000000";
+
+    fn test_header(number: u32) -> Header {
+        serde_json::from_value(json!({
+            "parentHash": ZERO_HASH,
+            "number": format!("{:#x}", number),
+            "stateRoot": ZERO_HASH,
+            "extrinsicsRoot": ZERO_HASH,
+            "digest": { "logs": [] },
+            "extension": { "V3": {
+                "appLookup": { "size": 0, "index": [] },
+                "commitment": { "rows": 0, "cols": 0, "commitment": [], "dataRoot": ZERO_HASH }
+            } }
+        }))
+        .unwrap()
+    }
+
+    fn test_justification(number: u32) -> GrandpaJustification {
+        serde_json::from_value(json!({
+            "round": 1,
+            "commit": { "target_hash": ZERO_HASH, "target_number": number, "precommits": [] },
+            "votes_ancestries": []
+        }))
+        .unwrap()
+    }
+
+    /// A chain with head 100 where authority set 0 justifies blocks up to 40 and set 1 is still
+    /// active. Only blocks 40 and 63 have stored justifications.
+    fn test_fetcher() -> RpcDataFetcher<FixtureDataSource> {
+        let mut fixture = Fixture::default();
+        for block_number in 0..=100 {
+            let set_id = if block_number < 40 { 0 } else { 1 };
+            fixture
+                .headers
+                .insert(block_number, test_header(block_number));
+            fixture.authority_set_ids.insert(block_number, set_id);
+            fixture
+                .authorities
+                .insert(block_number, vec![B256::repeat_byte(1)]);
+        }
+        for block_number in [40, 63] {
+            fixture
+                .justifications
+                .insert(block_number, test_justification(block_number));
+        }
+        RpcDataFetcher::with_source(FixtureDataSource::new(fixture), "test")
+    }
+
+    #[tokio::test]
+    async fn test_steps_to_last_justified_block_of_epoch() {
+        let fetcher = test_fetcher();
+        assert_eq!(
+            find_block_to_step_to(&fetcher, 20, 32, 20, 100, 0)
+                .await
+                .unwrap(),
+            Some(40)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_steps_to_next_justified_block() {
+        let fetcher = test_fetcher();
+        // Blocks 60 to 62 have no justification.
+        assert_eq!(
+            find_block_to_step_to(&fetcher, 20, 64, 30, 70, 1)
+                .await
+                .unwrap(),
+            Some(63)
+        );
+        // No justification between 60 and the maximum block 61.
+        assert_eq!(
+            find_block_to_step_to(&fetcher, 20, 16, 45, 100, 1)
+                .await
+                .unwrap(),
+            None
+        );
+        // The closest multiple of the interval is not after the current block.
+        assert_eq!(
+            find_block_to_step_to(&fetcher, 20, 64, 64, 70, 1)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_skips_justifications_that_fail_conversion() {
+        let mut fixture = test_fetcher().source.fixture().clone();
+        // The justification of block 61 is stored, but its authority set cannot be resolved.
+        fixture.justifications.insert(61, test_justification(61));
+        fixture.authorities.remove(&60);
+        let fetcher = RpcDataFetcher::with_source(FixtureDataSource::new(fixture), "test");
+        assert_eq!(
+            find_block_to_step_to(&fetcher, 20, 64, 30, 70, 1)
+                .await
+                .unwrap(),
+            Some(63)
+        );
+    }
+}
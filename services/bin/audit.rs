@@ -0,0 +1,187 @@
+//! Audits the commitments stored by SP1Vector deployments against Avail, independently of the
+//! operator, see `services::audit`.
+//!
+//!     `cargo run --bin audit -- --deployments <file>` audits every committed range once, prints
+//!     a JSON report for each deployment and exits with status 1 if any mismatch was found.
+//!     `cargo run --bin audit -- --deployments <file> --follow` keeps auditing the commitments and
+//!     authority sets stored since the last audit, including ranges and authority sets the
+//!     guardian stored again, and prints a report whenever one was checked.
+//!
+//! The deployments file has the format of `query/app/utils/deployments.json`. The Avail chains are
+//! configured from the environment, or with `--config <file>` (or INDEXER_CONFIG) from the same
+//! file as the indexer. The Ethereum RPC of each contract chain is read from RPC_<chain id>.
+//!
+//! The committed ranges are read from the database written by the `contract-indexer` binary with
+//! `--events-db <file>` (or CONTRACT_EVENTS_DB). Without one, the events are indexed into memory
+//! before each audit.
+//!
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use clap::Parser;
+use log::warn;
+use services::audit::{audit, AuditReport};
+use services::contract::ContractClient;
+use services::events::{
+    sync, ContractEventIndex, Deployment, Deployments, LogPosition, DEFAULT_CONFIRMATIONS,
+};
+use services::indexer::IndexerConfig;
+use services::input::RpcDataFetcher;
+
+#[derive(Parser, Debug, Clone)]
+#[command(about = "Recompute the commitments of SP1Vector deployments from Avail.")]
+struct AuditArgs {
+    /// JSON file listing the deployments. Defaults to CONTRACT_DEPLOYMENTS.
+    #[arg(long)]
+    deployments: Option<PathBuf>,
+    /// JSON file listing the Avail chains, in the indexer config format. Without one, a single
+    /// chain is configured from the environment.
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Contract event database written by the contract indexer.
+    #[arg(long)]
+    events_db: Option<PathBuf>,
+    /// Only audit the ranges that end after this Avail block.
+    #[arg(long, default_value_t = 0)]
+    from: u32,
+    /// Only index Ethereum blocks at least this deep, when no event database is given.
+    #[arg(long, default_value_t = DEFAULT_CONFIRMATIONS)]
+    confirmations: u64,
+    /// Keep auditing new commitments.
+    #[arg(long)]
+    follow: bool,
+}
+
+/// Interval at which new commitments are audited with `--follow`.
+const POLL_INTERVAL: Duration = Duration::from_secs(300);
+
+async fn fetchers(config: Option<PathBuf>) -> anyhow::Result<HashMap<String, RpcDataFetcher>> {
+    let config = config.or_else(|| env::var("INDEXER_CONFIG").ok().map(PathBuf::from));
+    let Some(config) = config else {
+        let fetcher = RpcDataFetcher::new().await?;
+        return Ok(HashMap::from([(
+            fetcher.avail_chain_id.to_lowercase(),
+            fetcher,
+        )]));
+    };
+    let mut fetchers = HashMap::new();
+    for chain in IndexerConfig::load(config)?.chains {
+        let fetcher = chain.fetcher_builder().build().await?;
+        fetchers.insert(chain.avail_chain_id.to_lowercase(), fetcher);
+    }
+    Ok(fetchers)
+}
+
+/// Audit the ranges of `deployment` that end after block `from` and its authority sets, as far as
+/// they were stored after the event at `after`.
+async fn audit_deployment(
+    deployment: &Deployment,
+    fetcher: &RpcDataFetcher,
+    client: &ContractClient,
+    index: &ContractEventIndex,
+    confirmations: Option<u64>,
+    from: u32,
+    after: Option<LogPosition>,
+) -> anyhow::Result<AuditReport> {
+    if let Some(confirmations) = confirmations {
+        sync(client, index, deployment, confirmations).await?;
+    }
+    Ok(audit(
+        fetcher,
+        client,
+        index,
+        deployment.id(),
+        from.saturating_add(1)..u32::MAX,
+        after,
+    )
+    .await?)
+}
+
+#[tokio::main]
+pub async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let args = AuditArgs::parse();
+    let deployments = match args.deployments {
+        Some(deployments) => deployments,
+        None => PathBuf::from(env::var("CONTRACT_DEPLOYMENTS")?),
+    };
+    let events_db = args
+        .events_db
+        .or_else(|| env::var("CONTRACT_EVENTS_DB").ok().map(PathBuf::from));
+    // Without the indexer's database, the events are synced into memory before each audit.
+    let (index, confirmations) = match events_db {
+        Some(events_db) => (ContractEventIndex::open(events_db)?, None),
+        None => (
+            ContractEventIndex::open_in_memory()?,
+            Some(args.confirmations),
+        ),
+    };
+
+    let deployments = Deployments::load(deployments)?.deployments;
+    let fetchers = fetchers(args.config).await?;
+    let mut audited = Vec::new();
+    for deployment in deployments {
+        let Some(fetcher) = fetchers.get(&deployment.avail_chain_id.to_lowercase()) else {
+            return Err(anyhow!(
+                "No Avail chain {} configured for {}:{}",
+                deployment.avail_chain_id,
+                deployment.contract_chain_id,
+                deployment.contract_address
+            ));
+        };
+        let client =
+            ContractClient::from_env(deployment.contract_chain_id, deployment.contract_address)?;
+        audited.push((deployment, fetcher, client, None));
+    }
+
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        let mut mismatches = 0;
+        for (deployment, fetcher, client, after) in &mut audited {
+            let report = match audit_deployment(
+                deployment,
+                fetcher,
+                client,
+                &index,
+                confirmations,
+                args.from,
+                *after,
+            )
+            .await
+            {
+                Ok(report) => report,
+                Err(e) if args.follow => {
+                    warn!(
+                        "[{}:{}] Audit failed: {}",
+                        deployment.contract_chain_id, deployment.contract_address, e
+                    );
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            mismatches += report.mismatches.len();
+            // Resume after the last event in the index, so events stored since are audited even
+            // if they rewrite a range or an authority set that was already audited.
+            *after = report.last_position;
+            if !args.follow || report.ranges_checked > 0 || report.authority_sets_checked > 0 {
+                println!("{}", serde_json::to_string(&report)?);
+            }
+        }
+
+        if !args.follow {
+            if mismatches > 0 {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        if mismatches > 0 {
+            warn!("Found {} mismatches", mismatches);
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! Indexes the `HeadUpdate`, `HeaderRangeCommitmentStored` and `AuthoritySetStored` events of
+//! SP1Vector deployments into SQLite, see `services::events`.
+//!
+//!     `cargo run --bin contract-indexer -- --deployments <file>` follows the deployments.
+//!     `cargo run --bin contract-indexer -- --deployments <file> --once` indexes the blocks
+//!     confirmed so far and prints a JSON report for each deployment.
+//!
+//! The deployments file has the format of `query/app/utils/deployments.json`. The Ethereum RPC of
+//! each contract chain is read from RPC_<chain id>. Each deployment is synced in its own task, so
+//! a failing RPC does not affect the others.
+//!
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::Parser;
+use log::{info, warn};
+use services::contract::ContractClient;
+use services::events::{sync, ContractEventIndex, Deployment, Deployments, DEFAULT_CONFIRMATIONS};
+
+#[derive(Parser, Debug, Clone)]
+#[command(about = "Index the events of SP1Vector deployments into SQLite.")]
+struct ContractIndexerArgs {
+    /// JSON file listing the deployments. Defaults to CONTRACT_DEPLOYMENTS.
+    #[arg(long)]
+    deployments: Option<PathBuf>,
+    /// Database file. Defaults to CONTRACT_EVENTS_DB, or contract_events.db.
+    #[arg(long)]
+    db: Option<PathBuf>,
+    /// Only index Ethereum blocks at least this deep.
+    #[arg(long, default_value_t = DEFAULT_CONFIRMATIONS)]
+    confirmations: u64,
+    /// Index the confirmed blocks once and exit.
+    #[arg(long)]
+    once: bool,
+}
+
+/// Interval at which each deployment is synced.
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+const DEFAULT_DB: &str = "contract_events.db";
+
+async fn follow(
+    deployment: Deployment,
+    client: ContractClient,
+    index: Arc<ContractEventIndex>,
+    confirmations: u64,
+) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        match sync(&client, &index, &deployment, confirmations).await {
+            Ok(report) if report.events > 0 || report.rolled_back_from.is_some() => info!(
+                "[{}:{}] Indexed {} events in Ethereum blocks {:?}",
+                deployment.contract_chain_id,
+                deployment.contract_address,
+                report.events,
+                report.blocks
+            ),
+            Ok(_) => {}
+            Err(e) => warn!(
+                "[{}:{}] Sync failed: {}",
+                deployment.contract_chain_id, deployment.contract_address, e
+            ),
+        }
+    }
+}
+
+#[tokio::main]
+pub async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let args = ContractIndexerArgs::parse();
+    let deployments = match args.deployments {
+        Some(deployments) => deployments,
+        None => PathBuf::from(env::var("CONTRACT_DEPLOYMENTS")?),
+    };
+    let db = args.db.unwrap_or_else(|| {
+        PathBuf::from(env::var("CONTRACT_EVENTS_DB").unwrap_or_else(|_| DEFAULT_DB.to_string()))
+    });
+
+    let deployments = Deployments::load(deployments)?.deployments;
+    let index = Arc::new(ContractEventIndex::open(db)?);
+    let mut clients = Vec::new();
+    for deployment in &deployments {
+        clients.push(ContractClient::from_env(
+            deployment.contract_chain_id,
+            deployment.contract_address,
+        )?);
+    }
+
+    if args.once {
+        for (deployment, client) in deployments.iter().zip(&clients) {
+            let report = sync(client, &index, deployment, args.confirmations).await?;
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&serde_json::json!({
+                    "deployment": deployment,
+                    "report": report,
+                }))?
+            );
+        }
+        return Ok(());
+    }
+
+    let mut tasks = Vec::new();
+    for (deployment, client) in deployments.into_iter().zip(clients) {
+        tasks.push(tokio::spawn(follow(
+            deployment,
+            client,
+            index.clone(),
+            args.confirmations,
+        )));
+    }
+    futures::future::join_all(tasks).await;
+    Ok(())
+}
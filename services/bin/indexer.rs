@@ -1,15 +1,142 @@
+//! Indexes GRANDPA justifications into the configured justification store.
+//!
+//!     `cargo run --bin indexer` follows new justifications. It reconnects when the subscription
+//!     fails and fills the blocks finalized while it was disconnected.
+//!     `cargo run --bin indexer -- backfill --from <block> --to <block>` recovers past ones.
+//!     `cargo run --bin indexer -- coverage --from <block> --to <block>` reports missing ones.
+//!     `cargo run --bin indexer -- prune --dry-run` reports the ones the retention policy drops.
+//!
+//! The chain is configured from the environment, or with `--config <file>` (or INDEXER_CONFIG)
+//! several chains are indexed at once, see `services::indexer`. Each chain runs in its own task,
+//! so a failing chain does not affect the others.
+//!
+//! Chains with a retention policy are pruned every hour while listening, see
+//! `services::retention`.
+//!
+//! With `--api-addr <addr>` (or INDEXER_API_ADDR), the stored justifications and the status of
+//! each chain are also served over HTTP, see `services::api`. While listening, coverage reports
+//! are served from the justified blocks kept in memory, see `services::coverage`.
+//!
 use avail_subxt::primitives::Header;
 use avail_subxt::RpcParams;
+use clap::{Parser, Subcommand};
 use codec::Decode;
-use log::debug;
+use log::{debug, error, info, warn};
 use serde::de::Error;
 use serde::Deserialize;
-use services::aws::AWSClient;
-use services::input::RpcDataFetcher;
+use services::api::{self, Api};
+use services::backfill::{backfill, fill_gap, BackfillReport};
+use services::coverage::{epoch_ends, CoverageReport, CoverageTracker};
+use services::error::FetchError;
+use services::indexer::{ChainState, IndexerConfig, IndexerStatus};
+use services::input::{RetryPolicy, RpcDataFetcher, RpcDataFetcherBuilder};
+use services::retention::{prune, PruneReport, RetentionPolicy};
+use services::store::{put_with_retry, JustificationStore, StoreConfig};
 use services::types::{Commit, GrandpaJustification};
 use sp_core::bytes;
+use std::env;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 use subxt::backend::rpc::RpcSubscription;
 
+#[derive(Parser, Debug, Clone)]
+#[command(about = "Index GRANDPA justifications from the Avail RPC.")]
+struct IndexerArgs {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Serve the justification API on this address, e.g. 0.0.0.0:8080.
+    #[arg(long)]
+    api_addr: Option<SocketAddr>,
+    /// JSON file listing the chains to index. Without one, a single chain is configured from the
+    /// environment.
+    #[arg(long)]
+    config: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Store justifications as they are finalized. This is the default.
+    Listen,
+    /// Store the justifications the node can prove for a range of past blocks.
+    Backfill {
+        #[arg(long)]
+        from: u32,
+        #[arg(long)]
+        to: u32,
+        /// Chain to backfill. Required if the config lists several chains.
+        #[arg(long)]
+        chain: Option<String>,
+    },
+    /// Print a JSON report of the blocks with stored justifications in a range, with the gaps
+    /// and the epoch end blocks without one. Exits with status 1 if anything is missing.
+    Coverage {
+        #[arg(long)]
+        from: u32,
+        #[arg(long)]
+        to: u32,
+        /// Chain to report on. Required if the config lists several chains.
+        #[arg(long)]
+        chain: Option<String>,
+        /// Shortest run of blocks without a justification to report as a gap.
+        #[arg(long, default_value_t = 1)]
+        min_gap: u32,
+    },
+    /// Delete the justifications that the retention policy of a chain drops and print a JSON
+    /// report of them.
+    Prune {
+        /// Chain to prune. Required if the config lists several chains.
+        #[arg(long)]
+        chain: Option<String>,
+        /// First block to consider.
+        #[arg(long, default_value_t = 0)]
+        from: u32,
+        /// Only report what would be deleted.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// Interval at which the status of every chain is logged.
+const STATUS_LOG_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Interval at which chains with a retention policy are pruned.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// A chain to index.
+#[derive(Clone)]
+struct IndexedChain {
+    avail_chain_id: String,
+    builder: RpcDataFetcherBuilder,
+    store: Arc<dyn JustificationStore>,
+    retention: Option<RetentionPolicy>,
+}
+
+/// The chains listed in the config file, or the single chain configured from the environment.
+async fn indexed_chains(config: Option<PathBuf>) -> anyhow::Result<Vec<IndexedChain>> {
+    let config = config.or_else(|| env::var("INDEXER_CONFIG").ok().map(PathBuf::from));
+    let Some(config) = config else {
+        // Justifications are written to DynamoDB unless another store is configured.
+        return Ok(vec![IndexedChain {
+            avail_chain_id: env::var("AVAIL_CHAIN_ID")?,
+            builder: RpcDataFetcher::builder().from_env()?,
+            store: StoreConfig::from_env()?.unwrap_or_default().open().await?,
+            retention: RetentionPolicy::from_env()?,
+        }]);
+    };
+    let mut chains = Vec::new();
+    for chain in IndexerConfig::load(config)?.chains {
+        chains.push(IndexedChain {
+            builder: chain.fetcher_builder(),
+            store: chain.store.open().await?,
+            retention: chain.retention,
+            avail_chain_id: chain.avail_chain_id,
+        });
+    }
+    Ok(chains)
+}
+
 /// The justification type that the Avail Subxt client returns for justifications. Needs a custom
 /// deserializer, so we can't use the equivalent `GrandpaJustification` type.
 #[derive(Clone, Debug, Decode)]
@@ -41,39 +168,342 @@ impl<'de> Deserialize<'de> for AvailSubscriptionGrandpaJustification {
     }
 }
 
-async fn listen_for_justifications(fetcher: RpcDataFetcher, aws_client: AWSClient) {
-    let sub: Result<RpcSubscription<AvailSubscriptionGrandpaJustification>, _> = fetcher
-        .client
+fn log_report(avail_chain_id: &str, report: &BackfillReport) {
+    info!(
+        "[{}] Stored {} justifications, {} were already stored",
+        avail_chain_id,
+        report.stored.len(),
+        report.already_stored.len()
+    );
+    for range in &report.unrecovered {
+        warn!(
+            "[{}] Could not recover justifications for blocks {}..={}",
+            avail_chain_id,
+            range.start(),
+            range.end()
+        );
+    }
+}
+
+/// Connect, fill the blocks finalized since the latest stored justification and store new
+/// justifications until the subscription or the store fails. `backoff` is reset once the
+/// subscription is established.
+async fn listen_for_justifications(
+    chain: &IndexedChain,
+    status: &IndexerStatus,
+    retry_policy: &RetryPolicy,
+    backoff: &mut Duration,
+) -> Result<(), FetchError> {
+    let avail_chain_id = &chain.avail_chain_id;
+    let store = chain.store.as_ref();
+    status.update(avail_chain_id, |status| {
+        status.state = ChainState::Connecting
+    });
+    let fetcher = chain.builder.clone().build().await?;
+    let mut sub: RpcSubscription<AvailSubscriptionGrandpaJustification> = fetcher
+        .client()
         .rpc()
         .subscribe(
             "grandpa_subscribeJustifications",
             RpcParams::new(),
             "grandpa_unsubscribeJustifications",
         )
-        .await;
-    let mut sub = sub.unwrap();
+        .await
+        .map_err(|e| FetchError::Rpc(e.to_string()))?;
+    *backoff = retry_policy.initial_backoff;
+
+    // Subscribe before filling the gap, so no justification is missed in between.
+    if let Some(latest) = store.latest(avail_chain_id).await? {
+        let report = fill_gap(&fetcher, store, latest.block_number(), retry_policy).await?;
+        log_report(avail_chain_id, &report);
+    }
+    status.update(avail_chain_id, |status| {
+        status.state = ChainState::Listening
+    });
 
     // Wait for new justification.
-    while let Some(Ok(justification)) = sub.next().await {
+    while let Some(justification) = sub.next().await {
+        let justification: GrandpaJustification = justification
+            .map_err(|e| FetchError::Rpc(e.to_string()))?
+            .into();
+        let block_number = justification.commit.target_number;
         debug!(
-            "New justification from block {}",
-            justification.commit.target_number
+            "[{}] New justification from block {}",
+            avail_chain_id, block_number
         );
 
-        aws_client
-            .add_justification(&fetcher.avail_chain_id, justification.into())
+        // Reject justifications that do not verify against the authority set of their block, so
+        // a faulty node cannot poison the store.
+        let record = match fetcher.validate_justification(justification).await {
+            Ok(record) => record,
+            Err(FetchError::Verification(e)) => {
+                error!(
+                    "[{}] Rejected invalid justification for block {}: {}",
+                    avail_chain_id, block_number, e
+                );
+                status.update(avail_chain_id, |status| status.rejected += 1);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        put_with_retry(store, avail_chain_id, &record, retry_policy).await?;
+        status.update(avail_chain_id, |status| {
+            status.indexed += 1;
+            status.last_indexed_block = status.last_indexed_block.max(Some(block_number));
+        });
+    }
+    Err(FetchError::Rpc(
+        "Justification subscription ended".to_string(),
+    ))
+}
+
+/// The chain named `avail_chain_id`, or the only configured chain if it is not set.
+fn select_chain(
+    chains: Vec<IndexedChain>,
+    avail_chain_id: Option<String>,
+) -> anyhow::Result<IndexedChain> {
+    match avail_chain_id {
+        Some(avail_chain_id) => chains
+            .into_iter()
+            .find(|chain| chain.avail_chain_id.eq_ignore_ascii_case(&avail_chain_id))
+            .ok_or_else(|| anyhow::anyhow!("Chain {} is not configured", avail_chain_id)),
+        None if chains.len() == 1 => Ok(chains.into_iter().next().unwrap()),
+        None => anyhow::bail!("--chain is required when several chains are configured"),
+    }
+}
+
+/// Follow the justifications of `chain` forever, reconnecting with backoff when it fails.
+async fn index_chain(chain: IndexedChain, status: IndexerStatus) {
+    let retry_policy = RetryPolicy::default();
+    let mut backoff = retry_policy.initial_backoff;
+    loop {
+        if let Err(e) =
+            listen_for_justifications(&chain, &status, &retry_policy, &mut backoff).await
+        {
+            warn!(
+                "[{}] Indexer disconnected: {}. Reconnecting in {:?}.",
+                chain.avail_chain_id, e, backoff
+            );
+            status.update(&chain.avail_chain_id, |status| {
+                status.state = ChainState::Disconnected;
+                status.reconnects += 1;
+                status.last_error = Some(e.to_string());
+            });
+        }
+        tokio::time::sleep(backoff).await;
+        backoff = std::cmp::min(backoff * 2, retry_policy.max_backoff);
+    }
+}
+
+/// Run `index_chain` in its own task, restarting it if it panics.
+async fn supervise_chain(chain: IndexedChain, status: IndexerStatus) {
+    loop {
+        let task = tokio::spawn(index_chain(chain.clone(), status.clone()));
+        if let Err(e) = task.await {
+            error!(
+                "[{}] Indexer task failed: {}. Restarting.",
+                chain.avail_chain_id, e
+            );
+            status.update(&chain.avail_chain_id, |status| {
+                status.state = ChainState::Disconnected;
+                status.reconnects += 1;
+                status.last_error = Some(e.to_string());
+            });
+        }
+        tokio::time::sleep(RetryPolicy::default().max_backoff).await;
+    }
+}
+
+fn log_prune_report(report: &PruneReport) {
+    info!(
+        "[{}] Pruned {} justifications in blocks {}..{}, kept {}",
+        report.avail_chain_id,
+        report.deleted.len(),
+        report.blocks.start,
+        report.blocks.end,
+        report.kept
+    );
+}
+
+/// Prune `chain` with `retention` every `PRUNE_INTERVAL`. Each run starts where the previous one
+/// ended, so old blocks are only read once. The fetcher is reused across runs and only rebuilt
+/// after a run failed.
+async fn prune_chain(chain: IndexedChain, retention: RetentionPolicy) {
+    let mut start_block = 0;
+    let mut fetcher = None;
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let result = async {
+            let fetcher = match &mut fetcher {
+                Some(fetcher) => fetcher,
+                None => fetcher.insert(chain.builder.clone().build().await?),
+            };
+            prune(
+                fetcher,
+                chain.store.as_ref(),
+                &chain.avail_chain_id,
+                &retention,
+                start_block,
+                false,
+            )
             .await
-            .unwrap();
+        }
+        .await;
+        match result {
+            Ok(report) => {
+                log_prune_report(&report);
+                // Start the next run at the run of blocks the cutoff fell into.
+                start_block = report.blocks.end - report.blocks.end % retention.keep_every;
+            }
+            Err(e) => {
+                warn!(
+                    "[{}] Failed to prune justifications: {}. Retrying in {:?}.",
+                    chain.avail_chain_id, e, PRUNE_INTERVAL
+                );
+                // The connection may be broken, so reconnect on the next run.
+                fetcher = None;
+            }
+        }
+    }
+}
+
+fn log_status(status: &IndexerStatus) {
+    for (avail_chain_id, status) in status.snapshot() {
+        info!(
+            "[{}] {:?}, last indexed block {:?}, {} indexed, {} rejected, {} reconnects",
+            avail_chain_id,
+            status.state,
+            status.last_indexed_block,
+            status.indexed,
+            status.rejected,
+            status.reconnects
+        );
     }
 }
 
 #[tokio::main]
-pub async fn main() {
+pub async fn main() -> anyhow::Result<()> {
     dotenv::dotenv().ok();
     env_logger::init();
 
-    let fetcher = RpcDataFetcher::new().await;
-    let aws_client = AWSClient::new().await;
+    let args = IndexerArgs::parse();
+
+    let command = args.command.unwrap_or(Command::Listen);
+    let mut chains = indexed_chains(args.config).await?;
+    let status = IndexerStatus::default();
+    // While listening, the justified blocks of each chain are kept in memory for coverage reports
+    // and pruning.
+    if matches!(command, Command::Listen) {
+        for chain in &mut chains {
+            chain.store = Arc::new(CoverageTracker::new(chain.store.clone()));
+        }
+    }
 
-    listen_for_justifications(fetcher, aws_client).await;
+    let api_addr = match args.api_addr {
+        Some(api_addr) => Some(api_addr),
+        None => env::var("INDEXER_API_ADDR")
+            .ok()
+            .map(|api_addr| api_addr.parse())
+            .transpose()?,
+    };
+    if let Some(api_addr) = api_addr {
+        let mut api = Api::new(
+            chains
+                .iter()
+                .map(|chain| (chain.avail_chain_id.clone(), chain.store.clone()))
+                .collect(),
+        )
+        .with_status(status.clone());
+        for chain in &chains {
+            api = api.with_fetcher(&chain.avail_chain_id, chain.builder.clone());
+        }
+        let router = api.router();
+        tokio::spawn(async move {
+            if let Err(e) = api::serve(api_addr, router).await {
+                error!("Justification API stopped: {}", e);
+            }
+        });
+    }
+
+    match command {
+        Command::Listen => {
+            for chain in chains {
+                if let Some(retention) = chain.retention {
+                    tokio::spawn(prune_chain(chain.clone(), retention));
+                }
+                tokio::spawn(supervise_chain(chain, status.clone()));
+            }
+            let mut interval = tokio::time::interval(STATUS_LOG_INTERVAL);
+            loop {
+                interval.tick().await;
+                log_status(&status);
+            }
+        }
+        Command::Backfill { from, to, chain } => {
+            let chain = select_chain(chains, chain)?;
+            let fetcher = chain.builder.build().await?;
+            let report = backfill(
+                &fetcher,
+                chain.store.as_ref(),
+                from,
+                to,
+                &RetryPolicy::default(),
+            )
+            .await?;
+            info!(
+                "[{}] Backfilled blocks {}..={}",
+                chain.avail_chain_id, from, to
+            );
+            log_report(&chain.avail_chain_id, &report);
+        }
+        Command::Coverage {
+            from,
+            to,
+            chain,
+            min_gap,
+        } => {
+            let chain = select_chain(chains, chain)?;
+            let fetcher = chain.builder.build().await?;
+            let justified = chain
+                .store
+                .justified_blocks(&chain.avail_chain_id, from, to)
+                .await?;
+            let epoch_ends = epoch_ends(&fetcher, from, to).await?;
+            let report = CoverageReport::new(
+                chain.avail_chain_id,
+                from,
+                to,
+                justified,
+                min_gap,
+                &epoch_ends,
+            );
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            if !report.is_complete() {
+                std::process::exit(1);
+            }
+        }
+        Command::Prune {
+            chain,
+            from,
+            dry_run,
+        } => {
+            let chain = select_chain(chains, chain)?;
+            let retention = chain.retention.ok_or_else(|| {
+                anyhow::anyhow!("Chain {} has no retention policy", chain.avail_chain_id)
+            })?;
+            let fetcher = chain.builder.build().await?;
+            let report = prune(
+                &fetcher,
+                chain.store.as_ref(),
+                &chain.avail_chain_id,
+                &retention,
+                from,
+                dry_run,
+            )
+            .await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+    }
+    Ok(())
 }
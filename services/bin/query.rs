@@ -0,0 +1,252 @@
+//! Serves Merkle proofs that the data root of an Avail block is included in a data commitment of
+//! an SP1Vector contract. This is the data root proof route of the `query` service, with the same
+//! parameters and response:
+//!
+//!     GET /api?chainName=<chain>&contractChainId=<id>&contractAddress=<address>&blockNumber=<n>
+//!
+//! `blockHash=<hash>` may be passed instead of `blockNumber`. The response is
+//! `{"data": <proof>}`, see `services::proofs::DataRootProof`, or `{"success": false, "error": ..}`.
+//!
+//! `GET /api/state` takes the same parameters and proves the state root of the block against the
+//! state commitment instead, see `services::proofs::StateRootProof`.
+//!
+//! The Avail chains are configured from the environment, or with `--config <file>` (or
+//! INDEXER_CONFIG) from the same file as the indexer. The Ethereum RPC of each contract chain is
+//! read from RPC_<chain id>. Commitments read from the contract and the data roots of recently
+//! proven ranges are cached, so repeated queries do not refetch them.
+//!
+//! With `--events-db <file>` (or CONTRACT_EVENTS_DB), commitments are looked up in the database
+//! written by the `contract-indexer` binary first, see `services::events`.
+//!
+use std::collections::HashMap;
+use std::env;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use alloy_primitives::{Address, B256};
+use axum::extract::rejection::QueryRejection;
+use axum::extract::{Query, State};
+use axum::http::{header::HeaderName, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use clap::Parser;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use services::contract::{CommitmentIndex, ContractClient};
+use services::error::FetchError;
+use services::events::{ContractEventIndex, ContractId};
+use services::indexer::IndexerConfig;
+use services::input::RpcDataFetcher;
+use services::proofs::{BlockId, CommitmentProver};
+
+#[derive(Parser, Debug, Clone)]
+#[command(
+    about = "Serve data and state root proofs against the commitments of SP1Vector contracts."
+)]
+struct QueryArgs {
+    /// Address to serve on. Defaults to QUERY_ADDR, or 0.0.0.0:8080.
+    #[arg(long)]
+    addr: Option<SocketAddr>,
+    /// JSON file listing the Avail chains to serve, in the indexer config format. Without one, a
+    /// single chain is configured from the environment.
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Contract event database written by the contract indexer.
+    #[arg(long)]
+    events_db: Option<PathBuf>,
+}
+
+const DEFAULT_ADDR: &str = "0.0.0.0:8080";
+
+/// Responses are cached by the CDN for a day, since a committed range never changes.
+const CDN_CACHE_CONTROL: (HeaderName, &str) = (
+    HeaderName::from_static("cdn-cache-control"),
+    "public, max-age=86400",
+);
+
+/// Commitment indexes by contract.
+type Contracts = HashMap<ContractId, Arc<CommitmentIndex>>;
+
+#[derive(Clone)]
+struct QueryState {
+    /// Provers by lowercased Avail chain id.
+    provers: Arc<HashMap<String, CommitmentProver>>,
+    /// Created on first use.
+    contracts: Arc<Mutex<Contracts>>,
+    /// Index of contract events, if configured.
+    events: Option<Arc<ContractEventIndex>>,
+}
+
+impl QueryState {
+    fn contract(&self, id: ContractId) -> Result<Arc<CommitmentIndex>, FetchError> {
+        let mut contracts = self.contracts.lock().unwrap();
+        if let Some(contract) = contracts.get(&id) {
+            return Ok(contract.clone());
+        }
+        let mut contract = CommitmentIndex::new(ContractClient::from_env(id.chain_id, id.address)?);
+        if let Some(events) = &self.events {
+            contract = contract.with_events(events.clone(), id);
+        }
+        let contract = Arc::new(contract);
+        contracts.insert(id, contract.clone());
+        Ok(contract)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProofQuery {
+    chain_name: String,
+    contract_chain_id: u64,
+    contract_address: Address,
+    block_hash: Option<B256>,
+    block_number: Option<u32>,
+}
+
+fn error_response(status: StatusCode, error: impl ToString) -> Response {
+    (
+        status,
+        Json(json!({ "success": false, "error": error.to_string() })),
+    )
+        .into_response()
+}
+
+fn error_status(error: &FetchError) -> StatusCode {
+    match error {
+        FetchError::InvalidRequest(_) | FetchError::Config(_) => StatusCode::BAD_REQUEST,
+        FetchError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        _ if error.is_retryable() => StatusCode::BAD_GATEWAY,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// The prover, contract and block a query asks for.
+fn parse_query(
+    state: &QueryState,
+    query: Result<Query<ProofQuery>, QueryRejection>,
+) -> Result<(&CommitmentProver, Arc<CommitmentIndex>, BlockId), (StatusCode, String)> {
+    let Query(query) = query.map_err(|e| (StatusCode::BAD_REQUEST, e.body_text()))?;
+    let Some(prover) = state.provers.get(&query.chain_name.to_lowercase()) else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Unknown Avail chain {}", query.chain_name),
+        ));
+    };
+    let block = match (query.block_hash, query.block_number) {
+        (Some(block_hash), _) => BlockId::Hash(block_hash),
+        (None, Some(block_number)) => BlockId::Number(block_number),
+        (None, None) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "No block hash or block number provided!".to_string(),
+            ))
+        }
+    };
+    let contract = state
+        .contract(ContractId {
+            chain_id: query.contract_chain_id,
+            address: query.contract_address,
+        })
+        .map_err(|e| (error_status(&e), e.to_string()))?;
+    Ok((prover, contract, block))
+}
+
+fn proof_response<T: Serialize>(block: BlockId, proof: Result<Option<T>, FetchError>) -> Response {
+    match proof {
+        Ok(Some(proof)) => ([CDN_CACHE_CONTROL], Json(json!({ "data": proof }))).into_response(),
+        Ok(None) => error_response(
+            StatusCode::NOT_FOUND,
+            "Requested block is not in the range of blocks contained in the VectorX contract.",
+        ),
+        Err(e) => {
+            error!("Failed to prove {:?}: {}", block, e);
+            error_response(error_status(&e), e)
+        }
+    }
+}
+
+async fn data_root_proof(
+    State(state): State<QueryState>,
+    query: Result<Query<ProofQuery>, QueryRejection>,
+) -> Response {
+    match parse_query(&state, query) {
+        Ok((prover, contract, block)) => {
+            proof_response(block, prover.prove_data_root(&contract, block).await)
+        }
+        Err((status, error)) => error_response(status, error),
+    }
+}
+
+async fn state_root_proof(
+    State(state): State<QueryState>,
+    query: Result<Query<ProofQuery>, QueryRejection>,
+) -> Response {
+    match parse_query(&state, query) {
+        Ok((prover, contract, block)) => {
+            proof_response(block, prover.prove_state_root(&contract, block).await)
+        }
+        Err((status, error)) => error_response(status, error),
+    }
+}
+
+/// Provers for the chains listed in the config file, or the single chain configured from the
+/// environment.
+async fn provers(config: Option<PathBuf>) -> anyhow::Result<HashMap<String, CommitmentProver>> {
+    let config = config.or_else(|| env::var("INDEXER_CONFIG").ok().map(PathBuf::from));
+    let Some(config) = config else {
+        let fetcher = RpcDataFetcher::new().await?;
+        return Ok(HashMap::from([(
+            fetcher.avail_chain_id.to_lowercase(),
+            CommitmentProver::new(fetcher),
+        )]));
+    };
+    let mut provers = HashMap::new();
+    for chain in IndexerConfig::load(config)?.chains {
+        let fetcher = chain.fetcher_builder().build().await?;
+        provers.insert(
+            chain.avail_chain_id.to_lowercase(),
+            CommitmentProver::new(fetcher),
+        );
+    }
+    Ok(provers)
+}
+
+#[tokio::main]
+pub async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let args = QueryArgs::parse();
+    let addr = match args.addr {
+        Some(addr) => addr,
+        None => env::var("QUERY_ADDR")
+            .unwrap_or_else(|_| DEFAULT_ADDR.to_string())
+            .parse()?,
+    };
+
+    let events_db = args
+        .events_db
+        .or_else(|| env::var("CONTRACT_EVENTS_DB").ok().map(PathBuf::from));
+    let events = match events_db {
+        Some(events_db) => Some(Arc::new(ContractEventIndex::open(events_db)?)),
+        None => None,
+    };
+
+    let state = QueryState {
+        provers: Arc::new(provers(args.config).await?),
+        contracts: Arc::default(),
+        events,
+    };
+    let router = Router::new()
+        .route("/api", get(data_root_proof))
+        .route("/api/state", get(state_root_proof))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!("Serving data root proofs on {}", listener.local_addr()?);
+    axum::serve(listener, router).await?;
+    Ok(())
+}
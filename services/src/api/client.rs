@@ -0,0 +1,207 @@
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::{
+    ApiErrorCode, ApiResponse, BlockRange, CoverageQuery, Health, NearestQuery, API_VERSION,
+    MAX_RANGE_BLOCKS,
+};
+use crate::coverage::CoverageReport;
+use crate::error::FetchError;
+use crate::input::Result;
+use crate::store::{storage_error, JustificationRecord, JustificationStore, Prefer};
+
+/// Client for the justification API served by the indexer. Also usable as a read-only
+/// `JustificationStore`, so `RpcDataFetcher` can read justifications from a remote indexer.
+#[derive(Debug, Clone)]
+pub struct JustificationApiClient {
+    http_client: reqwest::Client,
+    url: String,
+}
+
+impl JustificationApiClient {
+    /// Client for the API served at `url`, e.g. `http://localhost:8080`.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            http_client: reqwest::Client::new(),
+            url: url.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// The response data, or `None` if the API returned `ApiErrorCode::NotFound`.
+    async fn request<T: DeserializeOwned, Q: Serialize + ?Sized>(
+        &self,
+        path: &str,
+        query: &Q,
+    ) -> Result<Option<T>> {
+        let response: ApiResponse<T> = self
+            .http_client
+            .get(format!("{}{}", self.url, path))
+            .query(query)
+            .send()
+            .await
+            .map_err(|e| FetchError::Query(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| FetchError::decode("justification API response", e))?;
+        match response {
+            ApiResponse::Ok { version, .. } | ApiResponse::Error { version, .. }
+                if version != API_VERSION =>
+            {
+                Err(FetchError::decode(
+                    "justification API response",
+                    format!("unsupported API version {}", version),
+                ))
+            }
+            ApiResponse::Ok { data, .. } => Ok(Some(data)),
+            ApiResponse::Error { error, .. } if error.code == ApiErrorCode::NotFound => Ok(None),
+            ApiResponse::Error { error, .. } => Err(FetchError::Query(error.message)),
+        }
+    }
+
+    pub async fn health(&self) -> Result<Health> {
+        self.request("/v1/health", &()).await?.ok_or_else(|| {
+            FetchError::decode("justification API response", "health route not found")
+        })
+    }
+
+    /// The justification for `block_number`, if one is stored.
+    pub async fn justification(
+        &self,
+        avail_chain_id: &str,
+        block_number: u32,
+    ) -> Result<Option<JustificationRecord>> {
+        self.request(
+            &format!(
+                "/v1/chains/{}/justifications/{}",
+                avail_chain_id, block_number
+            ),
+            &(),
+        )
+        .await
+    }
+
+    /// The stored justification with the lowest or highest block number in `from..=to`, as
+    /// preferred. Without `to` the range ends at the latest stored justification.
+    pub async fn nearest(
+        &self,
+        avail_chain_id: &str,
+        from: u32,
+        to: Option<u32>,
+        prefer: Prefer,
+    ) -> Result<Option<JustificationRecord>> {
+        self.request(
+            &format!("/v1/chains/{}/justifications/nearest", avail_chain_id),
+            &NearestQuery { from, to, prefer },
+        )
+        .await
+    }
+
+    /// Stored justifications for blocks in `from..=to`. The range may span at most
+    /// `MAX_RANGE_BLOCKS` blocks.
+    pub async fn range(
+        &self,
+        avail_chain_id: &str,
+        from: u32,
+        to: u32,
+    ) -> Result<Vec<JustificationRecord>> {
+        Ok(self
+            .request(
+                &format!("/v1/chains/{}/justifications", avail_chain_id),
+                &BlockRange { from, to: Some(to) },
+            )
+            .await?
+            .unwrap_or_default())
+    }
+
+    /// Coverage of `from..=to`, with gaps of at least `min_gap` blocks.
+    pub async fn coverage(
+        &self,
+        avail_chain_id: &str,
+        from: u32,
+        to: u32,
+        min_gap: Option<u32>,
+    ) -> Result<CoverageReport> {
+        self.request(
+            &format!("/v1/chains/{}/coverage", avail_chain_id),
+            &CoverageQuery { from, to, min_gap },
+        )
+        .await?
+        .ok_or_else(|| FetchError::Query(format!("Chain {} is not indexed", avail_chain_id)))
+    }
+}
+
+#[async_trait]
+impl JustificationStore for JustificationApiClient {
+    async fn put(&self, _avail_chain_id: &str, _record: &JustificationRecord) -> Result<()> {
+        Err(storage_error("the justification API is read-only"))
+    }
+
+    async fn get(
+        &self,
+        avail_chain_id: &str,
+        block_number: u32,
+    ) -> Result<Option<JustificationRecord>> {
+        self.justification(avail_chain_id, block_number).await
+    }
+
+    async fn delete(&self, _avail_chain_id: &str, _block_numbers: &[u32]) -> Result<()> {
+        Err(storage_error("the justification API is read-only"))
+    }
+
+    async fn range(
+        &self,
+        avail_chain_id: &str,
+        start_block_number: u32,
+        end_block_number: u32,
+    ) -> Result<Vec<JustificationRecord>> {
+        let mut justifications = Vec::new();
+        let mut from = start_block_number;
+        while from <= end_block_number {
+            let to = from
+                .saturating_add(MAX_RANGE_BLOCKS - 1)
+                .min(end_block_number);
+            justifications
+                .extend(JustificationApiClient::range(self, avail_chain_id, from, to).await?);
+            if to == u32::MAX {
+                break;
+            }
+            from = to + 1;
+        }
+        Ok(justifications)
+    }
+
+    async fn latest(&self, avail_chain_id: &str) -> Result<Option<JustificationRecord>> {
+        let health = self.health().await?;
+        let latest_block = health
+            .chains
+            .iter()
+            .find(|chain| chain.avail_chain_id.eq_ignore_ascii_case(avail_chain_id))
+            .and_then(|chain| chain.latest_block);
+        match latest_block {
+            Some(block_number) => self.justification(avail_chain_id, block_number).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn nearest_justified_block(
+        &self,
+        avail_chain_id: &str,
+        start_block_number: u32,
+        end_block_number: u32,
+        prefer: Prefer,
+    ) -> Result<Option<u32>> {
+        if start_block_number > end_block_number {
+            return Ok(None);
+        }
+        Ok(self
+            .nearest(
+                avail_chain_id,
+                start_block_number,
+                Some(end_block_number),
+                prefer,
+            )
+            .await?
+            .map(|record| record.block_number()))
+    }
+}
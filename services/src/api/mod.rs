@@ -0,0 +1,213 @@
+//! HTTP API over a `JustificationStore`, served by the indexer, and a typed client for it.
+//!
+//! Routes are under `/v1`:
+//!
+//! ```text
+//! GET /v1/health
+//! GET /v1/chains/<chain id>/justifications/<block number>
+//! GET /v1/chains/<chain id>/justifications/nearest?from=<block>[&to=<block>][&prefer=lowest|highest]
+//! GET /v1/chains/<chain id>/justifications?from=<block>&to=<block>
+//! GET /v1/chains/<chain id>/coverage?from=<block>&to=<block>[&min_gap=<blocks>]
+//! ```
+//!
+//! Every response body is an `ApiResponse`, which carries `API_VERSION`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::indexer::ChainStatus;
+use crate::store::Prefer;
+
+mod client;
+mod server;
+
+pub use client::JustificationApiClient;
+pub use server::{router, serve, Api};
+
+/// Version of the response types. Bumped on incompatible changes.
+pub const API_VERSION: u32 = 1;
+
+/// Maximum number of blocks in a range listing.
+pub const MAX_RANGE_BLOCKS: u32 = 1000;
+
+/// Maximum number of blocks in a coverage report.
+pub const MAX_COVERAGE_BLOCKS: u32 = 1_000_000;
+
+/// Response body of every route.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ApiResponse<T> {
+    Ok { version: u32, data: T },
+    Error { version: u32, error: ApiError },
+}
+
+impl<T> ApiResponse<T> {
+    pub fn ok(data: T) -> Self {
+        ApiResponse::Ok {
+            version: API_VERSION,
+            data,
+        }
+    }
+
+    pub fn error(code: ApiErrorCode, message: impl Into<String>) -> Self {
+        ApiResponse::Error {
+            version: API_VERSION,
+            error: ApiError {
+                code,
+                message: message.into(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiError {
+    pub code: ApiErrorCode,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiErrorCode {
+    /// The chain is not served, or no justification matches the request.
+    NotFound,
+    /// The request parameters are invalid.
+    BadRequest,
+    /// The justification store failed.
+    Internal,
+}
+
+/// Status of one chain served by the API.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainHealth {
+    pub avail_chain_id: String,
+    /// Highest block with a stored justification.
+    pub latest_block: Option<u32>,
+    /// Set if the store could not be read.
+    pub error: Option<String>,
+    /// Status of the indexer task of the chain, if the API is served by the indexer.
+    #[serde(default)]
+    pub indexer: Option<ChainStatus>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Health {
+    pub chains: Vec<ChainHealth>,
+}
+
+/// Query parameters of the range route.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockRange {
+    pub from: u32,
+    pub to: Option<u32>,
+}
+
+/// Query parameters of the nearest route. Without `to` the search is bounded by the latest stored
+/// justification.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NearestQuery {
+    pub from: u32,
+    pub to: Option<u32>,
+    /// Which end of the range to search from. Defaults to the lowest block.
+    #[serde(default)]
+    pub prefer: Prefer,
+}
+
+/// Query parameters of the coverage route.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoverageQuery {
+    pub from: u32,
+    pub to: u32,
+    /// Shortest run of blocks without a justification that is reported as a gap. Defaults to 1.
+    pub min_gap: Option<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use sp_core::H256;
+
+    use super::*;
+    use crate::error::FetchError;
+    use crate::store::{test_record, JustificationRecord, JustificationStore, SqliteStore};
+
+    #[tokio::test]
+    async fn test_client_reads_served_justifications() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        for block_number in [5, 8] {
+            store
+                .put("turing", &test_record(block_number))
+                .await
+                .unwrap();
+        }
+        let stores = HashMap::from([(
+            "Turing".to_string(),
+            Arc::new(store) as Arc<dyn JustificationStore>,
+        )]);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router(stores)).await });
+        let client = JustificationApiClient::new(format!("http://{}", addr));
+
+        let justification = client.justification("turing", 5).await.unwrap().unwrap();
+        assert_eq!(
+            justification.justification.commit.target_hash,
+            H256::repeat_byte(5)
+        );
+        assert!(client.justification("turing", 6).await.unwrap().is_none());
+        assert!(client.justification("hex", 5).await.unwrap().is_none());
+
+        let nearest = client
+            .nearest("turing", 6, None, Prefer::Lowest)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(nearest.block_number(), 8);
+        let nearest = client
+            .nearest("turing", 0, Some(10), Prefer::Highest)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(nearest.block_number(), 8);
+        assert!(client
+            .nearest("turing", 6, Some(7), Prefer::Lowest)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(client
+            .nearest("turing", 9, None, Prefer::Lowest)
+            .await
+            .unwrap()
+            .is_none());
+        let nearest =
+            JustificationStore::nearest_justified_block(&client, "turing", 0, 7, Prefer::Highest)
+                .await
+                .unwrap();
+        assert_eq!(nearest, Some(5));
+
+        let range = client.range("turing", 0, 10).await.unwrap();
+        assert_eq!(
+            range
+                .iter()
+                .map(JustificationRecord::block_number)
+                .collect::<Vec<_>>(),
+            vec![5, 8]
+        );
+        assert!(matches!(
+            client.range("turing", 10, 0).await,
+            Err(FetchError::Query(_))
+        ));
+
+        let coverage = client.coverage("turing", 0, 10, Some(3)).await.unwrap();
+        assert_eq!(coverage.justified.ranges(), &[5..=5, 8..=8]);
+        assert_eq!(coverage.gaps, vec![0..=4]);
+        assert!(client.coverage("hex", 0, 10, None).await.is_err());
+
+        let health = client.health().await.unwrap();
+        assert_eq!(health.chains.len(), 1);
+        assert_eq!(health.chains[0].latest_block, Some(8));
+        let latest = JustificationStore::latest(&client, "turing").await.unwrap();
+        assert_eq!(latest.unwrap().block_number(), 8);
+    }
+}
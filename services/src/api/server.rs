@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::extract::rejection::{PathRejection, QueryRejection};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use log::info;
+use serde::Serialize;
+use tokio::sync::OnceCell;
+
+use super::{
+    ApiErrorCode, ApiResponse, BlockRange, ChainHealth, CoverageQuery, Health, NearestQuery,
+    MAX_COVERAGE_BLOCKS, MAX_RANGE_BLOCKS,
+};
+use crate::coverage::{epoch_ends, CoverageReport};
+use crate::error::FetchError;
+use crate::indexer::IndexerStatus;
+use crate::input::{RpcDataFetcher, RpcDataFetcherBuilder};
+use crate::store::JustificationStore;
+
+/// Justification stores by lowercased Avail chain id.
+type Stores = Arc<HashMap<String, Arc<dyn JustificationStore>>>;
+
+/// A fetcher that is only connected when a coverage report first needs it.
+struct LazyFetcher {
+    builder: RpcDataFetcherBuilder,
+    fetcher: OnceCell<RpcDataFetcher>,
+}
+
+#[derive(Clone)]
+struct ApiState {
+    stores: Stores,
+    status: Option<IndexerStatus>,
+    fetchers: Arc<HashMap<String, LazyFetcher>>,
+}
+
+/// The justification API over a set of stores, keyed by Avail chain id.
+pub struct Api {
+    stores: HashMap<String, Arc<dyn JustificationStore>>,
+    status: Option<IndexerStatus>,
+    fetchers: HashMap<String, LazyFetcher>,
+}
+
+impl Api {
+    pub fn new(stores: HashMap<String, Arc<dyn JustificationStore>>) -> Self {
+        Self {
+            stores: stores
+                .into_iter()
+                .map(|(avail_chain_id, store)| (avail_chain_id.to_lowercase(), store))
+                .collect(),
+            status: None,
+            fetchers: HashMap::new(),
+        }
+    }
+
+    /// Report the indexer `status` of each chain in the health route.
+    pub fn with_status(mut self, status: IndexerStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Check the epoch end blocks in coverage reports of `avail_chain_id` against the Avail RPC,
+    /// with a fetcher built from `builder` on first use.
+    pub fn with_fetcher(mut self, avail_chain_id: &str, builder: RpcDataFetcherBuilder) -> Self {
+        self.fetchers.insert(
+            avail_chain_id.to_lowercase(),
+            LazyFetcher {
+                builder,
+                fetcher: OnceCell::new(),
+            },
+        );
+        self
+    }
+
+    pub fn router(self) -> Router {
+        let state = ApiState {
+            stores: Arc::new(self.stores),
+            status: self.status,
+            fetchers: Arc::new(self.fetchers),
+        };
+        Router::new()
+            .route("/v1/health", get(health))
+            .route("/v1/chains/:chain/coverage", get(coverage))
+            .route("/v1/chains/:chain/justifications", get(justification_range))
+            .route(
+                "/v1/chains/:chain/justifications/nearest",
+                get(nearest_justification),
+            )
+            .route(
+                "/v1/chains/:chain/justifications/:block",
+                get(justification),
+            )
+            .with_state(state)
+    }
+}
+
+struct Failure(ApiErrorCode, String);
+
+impl From<FetchError> for Failure {
+    fn from(e: FetchError) -> Self {
+        match e {
+            FetchError::InvalidRequest(message) => Failure(ApiErrorCode::BadRequest, message),
+            e => Failure(ApiErrorCode::Internal, e.to_string()),
+        }
+    }
+}
+
+fn respond<T: Serialize>(result: Result<T, Failure>) -> Response {
+    match result {
+        Ok(data) => (StatusCode::OK, Json(ApiResponse::ok(data))).into_response(),
+        Err(Failure(code, message)) => {
+            let status = match code {
+                ApiErrorCode::NotFound => StatusCode::NOT_FOUND,
+                ApiErrorCode::BadRequest => StatusCode::BAD_REQUEST,
+                ApiErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (status, Json(ApiResponse::<T>::error(code, message))).into_response()
+        }
+    }
+}
+
+fn store<'a>(
+    stores: &'a Stores,
+    avail_chain_id: &str,
+) -> Result<&'a dyn JustificationStore, Failure> {
+    stores
+        .get(&avail_chain_id.to_lowercase())
+        .map(|store| store.as_ref())
+        .ok_or_else(|| {
+            Failure(
+                ApiErrorCode::NotFound,
+                format!("Chain {} is not indexed", avail_chain_id),
+            )
+        })
+}
+
+fn bad_request(rejection: impl std::fmt::Display) -> Failure {
+    Failure(ApiErrorCode::BadRequest, rejection.to_string())
+}
+
+/// Router serving the justifications in `stores`, keyed by Avail chain id.
+pub fn router(stores: HashMap<String, Arc<dyn JustificationStore>>) -> Router {
+    Api::new(stores).router()
+}
+
+/// Serve `router` on `addr` until the process exits.
+pub async fn serve(addr: SocketAddr, router: Router) -> std::io::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!(
+        "Serving the justification API on {}",
+        listener.local_addr()?
+    );
+    axum::serve(listener, router).await
+}
+
+async fn health(State(state): State<ApiState>) -> Response {
+    let mut chains = Vec::new();
+    let mut healthy = true;
+    for (avail_chain_id, store) in state.stores.iter() {
+        let latest = store.latest(avail_chain_id).await;
+        healthy &= latest.is_ok();
+        chains.push(ChainHealth {
+            avail_chain_id: avail_chain_id.clone(),
+            latest_block: latest
+                .as_ref()
+                .ok()
+                .and_then(|record| record.as_ref().map(|record| record.block_number())),
+            error: latest.err().map(|e| e.to_string()),
+            indexer: state
+                .status
+                .as_ref()
+                .and_then(|status| status.get(avail_chain_id)),
+        });
+    }
+    chains.sort_by(|a, b| a.avail_chain_id.cmp(&b.avail_chain_id));
+    let status = if healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(ApiResponse::ok(Health { chains }))).into_response()
+}
+
+async fn justification(
+    State(ApiState { stores, .. }): State<ApiState>,
+    path: Result<Path<(String, u32)>, PathRejection>,
+) -> Response {
+    respond(
+        async {
+            let Path((avail_chain_id, block_number)) = path.map_err(bad_request)?;
+            store(&stores, &avail_chain_id)?
+                .get(&avail_chain_id, block_number)
+                .await?
+                .ok_or_else(|| {
+                    Failure(
+                        ApiErrorCode::NotFound,
+                        FetchError::MissingJustification(block_number).to_string(),
+                    )
+                })
+        }
+        .await,
+    )
+}
+
+async fn nearest_justification(
+    State(ApiState { stores, .. }): State<ApiState>,
+    path: Result<Path<String>, PathRejection>,
+    query: Result<Query<NearestQuery>, QueryRejection>,
+) -> Response {
+    respond(
+        async {
+            let Path(avail_chain_id) = path.map_err(bad_request)?;
+            let Query(query) = query.map_err(bad_request)?;
+            let store = store(&stores, &avail_chain_id)?;
+            let to = match query.to {
+                Some(to) => Some(to),
+                None => store
+                    .latest(&avail_chain_id)
+                    .await?
+                    .map(|record| record.block_number()),
+            };
+            let block_number = match to {
+                Some(to) => {
+                    store
+                        .nearest_justified_block(&avail_chain_id, query.from, to, query.prefer)
+                        .await?
+                }
+                None => None,
+            };
+            let record = match block_number {
+                Some(block_number) => store.get(&avail_chain_id, block_number).await?,
+                None => None,
+            };
+            record.ok_or_else(|| {
+                Failure(
+                    ApiErrorCode::NotFound,
+                    format!(
+                        "No justification found in blocks {}..={}",
+                        query.from,
+                        to.map_or("latest".to_string(), |to| to.to_string())
+                    ),
+                )
+            })
+        }
+        .await,
+    )
+}
+
+async fn justification_range(
+    State(ApiState { stores, .. }): State<ApiState>,
+    path: Result<Path<String>, PathRejection>,
+    query: Result<Query<BlockRange>, QueryRejection>,
+) -> Response {
+    respond(
+        async {
+            let Path(avail_chain_id) = path.map_err(bad_request)?;
+            let Query(range) = query.map_err(bad_request)?;
+            let to = range.to.ok_or_else(|| bad_request("Missing field `to`"))?;
+            if to < range.from || to - range.from >= MAX_RANGE_BLOCKS {
+                return Err(bad_request(format!(
+                    "Range {}..={} must be ascending and at most {} blocks",
+                    range.from, to, MAX_RANGE_BLOCKS
+                )));
+            }
+            Ok(store(&stores, &avail_chain_id)?
+                .range(&avail_chain_id, range.from, to)
+                .await?)
+        }
+        .await,
+    )
+}
+
+async fn coverage(
+    State(ApiState {
+        stores, fetchers, ..
+    }): State<ApiState>,
+    path: Result<Path<String>, PathRejection>,
+    query: Result<Query<CoverageQuery>, QueryRejection>,
+) -> Response {
+    respond(
+        async {
+            let Path(avail_chain_id) = path.map_err(bad_request)?;
+            let Query(query) = query.map_err(bad_request)?;
+            if query.to < query.from || query.to - query.from >= MAX_COVERAGE_BLOCKS {
+                return Err(bad_request(format!(
+                    "Range {}..={} must be ascending and at most {} blocks",
+                    query.from, query.to, MAX_COVERAGE_BLOCKS
+                )));
+            }
+            let justified = store(&stores, &avail_chain_id)?
+                .justified_blocks(&avail_chain_id, query.from, query.to)
+                .await?;
+            let epoch_ends = match fetchers.get(&avail_chain_id.to_lowercase()) {
+                Some(lazy) => {
+                    let fetcher = lazy
+                        .fetcher
+                        .get_or_try_init(|| lazy.builder.clone().build())
+                        .await?;
+                    epoch_ends(fetcher, query.from, query.to).await?
+                }
+                None => Vec::new(),
+            };
+            Ok(CoverageReport::new(
+                avail_chain_id,
+                query.from,
+                query.to,
+                justified,
+                query.min_gap.unwrap_or(1),
+                &epoch_ends,
+            ))
+        }
+        .await,
+    )
+}
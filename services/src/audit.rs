@@ -0,0 +1,270 @@
+//! Audit of the commitments an SP1Vector contract stored, independent of the prover. Every
+//! committed range is recomputed from the Avail headers, whether it was proven by the operator or
+//! written by the guardian with `updateBlockRangeData`, and the header and authority set hashes in
+//! the contract's storage are checked against the chain.
+//!
+//! `updateBlockRangeData` stores an authority set hash without emitting `AuthoritySetStored`, so
+//! besides the authority sets in the event index, the authority set active at the end of each
+//! committed range is checked if the contract stored a hash for it.
+
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+use alloy_primitives::B256;
+use codec::Encode;
+use serde::{Deserialize, Serialize};
+use sp1_vector_primitives::header_range::decode_header;
+use sp1_vector_primitives::merkle::get_merkle_root_commitments;
+use sp1_vector_primitives::types::DecodedHeaderData;
+
+use crate::contract::{ContractClient, HeaderRangeCommitment};
+use crate::error::FetchError;
+use crate::events::{ContractEventIndex, ContractId, LogPosition};
+use crate::input::{Result, RpcDataFetcher};
+use crate::source::AvailDataSource;
+
+/// A value stored by the contract that does not match the Avail chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Mismatch {
+    /// The range does not contain any block.
+    EmptyRange { start_block: u32, end_block: u32 },
+    /// The commitment tree size is not a power of two or is smaller than the range.
+    InvalidTreeSize {
+        start_block: u32,
+        end_block: u32,
+        commitment_tree_size: u32,
+    },
+    /// The data commitment differs from the one recomputed from the headers.
+    DataCommitment {
+        start_block: u32,
+        end_block: u32,
+        stored: B256,
+        recomputed: B256,
+    },
+    /// The state commitment differs from the one recomputed from the headers.
+    StateCommitment {
+        start_block: u32,
+        end_block: u32,
+        stored: B256,
+        recomputed: B256,
+    },
+    /// `blockHeightToHeaderHash` differs from the hash of the block.
+    HeaderHash {
+        block_number: u32,
+        stored: B256,
+        chain: B256,
+    },
+    /// `authoritySetIdToHash` differs from the hash of the authority set on Avail. `chain` is
+    /// `None` if the authority set has not been enacted yet.
+    AuthoritySetHash {
+        authority_set_id: u64,
+        stored: B256,
+        chain: Option<B256>,
+    },
+}
+
+/// Result of an audit of one contract.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditReport {
+    pub contract: ContractId,
+    pub avail_chain_id: String,
+    /// Number of committed ranges that were recomputed.
+    pub ranges_checked: usize,
+    /// End block of the last committed range that was recomputed.
+    pub last_end_block: Option<u32>,
+    /// Number of stored authority sets that were checked.
+    pub authority_sets_checked: usize,
+    /// Highest id of the stored authority sets that were checked.
+    pub last_authority_set_id: Option<u64>,
+    /// Position of the latest commitment or authority set event in the index when the audit
+    /// started. Auditing the events after it covers everything stored since.
+    pub last_position: Option<LogPosition>,
+    pub mismatches: Vec<Mismatch>,
+}
+
+/// Recompute the data and state commitments of `commitment` from the decoded headers of the blocks
+/// it covers, using the tree size stored with it.
+pub fn check_commitment(
+    commitment: &HeaderRangeCommitment,
+    headers: &[DecodedHeaderData],
+) -> Vec<Mismatch> {
+    let tree_size = commitment.commitment_tree_size as usize;
+    if !tree_size.is_power_of_two() || tree_size < headers.len() {
+        return vec![Mismatch::InvalidTreeSize {
+            start_block: commitment.start_block,
+            end_block: commitment.end_block,
+            commitment_tree_size: commitment.commitment_tree_size,
+        }];
+    }
+    let (state_commitment, data_commitment) = get_merkle_root_commitments(headers, tree_size);
+    let mut mismatches = Vec::new();
+    if data_commitment != commitment.data_commitment {
+        mismatches.push(Mismatch::DataCommitment {
+            start_block: commitment.start_block,
+            end_block: commitment.end_block,
+            stored: commitment.data_commitment,
+            recomputed: data_commitment,
+        });
+    }
+    if state_commitment != commitment.state_commitment {
+        mismatches.push(Mismatch::StateCommitment {
+            start_block: commitment.start_block,
+            end_block: commitment.end_block,
+            stored: commitment.state_commitment,
+            recomputed: state_commitment,
+        });
+    }
+    mismatches
+}
+
+/// Audit the ranges committed by `contract` that cover any of `blocks`, and the authority sets it
+/// stored, as listed in `index`. With `after`, only the commitments and authority sets stored
+/// after that event are audited, including ranges and authority sets that were stored again, e.g.
+/// by the guardian.
+pub async fn audit<S: AvailDataSource>(
+    fetcher: &RpcDataFetcher<S>,
+    client: &ContractClient,
+    index: &ContractEventIndex,
+    contract: ContractId,
+    blocks: Range<u32>,
+    after: Option<LogPosition>,
+) -> Result<AuditReport> {
+    let mut report = AuditReport {
+        contract,
+        avail_chain_id: fetcher.avail_chain_id.clone(),
+        ranges_checked: 0,
+        last_end_block: None,
+        authority_sets_checked: 0,
+        last_authority_set_id: None,
+        // Read before the events, so events indexed during the audit are audited the next time.
+        last_position: index.latest_position(contract)?.max(after),
+        mismatches: Vec::new(),
+    };
+
+    let mut authority_sets = BTreeSet::new();
+    for commitment in index.commitments(contract, blocks, after)? {
+        if commitment.end_block <= commitment.start_block {
+            report.mismatches.push(Mismatch::EmptyRange {
+                start_block: commitment.start_block,
+                end_block: commitment.end_block,
+            });
+            continue;
+        }
+        let headers = fetcher
+            .get_block_headers_range(commitment.start_block + 1, commitment.end_block)
+            .await?
+            .iter()
+            .map(|header| decode_header(&header.encode()))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| FetchError::decode("header", e))?;
+        report
+            .mismatches
+            .extend(check_commitment(&commitment, &headers));
+
+        let stored = client.header_hash(commitment.end_block).await?;
+        let chain = fetcher.get_block_hash(commitment.end_block).await?;
+        if stored != chain {
+            report.mismatches.push(Mismatch::HeaderHash {
+                block_number: commitment.end_block,
+                stored,
+                chain,
+            });
+        }
+
+        let (authority_set_id, chain) = fetcher
+            .get_authority_set_data_for_block(commitment.end_block)
+            .await?;
+        let stored = client.authority_set_hash(authority_set_id).await?;
+        if !stored.is_zero() && authority_sets.insert(authority_set_id) {
+            if stored != chain {
+                report.mismatches.push(Mismatch::AuthoritySetHash {
+                    authority_set_id,
+                    stored,
+                    chain: Some(chain),
+                });
+            }
+            report.authority_sets_checked += 1;
+            report.last_authority_set_id = report.last_authority_set_id.max(Some(authority_set_id));
+        }
+
+        report.ranges_checked += 1;
+        report.last_end_block = report.last_end_block.max(Some(commitment.end_block));
+    }
+
+    for (authority_set_id, _) in index.authority_sets(contract, after)? {
+        if !authority_sets.insert(authority_set_id) {
+            continue;
+        }
+        let stored = client.authority_set_hash(authority_set_id).await?;
+        // The hash of a new authority set is computed at the epoch end block of the previous one.
+        let chain = match authority_set_id.checked_sub(1) {
+            Some(previous) => fetcher
+                .get_epoch(previous)
+                .await?
+                .map(|epoch| epoch.new_authority_set_hash),
+            None => None,
+        };
+        if chain != Some(stored) {
+            report.mismatches.push(Mismatch::AuthoritySetHash {
+                authority_set_id,
+                stored,
+                chain,
+            });
+        }
+        report.authority_sets_checked += 1;
+        report.last_authority_set_id = report.last_authority_set_id.max(Some(authority_set_id));
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(block_number: u32) -> DecodedHeaderData {
+        DecodedHeaderData {
+            block_number,
+            parent_hash: B256::ZERO,
+            state_root: B256::repeat_byte(block_number as u8),
+            data_root: B256::repeat_byte(!(block_number as u8)),
+        }
+    }
+
+    #[test]
+    fn test_check_commitment() {
+        let headers: Vec<_> = (101..=103).map(header).collect();
+        let (state_commitment, data_commitment) = get_merkle_root_commitments(&headers, 4);
+        let mut commitment = HeaderRangeCommitment {
+            start_block: 100,
+            end_block: 103,
+            data_commitment,
+            state_commitment,
+            commitment_tree_size: 4,
+        };
+        assert!(check_commitment(&commitment, &headers).is_empty());
+
+        commitment.data_commitment = B256::ZERO;
+        assert_eq!(
+            check_commitment(&commitment, &headers),
+            vec![Mismatch::DataCommitment {
+                start_block: 100,
+                end_block: 103,
+                stored: B256::ZERO,
+                recomputed: data_commitment,
+            }]
+        );
+
+        // The same roots in a larger tree have a different commitment.
+        commitment.data_commitment = data_commitment;
+        commitment.commitment_tree_size = 8;
+        assert_eq!(check_commitment(&commitment, &headers).len(), 2);
+
+        commitment.commitment_tree_size = 2;
+        let mismatches = check_commitment(&commitment, &headers);
+        assert!(matches!(mismatches[..], [Mismatch::InvalidTreeSize { .. }]));
+        let json = serde_json::to_value(&mismatches[0]).unwrap();
+        assert_eq!(json["kind"], "invalid_tree_size");
+    }
+}
@@ -0,0 +1,383 @@
+//! Recovery of justifications for past blocks, for ranges the indexer missed while it was down
+//! and for chains without indexed history.
+
+use std::ops::RangeInclusive;
+
+use alloy_primitives::B256;
+use codec::Decode;
+use log::{info, warn};
+use sp_core::H256;
+
+use crate::error::FetchError;
+use crate::input::{Result, RetryPolicy, RpcDataFetcher};
+use crate::source::AvailDataSource;
+use crate::store::{put_with_retry, JustificationRecord, JustificationStore};
+use crate::types::GrandpaJustification;
+
+/// Outcome of a backfill.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BackfillReport {
+    /// Blocks whose justification was written to the store.
+    pub stored: Vec<u32>,
+    /// Blocks whose justification was already in the store.
+    pub already_stored: Vec<u32>,
+    /// Blocks for which the node did not return a valid finality proof.
+    pub unrecovered: Vec<RangeInclusive<u32>>,
+}
+
+impl BackfillReport {
+    fn add_unrecovered(&mut self, blocks: RangeInclusive<u32>) {
+        match self.unrecovered.last_mut() {
+            Some(range) if *range.end() + 1 == *blocks.start() => {
+                *range = *range.start()..=*blocks.end();
+            }
+            _ => self.unrecovered.push(blocks),
+        }
+    }
+
+    fn remove_unrecovered(&mut self, block_number: u32) {
+        let mut unrecovered = Vec::new();
+        for range in self.unrecovered.drain(..) {
+            if !range.contains(&block_number) {
+                unrecovered.push(range);
+                continue;
+            }
+            if block_number > *range.start() {
+                unrecovered.push(*range.start()..=block_number - 1);
+            }
+            if block_number < *range.end() {
+                unrecovered.push(block_number + 1..=*range.end());
+            }
+        }
+        self.unrecovered = unrecovered;
+    }
+
+    fn contains(&self, block_number: u32) -> bool {
+        self.stored.contains(&block_number) || self.already_stored.contains(&block_number)
+    }
+}
+
+/// Store the justifications the node can prove for blocks in `start_block_number..=end_block_number`.
+///
+/// `grandpa_proveFinality` returns the justification of the first block at or after the requested
+/// block that the node has one for (epoch end blocks, and any other blocks whose justification it
+/// kept), so the range is walked from one justified block to the next. Every justification is
+/// verified against the authority set of its block before it is stored. If it is invalid, the
+/// blocks up to the justified block are reported as unrecovered and the walk continues after it.
+/// If the node has no proof at all, the rest of the range is unrecovered and the walk stops. Other
+/// errors while requesting a proof, e.g. a failed request, are returned so the backfill can be
+/// retried.
+/// Justifications that are already stored are not written again, so a backfill can be re-run over
+/// the same range. Failed writes are retried with `retry_policy`.
+pub async fn backfill<S: AvailDataSource>(
+    fetcher: &RpcDataFetcher<S>,
+    store: &dyn JustificationStore,
+    start_block_number: u32,
+    end_block_number: u32,
+    retry_policy: &RetryPolicy,
+) -> Result<BackfillReport> {
+    if end_block_number < start_block_number {
+        return Err(FetchError::InvalidRequest(format!(
+            "Backfill end block {} is before start block {}",
+            end_block_number, start_block_number
+        )));
+    }
+
+    let mut report = BackfillReport::default();
+    let mut block_number = start_block_number;
+    while block_number <= end_block_number {
+        let (justification, proven_block) = match prove_finality(fetcher, block_number).await {
+            Ok(proof) => proof,
+            Err(FetchError::BlockNotFound(_)) => {
+                warn!(
+                    "No finality proof for block {}, stopping the backfill",
+                    block_number
+                );
+                report.add_unrecovered(block_number..=end_block_number);
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        let justified_block = justification.commit.target_number;
+        if justified_block > end_block_number {
+            break;
+        }
+        let record = match verify_justification(fetcher, justification, proven_block).await {
+            Ok(record) => record,
+            Err(e) => {
+                warn!(
+                    "Invalid justification for block {} in the finality proof for block {}: {}",
+                    justified_block, block_number, e
+                );
+                report.add_unrecovered(block_number..=justified_block);
+                block_number = justified_block + 1;
+                continue;
+            }
+        };
+
+        let avail_chain_id = &fetcher.avail_chain_id;
+        if store.get(avail_chain_id, justified_block).await?.is_some() {
+            report.already_stored.push(justified_block);
+        } else {
+            put_with_retry(store, avail_chain_id, &record, retry_policy).await?;
+            info!("Backfilled justification for block {}", justified_block);
+            report.stored.push(justified_block);
+        }
+        block_number = justified_block + 1;
+    }
+    Ok(report)
+}
+
+/// Backfill the blocks finalized after `last_indexed_block`, e.g. while the indexer was down.
+///
+/// The node keeps the justification of every epoch end block, so the backfill normally reaches
+/// all of them. Epoch end blocks in the gap that are still missing afterwards are requested once
+/// more, since rotate proofs cannot be generated without them.
+pub async fn fill_gap<S: AvailDataSource>(
+    fetcher: &RpcDataFetcher<S>,
+    store: &dyn JustificationStore,
+    last_indexed_block: u32,
+    retry_policy: &RetryPolicy,
+) -> Result<BackfillReport> {
+    let head = fetcher.get_head().await?.number;
+    if head <= last_indexed_block {
+        return Ok(BackfillReport::default());
+    }
+    info!(
+        "Filling justifications for blocks {}..={}",
+        last_indexed_block + 1,
+        head
+    );
+    let mut report = backfill(fetcher, store, last_indexed_block + 1, head, retry_policy).await?;
+
+    let first_authority_set_id = fetcher.get_authority_set_id(last_indexed_block).await?;
+    let head_authority_set_id = fetcher.get_authority_set_id(head).await?;
+    for authority_set_id in first_authority_set_id..head_authority_set_id {
+        let epoch_end_block = fetcher.last_justified_block(authority_set_id).await?;
+        if epoch_end_block <= last_indexed_block || report.contains(epoch_end_block) {
+            continue;
+        }
+        let epoch_report = backfill(
+            fetcher,
+            store,
+            epoch_end_block,
+            epoch_end_block,
+            retry_policy,
+        )
+        .await?;
+        if epoch_report.contains(epoch_end_block) {
+            report.remove_unrecovered(epoch_end_block);
+            report.stored.extend(epoch_report.stored);
+            report.already_stored.extend(epoch_report.already_stored);
+        } else {
+            warn!(
+                "Could not recover the epoch end block {} of authority set {}",
+                epoch_end_block, authority_set_id
+            );
+        }
+    }
+    Ok(report)
+}
+
+/// The justification in the finality proof for `block_number`, checked to be on a block at or
+/// after `block_number`, and the hash of the block the proof is for.
+async fn prove_finality<S: AvailDataSource>(
+    fetcher: &RpcDataFetcher<S>,
+    block_number: u32,
+) -> Result<(GrandpaJustification, H256)> {
+    let source = &fetcher.source;
+    let finality_proof = source.finality_proof(block_number).await?;
+    let justification = GrandpaJustification::decode(&mut finality_proof.justification.as_slice())
+        .map_err(|e| FetchError::decode("finality proof justification", e))?;
+    if justification.commit.target_number < block_number {
+        return Err(FetchError::decode(
+            "finality proof",
+            format!(
+                "justified block {} is before the requested block",
+                justification.commit.target_number
+            ),
+        ));
+    }
+    Ok((justification, finality_proof.block))
+}
+
+/// Verify that `justification` is for `proven_block`, the finalized block at its height, and
+/// against the authority set of the block.
+async fn verify_justification<S: AvailDataSource>(
+    fetcher: &RpcDataFetcher<S>,
+    justification: GrandpaJustification,
+    proven_block: H256,
+) -> Result<JustificationRecord> {
+    if justification.commit.target_hash != proven_block {
+        return Err(FetchError::decode(
+            "finality proof",
+            "justification is for a different block than the proof",
+        ));
+    }
+    let block_hash = fetcher
+        .source
+        .block_hash(justification.commit.target_number)
+        .await?;
+    if block_hash != B256::from(justification.commit.target_hash.0) {
+        return Err(FetchError::decode(
+            "finality proof",
+            "justified block is not the finalized block at its height",
+        ));
+    }
+    fetcher.validate_justification(justification).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::{Fixture, FixtureDataSource, RecordingDataSource};
+    use crate::store::SqliteStore;
+    use crate::types::FinalityProof;
+    use codec::Encode;
+    use serde::Deserialize;
+    use sp_core::ed25519::Public;
+    use std::env;
+    use std::fs::File;
+
+    #[derive(Deserialize)]
+    struct TestValidatorSet {
+        set_id: u64,
+        validator_set: Vec<Public>,
+    }
+
+    #[derive(Deserialize)]
+    struct TestCase {
+        validator_set: TestValidatorSet,
+        justification: GrandpaJustification,
+    }
+
+    /// A fixture that proves the justification in `path` for its block, with the authority set
+    /// of the test case, or with an unrelated authority set if `honest_authorities` is false.
+    fn test_fixture(path: &str, honest_authorities: bool) -> (Fixture, u32) {
+        let test_case: TestCase = serde_json::from_reader(File::open(path).unwrap()).unwrap();
+        let justification = test_case.justification;
+        let block_number = justification.commit.target_number;
+
+        let mut fixture = Fixture::default();
+        let authorities = if honest_authorities {
+            test_case
+                .validator_set
+                .validator_set
+                .iter()
+                .map(|public| B256::from(public.0))
+                .collect()
+        } else {
+            vec![B256::repeat_byte(1)]
+        };
+        fixture
+            .authority_set_ids
+            .insert(block_number - 1, test_case.validator_set.set_id);
+        fixture.authorities.insert(block_number - 1, authorities);
+        fixture
+            .block_hashes
+            .insert(block_number, B256::from(justification.commit.target_hash.0));
+        fixture.finality_proofs.insert(
+            block_number,
+            FinalityProof {
+                block: justification.commit.target_hash,
+                justification: justification.encode(),
+                unknown_headers: Vec::new(),
+            },
+        );
+        (fixture, block_number)
+    }
+
+    #[tokio::test]
+    async fn test_backfill_is_idempotent() {
+        let (fixture, block_number) = test_fixture("test_assets/ancestry.json", true);
+        let fetcher = RpcDataFetcher::with_source(FixtureDataSource::new(fixture), "test");
+        let store = SqliteStore::open_in_memory().unwrap();
+
+        let report = backfill(&fetcher, &store, 0, block_number + 2, &RetryPolicy::none())
+            .await
+            .unwrap();
+        assert_eq!(report.stored, vec![block_number]);
+        assert_eq!(
+            report.unrecovered,
+            vec![block_number + 1..=block_number + 2]
+        );
+        let record = store.get("test", block_number).await.unwrap().unwrap();
+        let metadata = record.metadata.unwrap();
+        assert!(metadata.signer_count * 3 > metadata.validator_set_size * 2);
+
+        let report = backfill(&fetcher, &store, 0, block_number, &RetryPolicy::none())
+            .await
+            .unwrap();
+        assert!(report.stored.is_empty());
+        assert_eq!(report.already_stored, vec![block_number]);
+    }
+
+    #[tokio::test]
+    async fn test_backfill_rejects_invalid_justifications() {
+        let (fixture, block_number) = test_fixture("test_assets/ancestry.json", false);
+        // Record the finality proofs requested from the fixture.
+        let source = RecordingDataSource::open(
+            FixtureDataSource::new(fixture),
+            env::temp_dir().join("vectorx-backfill-unused.json"),
+        )
+        .unwrap();
+        let fetcher = RpcDataFetcher::with_source(source, "test");
+        let store = SqliteStore::open_in_memory().unwrap();
+
+        let report = backfill(&fetcher, &store, 0, block_number + 2, &RetryPolicy::none())
+            .await
+            .unwrap();
+        assert!(report.stored.is_empty());
+        assert_eq!(report.unrecovered, vec![0..=block_number + 2]);
+        assert!(store.latest("test").await.unwrap().is_none());
+        // The invalid proof for the first block skips to the block after the justified one, where
+        // the node has no proof and the backfill stops.
+        assert_eq!(
+            fetcher
+                .source
+                .fixture()
+                .finality_proofs
+                .keys()
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![0]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_backfill_returns_proof_errors() {
+        let mut fixture = Fixture::default();
+        fixture.finality_proofs.insert(
+            5,
+            FinalityProof {
+                block: H256::repeat_byte(1),
+                justification: vec![0xff],
+                unknown_headers: Vec::new(),
+            },
+        );
+        let fetcher = RpcDataFetcher::with_source(FixtureDataSource::new(fixture), "test");
+        let store = SqliteStore::open_in_memory().unwrap();
+
+        // A proof that cannot be decoded fails the backfill instead of ending it.
+        let result = backfill(&fetcher, &store, 0, 10, &RetryPolicy::none()).await;
+        assert!(matches!(result, Err(FetchError::Decode { .. })));
+
+        // Without a proof, the rest of the range is unrecovered.
+        let report = backfill(&fetcher, &store, 6, 10, &RetryPolicy::none())
+            .await
+            .unwrap();
+        assert_eq!(report.unrecovered, vec![6..=10]);
+    }
+
+    #[test]
+    fn test_remove_unrecovered_splits_ranges() {
+        let mut report = BackfillReport {
+            unrecovered: vec![0..=4, 6..=9],
+            ..Default::default()
+        };
+        report.remove_unrecovered(0);
+        report.remove_unrecovered(7);
+        report.remove_unrecovered(9);
+        assert_eq!(report.unrecovered, vec![1..=4, 6..=6, 8..=8]);
+    }
+}
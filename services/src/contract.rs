@@ -0,0 +1,546 @@
+//! Reads the events of an SP1Vector contract, in particular the header range commitments it
+//! stored, over the Ethereum JSON-RPC of the chain it is deployed on.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+
+use alloy_primitives::{keccak256, Address, Bytes, B256};
+use alloy_sol_types::{sol, SolCall, SolEvent, SolValue};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::error::FetchError;
+use crate::events::{ContractEventIndex, ContractId};
+use crate::input::Result;
+
+sol! {
+    event HeadUpdate(uint32 blockNumber, bytes32 headerHash);
+
+    event AuthoritySetStored(uint64 authoritySetId, bytes32 authoritySetHash);
+
+    event HeaderRangeCommitmentStored(
+        uint32 startBlock,
+        uint32 endBlock,
+        bytes32 dataCommitment,
+        bytes32 stateCommitment,
+        uint32 headerRangeCommitmentTreeSize
+    );
+
+    function blockHeightToHeaderHash(uint32 blockHeight) external view returns (bytes32);
+
+    function authoritySetIdToHash(uint64 authoritySetId) external view returns (bytes32);
+}
+
+/// Number of Ethereum blocks whose logs are requested at a time.
+pub const LOG_BATCH_BLOCKS: u64 = 100_000;
+
+/// A header range commitment stored by the contract. It commits to the headers and data roots of
+/// blocks `start_block + 1..=end_block`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeaderRangeCommitment {
+    pub start_block: u32,
+    pub end_block: u32,
+    pub data_commitment: B256,
+    pub state_commitment: B256,
+    /// Number of leaves of the commitment trees, a power of two.
+    pub commitment_tree_size: u32,
+}
+
+impl HeaderRangeCommitment {
+    /// Whether the commitment covers `block_number`.
+    pub fn contains(&self, block_number: u32) -> bool {
+        self.start_block < block_number && block_number <= self.end_block
+    }
+
+    /// The key of the range in the contract's `dataRootCommitments`, the keccak256 hash of
+    /// `abi.encode(start_block, end_block)`.
+    pub fn range_hash(&self) -> B256 {
+        keccak256((self.start_block, self.end_block).abi_encode())
+    }
+}
+
+impl From<HeaderRangeCommitmentStored> for HeaderRangeCommitment {
+    fn from(event: HeaderRangeCommitmentStored) -> Self {
+        Self {
+            start_block: event.startBlock,
+            end_block: event.endBlock,
+            data_commitment: event.dataCommitment,
+            state_commitment: event.stateCommitment,
+            commitment_tree_size: event.headerRangeCommitmentTreeSize,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct RpcResponse<T> {
+    result: Option<T>,
+    error: Option<RpcError>,
+}
+
+/// An event emitted by the contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContractEvent {
+    /// The light client's head was updated.
+    HeadUpdate {
+        block_number: u32,
+        header_hash: B256,
+    },
+    /// The data and state commitments of a header range were stored.
+    HeaderRangeCommitmentStored(HeaderRangeCommitment),
+    /// The hash of a new authority set was stored.
+    AuthoritySetStored {
+        authority_set_id: u64,
+        authority_set_hash: B256,
+    },
+}
+
+/// An event and where it was emitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContractLog {
+    pub eth_block_number: u64,
+    pub log_index: u64,
+    pub event: ContractEvent,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RpcLog {
+    topics: Vec<B256>,
+    data: Bytes,
+    #[serde(default)]
+    block_number: Option<String>,
+    #[serde(default)]
+    log_index: Option<String>,
+}
+
+impl RpcLog {
+    fn decode_event(&self) -> Result<ContractEvent> {
+        let decode_error = |e| FetchError::decode("SP1Vector event", e);
+        match self.topics.first() {
+            Some(&HeadUpdate::SIGNATURE_HASH) => {
+                let event =
+                    HeadUpdate::decode_raw_log(self.topics.iter().copied(), &self.data, true)
+                        .map_err(decode_error)?;
+                Ok(ContractEvent::HeadUpdate {
+                    block_number: event.blockNumber,
+                    header_hash: event.headerHash,
+                })
+            }
+            Some(&HeaderRangeCommitmentStored::SIGNATURE_HASH) => {
+                HeaderRangeCommitmentStored::decode_raw_log(
+                    self.topics.iter().copied(),
+                    &self.data,
+                    true,
+                )
+                .map(|event| ContractEvent::HeaderRangeCommitmentStored(event.into()))
+                .map_err(decode_error)
+            }
+            Some(&AuthoritySetStored::SIGNATURE_HASH) => {
+                let event = AuthoritySetStored::decode_raw_log(
+                    self.topics.iter().copied(),
+                    &self.data,
+                    true,
+                )
+                .map_err(decode_error)?;
+                Ok(ContractEvent::AuthoritySetStored {
+                    authority_set_id: event.authoritySetId,
+                    authority_set_hash: event.authoritySetHash,
+                })
+            }
+            topic => Err(FetchError::decode(
+                "SP1Vector event",
+                format!("unexpected topic {:?}", topic),
+            )),
+        }
+    }
+}
+
+/// Parse a hex encoded quantity returned by the Ethereum RPC.
+fn parse_quantity(what: &'static str, quantity: &str) -> Result<u64> {
+    u64::from_str_radix(quantity.trim_start_matches("0x"), 16)
+        .map_err(|e| FetchError::decode(what, e))
+}
+
+/// Reads the events of an SP1Vector contract over the Ethereum JSON-RPC.
+#[derive(Debug, Clone)]
+pub struct ContractClient {
+    http_client: reqwest::Client,
+    rpc_url: String,
+    address: Address,
+}
+
+impl ContractClient {
+    pub fn new(rpc_url: impl Into<String>, address: Address) -> Self {
+        Self {
+            http_client: reqwest::Client::new(),
+            rpc_url: rpc_url.into(),
+            address,
+        }
+    }
+
+    /// Client for the contract at `address` on the chain `chain_id`, using the RPC URL in
+    /// RPC_<chain_id>.
+    pub fn from_env(chain_id: u64, address: Address) -> Result<Self> {
+        let rpc_url = env::var(format!("RPC_{}", chain_id))
+            .map_err(|_| FetchError::Config(format!("RPC_{} is not set", chain_id)))?;
+        Ok(Self::new(rpc_url, address))
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// The result of an RPC request, or `None` if the result is null.
+    async fn request_optional<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<Option<T>> {
+        let response: RpcResponse<T> = self
+            .http_client
+            .post(&self.rpc_url)
+            .json(&json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": method,
+                "params": params,
+            }))
+            .send()
+            .await
+            .map_err(|e| FetchError::Ethereum(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| FetchError::decode("Ethereum RPC response", e))?;
+        match response {
+            RpcResponse {
+                error: Some(error), ..
+            } => Err(FetchError::Ethereum(format!(
+                "{} failed with code {}: {}",
+                method, error.code, error.message
+            ))),
+            RpcResponse { result, .. } => Ok(result),
+        }
+    }
+
+    async fn request<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T> {
+        self.request_optional(method, params).await?.ok_or_else(|| {
+            FetchError::decode(
+                "Ethereum RPC response",
+                format!("{} returned no result", method),
+            )
+        })
+    }
+
+    /// The latest Ethereum block number.
+    pub async fn block_number(&self) -> Result<u64> {
+        let block_number: String = self.request("eth_blockNumber", json!([])).await?;
+        parse_quantity("Ethereum block number", &block_number)
+    }
+
+    /// Call a view function of the contract at the latest block.
+    async fn call<C: SolCall>(&self, call: C) -> Result<C::Return> {
+        let data: Bytes = self
+            .request(
+                "eth_call",
+                json!([
+                    {
+                        "to": self.address,
+                        "data": Bytes::from(call.abi_encode()),
+                    },
+                    "latest"
+                ]),
+            )
+            .await?;
+        C::abi_decode_returns(&data, true)
+            .map_err(|e| FetchError::decode("SP1Vector call result", e))
+    }
+
+    /// The header hash the contract stores for Avail block `block_number`, zero if none.
+    pub async fn header_hash(&self, block_number: u32) -> Result<B256> {
+        Ok(self
+            .call(blockHeightToHeaderHashCall {
+                blockHeight: block_number,
+            })
+            .await?
+            ._0)
+    }
+
+    /// The authority set hash the contract stores for `authority_set_id`, zero if none.
+    pub async fn authority_set_hash(&self, authority_set_id: u64) -> Result<B256> {
+        Ok(self
+            .call(authoritySetIdToHashCall {
+                authoritySetId: authority_set_id,
+            })
+            .await?
+            ._0)
+    }
+
+    /// The hash of Ethereum block `block_number`, or `None` if the block does not exist yet.
+    pub async fn block_hash(&self, block_number: u64) -> Result<Option<B256>> {
+        #[derive(Deserialize)]
+        struct Block {
+            hash: B256,
+        }
+        let block: Option<Block> = self
+            .request_optional(
+                "eth_getBlockByNumber",
+                json!([format!("{:#x}", block_number), false]),
+            )
+            .await?;
+        Ok(block.map(|block| block.hash))
+    }
+
+    async fn logs(&self, from_block: u64, to_block: u64, topics: &[B256]) -> Result<Vec<RpcLog>> {
+        self.request(
+            "eth_getLogs",
+            json!([{
+                "address": self.address,
+                "topics": [topics],
+                "fromBlock": format!("{:#x}", from_block),
+                "toBlock": format!("{:#x}", to_block),
+            }]),
+        )
+        .await
+    }
+
+    /// The `HeadUpdate`, `HeaderRangeCommitmentStored` and `AuthoritySetStored` events emitted in
+    /// Ethereum blocks `from_block..=to_block`, in the order they were emitted.
+    pub async fn events(&self, from_block: u64, to_block: u64) -> Result<Vec<ContractLog>> {
+        let logs = self
+            .logs(
+                from_block,
+                to_block,
+                &[
+                    HeadUpdate::SIGNATURE_HASH,
+                    HeaderRangeCommitmentStored::SIGNATURE_HASH,
+                    AuthoritySetStored::SIGNATURE_HASH,
+                ],
+            )
+            .await?;
+        logs.iter()
+            .map(|log| {
+                let (Some(eth_block_number), Some(log_index)) = (&log.block_number, &log.log_index)
+                else {
+                    return Err(FetchError::decode(
+                        "SP1Vector event",
+                        "log of a pending block",
+                    ));
+                };
+                Ok(ContractLog {
+                    eth_block_number: parse_quantity("log block number", eth_block_number)?,
+                    log_index: parse_quantity("log index", log_index)?,
+                    event: log.decode_event()?,
+                })
+            })
+            .collect()
+    }
+
+    /// The header range commitments stored in Ethereum blocks `from_block..=to_block`, in the
+    /// order they were stored.
+    pub async fn commitments(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<HeaderRangeCommitment>> {
+        let logs = self
+            .logs(
+                from_block,
+                to_block,
+                &[HeaderRangeCommitmentStored::SIGNATURE_HASH],
+            )
+            .await?;
+        logs.into_iter()
+            .map(|log| {
+                HeaderRangeCommitmentStored::decode_raw_log(log.topics, &log.data, true)
+                    .map(HeaderRangeCommitment::from)
+                    .map_err(|e| FetchError::decode("HeaderRangeCommitmentStored event", e))
+            })
+            .collect()
+    }
+}
+
+/// The commitment in `commitments`, ordered by block number, that covers `block_number`.
+fn find_commitment(
+    commitments: &[HeaderRangeCommitment],
+    block_number: u32,
+) -> Option<HeaderRangeCommitment> {
+    let index = commitments.partition_point(|commitment| commitment.end_block < block_number);
+    commitments
+        .get(index)
+        .filter(|commitment| commitment.contains(block_number))
+        .copied()
+}
+
+/// The header range commitments of one contract, read from its events on demand. Commitments
+/// never change once stored, so every commitment read is kept and a block covered by one of them
+/// is looked up without querying the Ethereum RPC again.
+pub struct CommitmentIndex {
+    client: ContractClient,
+    /// Commitments read so far, by end block.
+    commitments: Mutex<BTreeMap<u32, HeaderRangeCommitment>>,
+    /// Index of the contract's events, read before scanning logs.
+    events: Option<(Arc<ContractEventIndex>, ContractId)>,
+}
+
+impl CommitmentIndex {
+    pub fn new(client: ContractClient) -> Self {
+        Self {
+            client,
+            commitments: Mutex::new(BTreeMap::new()),
+            events: None,
+        }
+    }
+
+    /// Look commitments up in the events of `contract` in `index` before scanning logs. Blocks
+    /// the index has not caught up with are still found in the logs.
+    pub fn with_events(mut self, index: Arc<ContractEventIndex>, contract: ContractId) -> Self {
+        self.events = Some((index, contract));
+        self
+    }
+
+    pub fn client(&self) -> &ContractClient {
+        &self.client
+    }
+
+    fn cached(&self, block_number: u32) -> Option<HeaderRangeCommitment> {
+        self.commitments
+            .lock()
+            .unwrap()
+            .range(block_number..)
+            .next()
+            .map(|(_, commitment)| *commitment)
+            .filter(|commitment| commitment.contains(block_number))
+    }
+
+    fn insert(&self, commitments: &[HeaderRangeCommitment]) {
+        self.commitments.lock().unwrap().extend(
+            commitments
+                .iter()
+                .map(|commitment| (commitment.end_block, *commitment)),
+        );
+    }
+
+    /// The commitment that covers `block_number`, or `None` if the contract has not committed to
+    /// it yet.
+    ///
+    /// Logs are scanned back from the latest Ethereum block in batches of `LOG_BATCH_BLOCKS`,
+    /// until a batch starts at or before the block. The scan stops at the first batch without
+    /// any commitment, so the contract is expected to commit at least once per batch.
+    pub async fn commitment_for_block(
+        &self,
+        block_number: u32,
+    ) -> Result<Option<HeaderRangeCommitment>> {
+        if let Some(commitment) = self.cached(block_number) {
+            return Ok(Some(commitment));
+        }
+        if let Some((events, contract)) = &self.events {
+            if let Some(commitment) = events.commitment_for_block(*contract, block_number)? {
+                self.insert(&[commitment]);
+                return Ok(Some(commitment));
+            }
+        }
+
+        let mut to_block = self.client.block_number().await?;
+        loop {
+            let from_block = to_block.saturating_sub(LOG_BATCH_BLOCKS);
+            let commitments = self.client.commitments(from_block, to_block).await?;
+            self.insert(&commitments);
+            let (Some(first), Some(last)) = (commitments.first(), commitments.last()) else {
+                return Ok(None);
+            };
+            // Commitments are contiguous, so a block after the last one in this batch is not
+            // committed to yet.
+            if block_number > last.end_block {
+                return Ok(None);
+            }
+            if block_number > first.start_block {
+                return Ok(find_commitment(&commitments, block_number));
+            }
+            if from_block == 0 {
+                return Ok(None);
+            }
+            to_block = from_block - 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commitment(start_block: u32, end_block: u32) -> HeaderRangeCommitment {
+        HeaderRangeCommitment {
+            start_block,
+            end_block,
+            data_commitment: B256::repeat_byte(1),
+            state_commitment: B256::repeat_byte(2),
+            commitment_tree_size: 512,
+        }
+    }
+
+    #[test]
+    fn test_find_commitment() {
+        let commitments = [commitment(100, 180), commitment(180, 360)];
+        assert_eq!(find_commitment(&commitments, 100), None);
+        assert_eq!(find_commitment(&commitments, 101), Some(commitments[0]));
+        assert_eq!(find_commitment(&commitments, 180), Some(commitments[0]));
+        assert_eq!(find_commitment(&commitments, 181), Some(commitments[1]));
+        assert_eq!(find_commitment(&commitments, 361), None);
+    }
+
+    #[test]
+    fn test_range_hash_is_abi_encoded_range() {
+        let mut encoded = [0u8; 64];
+        encoded[28..32].copy_from_slice(&100u32.to_be_bytes());
+        encoded[60..64].copy_from_slice(&180u32.to_be_bytes());
+        assert_eq!(commitment(100, 180).range_hash(), keccak256(encoded));
+    }
+
+    #[test]
+    fn test_decode_event() {
+        let event = HeaderRangeCommitmentStored {
+            startBlock: 100,
+            endBlock: 180,
+            dataCommitment: B256::repeat_byte(1),
+            stateCommitment: B256::repeat_byte(2),
+            headerRangeCommitmentTreeSize: 512,
+        };
+        let log: RpcLog = serde_json::from_value(json!({
+            "topics": [HeaderRangeCommitmentStored::SIGNATURE_HASH],
+            "data": Bytes::from(event.encode_data()),
+            "blockNumber": "0x10",
+            "logIndex": "0x2",
+        }))
+        .unwrap();
+        assert_eq!(
+            log.decode_event().unwrap(),
+            ContractEvent::HeaderRangeCommitmentStored(commitment(100, 180))
+        );
+
+        let event = HeadUpdate {
+            blockNumber: 180,
+            headerHash: B256::repeat_byte(3),
+        };
+        let log: RpcLog = serde_json::from_value(json!({
+            "topics": [HeadUpdate::SIGNATURE_HASH],
+            "data": Bytes::from(event.encode_data()),
+        }))
+        .unwrap();
+        assert_eq!(
+            log.decode_event().unwrap(),
+            ContractEvent::HeadUpdate {
+                block_number: 180,
+                header_hash: B256::repeat_byte(3),
+            }
+        );
+    }
+}
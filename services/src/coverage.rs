@@ -0,0 +1,478 @@
+//! Which blocks have stored justifications. Gaps in the coverage point at indexer outages, and a
+//! missing epoch end justification blocks rotate proofs, so both are reported before the operator
+//! runs into them.
+//!
+//! The indexer keeps the justified blocks of each chain in a `CoverageTracker`, so coverage reports
+//! and pruning do not read the store.
+
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::input::{Result, RpcDataFetcher};
+use crate::source::AvailDataSource;
+use crate::store::{JustificationRecord, JustificationStore, Prefer};
+
+/// A set of block numbers stored as sorted, disjoint and non-adjacent ranges.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IntervalSet {
+    ranges: Vec<RangeInclusive<u32>>,
+}
+
+impl IntervalSet {
+    pub fn insert(&mut self, block_number: u32) {
+        // The first range that contains or ends right before `block_number`.
+        let i = self
+            .ranges
+            .partition_point(|range| range.end().saturating_add(1) < block_number);
+        match self.ranges.get_mut(i) {
+            Some(range) if *range.start() <= block_number.saturating_add(1) => {
+                *range = (*range.start()).min(block_number)..=(*range.end()).max(block_number);
+            }
+            _ => {
+                self.ranges.insert(i, block_number..=block_number);
+                return;
+            }
+        }
+        // Growing the range may have closed the gap to the next one.
+        if let Some(next) = self.ranges.get(i + 1) {
+            if self.ranges[i].end().saturating_add(1) >= *next.start() {
+                let end = *next.end();
+                self.ranges.remove(i + 1);
+                self.ranges[i] = *self.ranges[i].start()..=end;
+            }
+        }
+    }
+
+    pub fn remove(&mut self, block_number: u32) {
+        let i = self
+            .ranges
+            .partition_point(|range| *range.end() < block_number);
+        let Some(range) = self.ranges.get(i) else {
+            return;
+        };
+        let (start, end) = (*range.start(), *range.end());
+        if start > block_number {
+            return;
+        }
+        match (start < block_number, block_number < end) {
+            (false, false) => {
+                self.ranges.remove(i);
+            }
+            (true, false) => self.ranges[i] = start..=block_number - 1,
+            (false, true) => self.ranges[i] = block_number + 1..=end,
+            (true, true) => {
+                self.ranges[i] = start..=block_number - 1;
+                self.ranges.insert(i + 1, block_number + 1..=end);
+            }
+        }
+    }
+
+    pub fn contains(&self, block_number: u32) -> bool {
+        let i = self
+            .ranges
+            .partition_point(|range| *range.end() < block_number);
+        self.ranges
+            .get(i)
+            .is_some_and(|range| range.contains(&block_number))
+    }
+
+    pub fn ranges(&self) -> &[RangeInclusive<u32>] {
+        &self.ranges
+    }
+
+    /// Number of blocks in the set.
+    pub fn len(&self) -> u64 {
+        self.ranges
+            .iter()
+            .map(|range| (*range.end() - *range.start()) as u64 + 1)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// The blocks of the set in `start..=end`.
+    pub fn slice(&self, start: u32, end: u32) -> IntervalSet {
+        let i = self.ranges.partition_point(|range| *range.end() < start);
+        IntervalSet {
+            ranges: self.ranges[i..]
+                .iter()
+                .take_while(|range| *range.start() <= end)
+                .map(|range| (*range.start()).max(start)..=(*range.end()).min(end))
+                .collect(),
+        }
+    }
+
+    /// The blocks in the set, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        self.ranges.iter().flat_map(|range| range.clone())
+    }
+
+    /// The maximal ranges of blocks in `start..=end` that are not in the set.
+    pub fn gaps(&self, start: u32, end: u32) -> Vec<RangeInclusive<u32>> {
+        let mut gaps = Vec::new();
+        let mut cursor = start;
+        for range in &self.ranges {
+            if *range.end() < cursor {
+                continue;
+            }
+            if *range.start() > end {
+                break;
+            }
+            if *range.start() > cursor {
+                gaps.push(cursor..=*range.start() - 1);
+            }
+            if *range.end() >= end {
+                return gaps;
+            }
+            cursor = *range.end() + 1;
+        }
+        if cursor <= end {
+            gaps.push(cursor..=end);
+        }
+        gaps
+    }
+}
+
+impl FromIterator<u32> for IntervalSet {
+    fn from_iter<I: IntoIterator<Item = u32>>(block_numbers: I) -> Self {
+        let mut set = IntervalSet::default();
+        set.extend(block_numbers);
+        set
+    }
+}
+
+impl Extend<u32> for IntervalSet {
+    fn extend<I: IntoIterator<Item = u32>>(&mut self, block_numbers: I) {
+        for block_number in block_numbers {
+            self.insert(block_number);
+        }
+    }
+}
+
+/// The last block justified by an authority set, after which the next set takes over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EpochEnd {
+    pub authority_set_id: u64,
+    pub block_number: u32,
+}
+
+/// Justification coverage of a chain over a range of blocks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoverageReport {
+    pub avail_chain_id: String,
+    pub start_block: u32,
+    pub end_block: u32,
+    /// Blocks in the range with a stored justification.
+    pub justified: IntervalSet,
+    /// Runs of at least `min_gap` blocks without a stored justification.
+    pub gaps: Vec<RangeInclusive<u32>>,
+    /// Epoch end blocks in the range without a stored justification. Only checked if the epoch
+    /// end blocks are known, see `epoch_ends`.
+    pub missing_epoch_ends: Vec<EpochEnd>,
+}
+
+impl CoverageReport {
+    /// Report the coverage of `justified` over `start_block..=end_block`. Runs of blocks without
+    /// a justification shorter than `min_gap` are expected, since GRANDPA does not justify
+    /// every block, and are left out.
+    pub fn new(
+        avail_chain_id: impl Into<String>,
+        start_block: u32,
+        end_block: u32,
+        justified: IntervalSet,
+        min_gap: u32,
+        epoch_ends: &[EpochEnd],
+    ) -> Self {
+        let gaps = justified
+            .gaps(start_block, end_block)
+            .into_iter()
+            .filter(|gap| gap.end() - gap.start() + 1 >= min_gap)
+            .collect();
+        let missing_epoch_ends = epoch_ends
+            .iter()
+            .filter(|epoch_end| {
+                (start_block..=end_block).contains(&epoch_end.block_number)
+                    && !justified.contains(epoch_end.block_number)
+            })
+            .copied()
+            .collect();
+        CoverageReport {
+            avail_chain_id: avail_chain_id.into(),
+            start_block,
+            end_block,
+            justified,
+            gaps,
+            missing_epoch_ends,
+        }
+    }
+
+    /// Whether the report found no gaps and no missing epoch end justifications.
+    pub fn is_complete(&self) -> bool {
+        self.gaps.is_empty() && self.missing_epoch_ends.is_empty()
+    }
+}
+
+/// A store that keeps the set of justified blocks of each chain in memory, to answer block
+/// number and coverage queries without reading the inner store. The set of a chain is read from
+/// the inner store once, on the first query, and then updated on every `put` and `delete`. Writes
+/// to the inner store by other processes are not seen.
+pub struct CoverageTracker {
+    store: Arc<dyn JustificationStore>,
+    /// Justified blocks by lowercased Avail chain id.
+    justified: Mutex<HashMap<String, IntervalSet>>,
+}
+
+impl CoverageTracker {
+    pub fn new(store: Arc<dyn JustificationStore>) -> Self {
+        Self {
+            store,
+            justified: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The justified blocks of `avail_chain_id` in `start_block..=end_block`.
+    async fn slice(
+        &self,
+        avail_chain_id: &str,
+        start_block: u32,
+        end_block: u32,
+    ) -> Result<IntervalSet> {
+        // Writes wait for the lock after writing to the store, so a block stored while the set is
+        // read is either read or added afterwards.
+        let mut justified = self.justified.lock().await;
+        let key = avail_chain_id.to_lowercase();
+        if !justified.contains_key(&key) {
+            let blocks = match self.store.latest(avail_chain_id).await? {
+                Some(latest) => {
+                    self.store
+                        .justified_blocks(avail_chain_id, 0, latest.block_number())
+                        .await?
+                }
+                None => IntervalSet::default(),
+            };
+            justified.insert(key.clone(), blocks);
+        }
+        Ok(justified[&key].slice(start_block, end_block))
+    }
+
+    /// Apply `update` to the set of `avail_chain_id` if it has been read.
+    async fn update(&self, avail_chain_id: &str, update: impl FnOnce(&mut IntervalSet)) {
+        if let Some(blocks) = self
+            .justified
+            .lock()
+            .await
+            .get_mut(&avail_chain_id.to_lowercase())
+        {
+            update(blocks);
+        }
+    }
+}
+
+#[async_trait]
+impl JustificationStore for CoverageTracker {
+    async fn put(&self, avail_chain_id: &str, record: &JustificationRecord) -> Result<()> {
+        self.store.put(avail_chain_id, record).await?;
+        self.update(avail_chain_id, |blocks| {
+            blocks.insert(record.block_number())
+        })
+        .await;
+        Ok(())
+    }
+
+    async fn get(
+        &self,
+        avail_chain_id: &str,
+        block_number: u32,
+    ) -> Result<Option<JustificationRecord>> {
+        self.store.get(avail_chain_id, block_number).await
+    }
+
+    async fn range(
+        &self,
+        avail_chain_id: &str,
+        start_block_number: u32,
+        end_block_number: u32,
+    ) -> Result<Vec<JustificationRecord>> {
+        self.store
+            .range(avail_chain_id, start_block_number, end_block_number)
+            .await
+    }
+
+    async fn latest(&self, avail_chain_id: &str) -> Result<Option<JustificationRecord>> {
+        self.store.latest(avail_chain_id).await
+    }
+
+    async fn delete(&self, avail_chain_id: &str, block_numbers: &[u32]) -> Result<()> {
+        self.store.delete(avail_chain_id, block_numbers).await?;
+        self.update(avail_chain_id, |blocks| {
+            for &block_number in block_numbers {
+                blocks.remove(block_number);
+            }
+        })
+        .await;
+        Ok(())
+    }
+
+    async fn block_numbers(
+        &self,
+        avail_chain_id: &str,
+        start_block_number: u32,
+        end_block_number: u32,
+    ) -> Result<Vec<u32>> {
+        Ok(self
+            .slice(avail_chain_id, start_block_number, end_block_number)
+            .await?
+            .iter()
+            .collect())
+    }
+
+    async fn justified_blocks(
+        &self,
+        avail_chain_id: &str,
+        start_block_number: u32,
+        end_block_number: u32,
+    ) -> Result<IntervalSet> {
+        self.slice(avail_chain_id, start_block_number, end_block_number)
+            .await
+    }
+
+    async fn nearest_justified_block(
+        &self,
+        avail_chain_id: &str,
+        start_block_number: u32,
+        end_block_number: u32,
+        prefer: Prefer,
+    ) -> Result<Option<u32>> {
+        self.store
+            .nearest_justified_block(avail_chain_id, start_block_number, end_block_number, prefer)
+            .await
+    }
+}
+
+/// The epoch end blocks in `start_block..=end_block`.
+pub async fn epoch_ends<S: AvailDataSource>(
+    fetcher: &RpcDataFetcher<S>,
+    start_block: u32,
+    end_block: u32,
+) -> Result<Vec<EpochEnd>> {
+    let first_authority_set_id = fetcher.get_authority_set_id(start_block).await?;
+    let last_authority_set_id = fetcher.get_authority_set_id(end_block).await?;
+    let mut epoch_ends = Vec::new();
+    // An epoch end block is the last block of its authority set, so the set active at the end of
+    // the range has not ended within it.
+    for authority_set_id in first_authority_set_id.saturating_sub(1)..last_authority_set_id {
+        let block_number = fetcher.last_justified_block(authority_set_id).await?;
+        if (start_block..=end_block).contains(&block_number) {
+            epoch_ends.push(EpochEnd {
+                authority_set_id,
+                block_number,
+            });
+        }
+    }
+    Ok(epoch_ends)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{test_record, SqliteStore};
+
+    #[test]
+    fn test_interval_set_merges_adjacent_blocks() {
+        let set: IntervalSet = [5, 1, 3, 2, 9, 10, 7].into_iter().collect();
+        assert_eq!(set.ranges(), &[1..=3, 5..=5, 7..=7, 9..=10]);
+        assert_eq!(set.len(), 7);
+
+        let mut set = set;
+        set.insert(6);
+        set.insert(8);
+        set.insert(2);
+        assert_eq!(set.ranges(), &[1..=3, 5..=10]);
+        assert!(set.contains(7));
+        assert!(!set.contains(4));
+        assert!(!set.contains(11));
+    }
+
+    #[test]
+    fn test_interval_set_remove_and_slice() {
+        let mut set: IntervalSet = (1..=10).chain(20..=22).collect();
+        set.remove(5);
+        set.remove(1);
+        set.remove(10);
+        set.remove(15);
+        assert_eq!(set.ranges(), &[2..=4, 6..=9, 20..=22]);
+        assert_eq!(set.slice(3, 21).ranges(), &[3..=4, 6..=9, 20..=21]);
+        assert_eq!(set.slice(10, 19), IntervalSet::default());
+        assert_eq!(set.slice(0, 3).iter().collect::<Vec<_>>(), vec![2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_tracker_follows_writes() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        for block_number in [3, 4, 8] {
+            store
+                .put("turing", &test_record(block_number))
+                .await
+                .unwrap();
+        }
+        let tracker = CoverageTracker::new(Arc::new(store));
+        assert_eq!(
+            tracker
+                .justified_blocks("Turing", 0, 10)
+                .await
+                .unwrap()
+                .ranges(),
+            &[3..=4, 8..=8]
+        );
+
+        tracker.put("turing", &test_record(5)).await.unwrap();
+        tracker.delete("turing", &[3]).await.unwrap();
+        assert_eq!(
+            tracker
+                .justified_blocks("turing", 0, 10)
+                .await
+                .unwrap()
+                .ranges(),
+            &[4..=5, 8..=8]
+        );
+        assert_eq!(
+            tracker.block_numbers("turing", 5, 20).await.unwrap(),
+            vec![5, 8]
+        );
+    }
+
+    #[test]
+    fn test_interval_set_gaps() {
+        let set: IntervalSet = [3, 4, 5, 9].into_iter().collect();
+        assert_eq!(set.gaps(0, 12), vec![0..=2, 6..=8, 10..=12]);
+        assert_eq!(set.gaps(4, 9), vec![6..=8]);
+        assert_eq!(set.gaps(3, 5), vec![]);
+        assert_eq!(IntervalSet::default().gaps(1, 2), vec![1..=2]);
+    }
+
+    #[test]
+    fn test_report_flags_missing_epoch_ends() {
+        let justified: IntervalSet = (0..=100).filter(|n| n % 5 == 0 && *n != 60).collect();
+        let epoch_ends = [
+            EpochEnd {
+                authority_set_id: 1,
+                block_number: 40,
+            },
+            EpochEnd {
+                authority_set_id: 2,
+                block_number: 61,
+            },
+        ];
+        let report = CoverageReport::new("turing", 0, 100, justified, 8, &epoch_ends);
+        assert_eq!(report.gaps, vec![56..=64]);
+        assert_eq!(report.missing_epoch_ends, vec![epoch_ends[1]]);
+        assert!(!report.is_complete());
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Mutex, RwLock};
+
+use alloy_primitives::B256;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use crate::error::FetchError;
+use crate::input::Result;
+
+/// The end of an authority set's epoch. The epoch end block is the last block justified by
+/// `authority_set_id` and enacts the next authority set, whose hash is `new_authority_set_hash`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Epoch {
+    pub authority_set_id: u64,
+    pub end_block: u32,
+    pub new_authority_set_hash: B256,
+}
+
+/// Index of the epochs of a chain, keyed by authority set id. Epochs are immutable once
+/// finalized, so lookups are served from memory and new epochs are appended as they are found.
+/// If opened from a file, the index is persisted to SQLite and reloaded on restart.
+pub struct EpochIndex {
+    chain_id: String,
+    epochs: RwLock<BTreeMap<u64, Epoch>>,
+    conn: Option<Mutex<Connection>>,
+}
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS epochs (
+    chain_id TEXT NOT NULL,
+    authority_set_id INTEGER NOT NULL,
+    end_block INTEGER NOT NULL,
+    new_authority_set_hash BLOB NOT NULL,
+    PRIMARY KEY (chain_id, authority_set_id)
+);
+";
+
+fn storage_error(error: impl std::fmt::Display) -> FetchError {
+    FetchError::Storage(error.to_string())
+}
+
+impl EpochIndex {
+    /// An index that is not persisted.
+    pub fn in_memory(chain_id: impl Into<String>) -> Self {
+        Self {
+            chain_id: chain_id.into(),
+            epochs: RwLock::new(BTreeMap::new()),
+            conn: None,
+        }
+    }
+
+    /// Open the index persisted at `path` and load the known epochs of `chain_id`.
+    pub fn open(path: impl AsRef<Path>, chain_id: impl Into<String>) -> Result<Self> {
+        let chain_id = chain_id.into();
+        let conn = Connection::open(path).map_err(storage_error)?;
+        conn.execute_batch(SCHEMA).map_err(storage_error)?;
+
+        let mut epochs = BTreeMap::new();
+        {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT authority_set_id, end_block, new_authority_set_hash FROM epochs WHERE chain_id = ?1",
+                )
+                .map_err(storage_error)?;
+            let rows = stmt
+                .query_map(params![chain_id], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get::<_, Vec<u8>>(2)?))
+                })
+                .map_err(storage_error)?;
+            for row in rows {
+                let (authority_set_id, end_block, hash) = row.map_err(storage_error)?;
+                let new_authority_set_hash = B256::try_from(hash.as_slice())
+                    .map_err(|e| FetchError::decode("indexed authority set hash", e))?;
+                epochs.insert(
+                    authority_set_id,
+                    Epoch {
+                        authority_set_id,
+                        end_block,
+                        new_authority_set_hash,
+                    },
+                );
+            }
+        }
+
+        Ok(Self {
+            chain_id,
+            epochs: RwLock::new(epochs),
+            conn: Some(Mutex::new(conn)),
+        })
+    }
+
+    pub fn get(&self, authority_set_id: u64) -> Option<Epoch> {
+        self.epochs.read().unwrap().get(&authority_set_id).copied()
+    }
+
+    /// The epoch with the highest authority set id in the index.
+    pub fn latest(&self) -> Option<Epoch> {
+        self.epochs.read().unwrap().values().next_back().copied()
+    }
+
+    /// Add an epoch to the index, persisting it if the index is backed by a file.
+    pub fn insert(&self, epoch: Epoch) -> Result<()> {
+        if let Some(conn) = &self.conn {
+            conn.lock()
+                .unwrap()
+                .execute(
+                    "INSERT OR REPLACE INTO epochs (chain_id, authority_set_id, end_block, new_authority_set_hash) VALUES (?1, ?2, ?3, ?4)",
+                    params![
+                        self.chain_id,
+                        epoch.authority_set_id,
+                        epoch.end_block,
+                        epoch.new_authority_set_hash.as_slice()
+                    ],
+                )
+                .map_err(storage_error)?;
+        }
+        self.epochs
+            .write()
+            .unwrap()
+            .insert(epoch.authority_set_id, epoch);
+        Ok(())
+    }
+
+    /// Block range that must contain the epoch end block of `authority_set_id`, given the known
+    /// epochs before and after it. The upper bound is `None` if no later epoch is known.
+    pub fn search_bounds(&self, authority_set_id: u64) -> (u32, Option<u32>) {
+        let epochs = self.epochs.read().unwrap();
+        let low = epochs
+            .range(..authority_set_id)
+            .next_back()
+            .map_or(0, |(_, epoch)| epoch.end_block + 1);
+        let high = epochs
+            .range(authority_set_id + 1..)
+            .next()
+            .map(|(_, epoch)| epoch.end_block.saturating_sub(1));
+        (low, high)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn epoch(authority_set_id: u64, end_block: u32) -> Epoch {
+        Epoch {
+            authority_set_id,
+            end_block,
+            new_authority_set_hash: B256::repeat_byte(authority_set_id as u8),
+        }
+    }
+
+    #[test]
+    fn test_epoch_index_persists_and_bounds_search() {
+        let path = std::env::temp_dir().join(format!("vectorx-epochs-{}.db", std::process::id()));
+        let index = EpochIndex::open(&path, "turing").unwrap();
+        index.insert(epoch(2, 100)).unwrap();
+        index.insert(epoch(5, 400)).unwrap();
+
+        assert_eq!(index.search_bounds(3), (101, Some(399)));
+        assert_eq!(index.search_bounds(1), (0, Some(99)));
+        assert_eq!(index.search_bounds(6), (401, None));
+
+        let reopened = EpochIndex::open(&path, "turing").unwrap();
+        let other_chain = EpochIndex::open(&path, "mainnet").unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(reopened.get(2), Some(epoch(2, 100)));
+        assert_eq!(reopened.latest(), Some(epoch(5, 400)));
+        assert_eq!(other_chain.latest(), None);
+    }
+}
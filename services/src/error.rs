@@ -0,0 +1,114 @@
+use std::time::Duration;
+
+use alloy_primitives::B256;
+use sp1_vector_primitives::VerificationError;
+use thiserror::Error;
+
+/// Errors returned by the `RpcDataFetcher`.
+#[derive(Debug, Error)]
+pub enum FetchError {
+    /// The Avail RPC returned an error or the connection failed.
+    #[error("Avail RPC error: {0}")]
+    Rpc(String),
+    /// An Avail RPC request did not complete within the request timeout.
+    #[error("Avail RPC request timed out after {0:?}")]
+    Timeout(Duration),
+    /// The Ethereum RPC returned an error or the connection failed.
+    #[error("Ethereum RPC error: {0}")]
+    Ethereum(String),
+    /// The justification query service returned an error.
+    #[error("Justification query service error: {0}")]
+    Query(String),
+    /// Data returned by the Avail RPC or the query service could not be decoded.
+    #[error("Failed to decode {what}: {reason}")]
+    Decode { what: &'static str, reason: String },
+    /// No justification is stored for the block.
+    #[error("No justification found for block {0}")]
+    MissingJustification(u32),
+    /// The authority set has not rotated yet, so it has no epoch end block.
+    #[error("Authority set {0} is still active")]
+    AuthoritySetStillActive(u64),
+    /// The block is not known to the data source.
+    #[error("Block {0} not found")]
+    BlockNotFound(u32),
+    /// A GRANDPA authority has a voting weight other than 1.
+    #[error("Authority at block {block_number} has weight {weight}, expected 1")]
+    InvalidAuthorityWeight { block_number: u32, weight: u64 },
+    /// The epoch end block does not contain a GRANDPA scheduled change consensus log.
+    #[error("Block {0} should be an epoch end block, but has no scheduled change consensus log")]
+    MissingConsensusLog(u32),
+    /// The request arguments are invalid.
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    /// The fetcher is missing required configuration.
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+    /// A header fetched by parent hash does not link to its child.
+    #[error("Header chain is broken at block {block_number}: {reason}")]
+    HeaderLinkage { block_number: u32, reason: String },
+    /// Avail RPC endpoints returned different data for the same finalized block.
+    #[error("Avail RPC endpoints {endpoints:?} disagree on the {what} of block {block_number}")]
+    EndpointDisagreement {
+        what: &'static str,
+        block_number: u32,
+        endpoints: Vec<String>,
+    },
+    /// Reading or writing locally stored chain data failed.
+    #[error("Storage error: {0}")]
+    Storage(String),
+    /// The data or state roots of a header range do not match the commitment the contract stored.
+    #[error(
+        "The {what}s of blocks {} to {end_block} do not match the commitment {expected}, found {found}",
+        start_block + 1
+    )]
+    CommitmentMismatch {
+        what: &'static str,
+        start_block: u32,
+        end_block: u32,
+        expected: B256,
+        found: B256,
+    },
+    /// The fetched proof inputs failed native verification.
+    #[error("Proof inputs failed verification: {0}")]
+    Verification(#[from] VerificationError),
+}
+
+impl FetchError {
+    /// Whether the request that returned this error may succeed if it is retried.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            FetchError::Rpc(_)
+                | FetchError::Timeout(_)
+                | FetchError::Query(_)
+                | FetchError::Ethereum(_)
+                | FetchError::Storage(_)
+        )
+    }
+
+    /// Whether the node rejected the request because it is overloaded, e.g. because too many
+    /// requests are in flight or the client is rate limited.
+    pub fn is_overload(&self) -> bool {
+        const OVERLOAD_MARKERS: [&str; 4] = ["maxslotsexceeded", "too many", "rate limit", "429"];
+        match self {
+            FetchError::Rpc(message) => {
+                let message = message.to_lowercase();
+                OVERLOAD_MARKERS
+                    .iter()
+                    .any(|marker| message.contains(marker))
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn rpc(error: impl std::fmt::Display) -> Self {
+        FetchError::Rpc(error.to_string())
+    }
+
+    pub(crate) fn decode(what: &'static str, error: impl std::fmt::Display) -> Self {
+        FetchError::Decode {
+            what,
+            reason: error.to_string(),
+        }
+    }
+}
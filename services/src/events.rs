@@ -0,0 +1,695 @@
+//! Index of the events of SP1Vector deployments in SQLite, so the commitments, heads and authority
+//! sets a contract stored are looked up locally instead of rescanned from its logs on every query.
+//!
+//! Only Ethereum blocks at least `confirmations` deep are indexed. After each batch of blocks the
+//! hash of its last block is kept as a checkpoint. If a reorg deeper than the confirmation depth
+//! replaces a checkpointed block, the events after the newest checkpoint still on the chain are
+//! dropped and indexed again.
+//!
+//! The deployments are listed in the same JSON file as the `query` service uses:
+//!
+//! ```json
+//! {
+//!     "deployments": [
+//!         {
+//!             "contractChainId": 11155111,
+//!             "contractAddress": "0xe542db219a7e2b29c7aeaeace242c9a2cd528f96",
+//!             "cursorStartBlock": 6204379,
+//!             "availChainId": "turing"
+//!         }
+//!     ]
+//! }
+//! ```
+
+use std::fs::File;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Mutex;
+
+use alloy_primitives::{Address, B256};
+use log::warn;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+
+use crate::contract::{
+    ContractClient, ContractEvent, ContractLog, HeaderRangeCommitment, LOG_BATCH_BLOCKS,
+};
+use crate::error::FetchError;
+use crate::input::Result;
+
+/// Depth at which Ethereum blocks are indexed by default.
+pub const DEFAULT_CONFIRMATIONS: u64 = 12;
+
+/// Number of checkpoints kept per contract.
+const KEPT_CHECKPOINTS: u32 = 64;
+
+/// Number of times a batch of logs is fetched again when a reorg changes its last block while the
+/// logs are read.
+const MAX_REORG_RETRIES: u32 = 3;
+
+/// A contract, by the id of the chain it is deployed on and its address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ContractId {
+    pub chain_id: u64,
+    pub address: Address,
+}
+
+/// Where an event was emitted, as the Ethereum block number and the index of the log in the block.
+/// Events are ordered by their position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct LogPosition {
+    pub eth_block: u64,
+    pub log_index: u64,
+}
+
+/// An SP1Vector deployment to index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Deployment {
+    pub contract_chain_id: u64,
+    pub contract_address: Address,
+    /// First Ethereum block to index, e.g. the block the contract was deployed in.
+    #[serde(default)]
+    pub cursor_start_block: u64,
+    /// Avail chain the contract follows.
+    pub avail_chain_id: String,
+}
+
+impl Deployment {
+    pub fn id(&self) -> ContractId {
+        ContractId {
+            chain_id: self.contract_chain_id,
+            address: self.contract_address,
+        }
+    }
+}
+
+/// The deployments listed in a deployments file.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Deployments {
+    pub deployments: Vec<Deployment>,
+}
+
+impl Deployments {
+    /// Read the deployments from the JSON file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| {
+            FetchError::Config(format!(
+                "Failed to open deployments {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        serde_json::from_reader(file).map_err(|e| {
+            FetchError::Config(format!("Invalid deployments {}: {}", path.display(), e))
+        })
+    }
+}
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS head_updates (
+    chain_id INTEGER NOT NULL,
+    address BLOB NOT NULL,
+    eth_block INTEGER NOT NULL,
+    log_index INTEGER NOT NULL,
+    block_number INTEGER NOT NULL,
+    header_hash BLOB NOT NULL,
+    PRIMARY KEY (chain_id, address, eth_block, log_index)
+);
+CREATE TABLE IF NOT EXISTS header_range_commitments (
+    chain_id INTEGER NOT NULL,
+    address BLOB NOT NULL,
+    eth_block INTEGER NOT NULL,
+    log_index INTEGER NOT NULL,
+    start_block INTEGER NOT NULL,
+    end_block INTEGER NOT NULL,
+    data_commitment BLOB NOT NULL,
+    state_commitment BLOB NOT NULL,
+    commitment_tree_size INTEGER NOT NULL,
+    PRIMARY KEY (chain_id, address, eth_block, log_index)
+);
+CREATE INDEX IF NOT EXISTS header_range_commitments_by_end_block
+    ON header_range_commitments (chain_id, address, end_block);
+CREATE TABLE IF NOT EXISTS authority_sets (
+    chain_id INTEGER NOT NULL,
+    address BLOB NOT NULL,
+    eth_block INTEGER NOT NULL,
+    log_index INTEGER NOT NULL,
+    authority_set_id INTEGER NOT NULL,
+    authority_set_hash BLOB NOT NULL,
+    PRIMARY KEY (chain_id, address, eth_block, log_index)
+);
+CREATE TABLE IF NOT EXISTS checkpoints (
+    chain_id INTEGER NOT NULL,
+    address BLOB NOT NULL,
+    eth_block INTEGER NOT NULL,
+    block_hash BLOB NOT NULL,
+    PRIMARY KEY (chain_id, address, eth_block)
+);
+";
+
+const EVENT_TABLES: [&str; 3] = ["head_updates", "header_range_commitments", "authority_sets"];
+
+const COMMITMENT_COLUMNS: &str =
+    "start_block, end_block, data_commitment, state_commitment, commitment_tree_size";
+
+fn storage_error(error: impl std::fmt::Display) -> FetchError {
+    FetchError::Storage(error.to_string())
+}
+
+/// Condition on the events emitted after the position in the parameters `?{param}` (Ethereum
+/// block) and `?{param + 1}` (log index), or on all events if they are NULL.
+fn after_position(param: usize) -> String {
+    format!(
+        "(?{0} IS NULL OR eth_block > ?{0} OR (eth_block = ?{0} AND log_index > ?{1}))",
+        param,
+        param + 1
+    )
+}
+
+fn read_hash(row: &Row, index: usize) -> rusqlite::Result<B256> {
+    let hash: Vec<u8> = row.get(index)?;
+    B256::try_from(hash.as_slice()).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Blob, Box::new(e))
+    })
+}
+
+fn read_commitment(row: &Row) -> rusqlite::Result<HeaderRangeCommitment> {
+    Ok(HeaderRangeCommitment {
+        start_block: row.get(0)?,
+        end_block: row.get(1)?,
+        data_commitment: read_hash(row, 2)?,
+        state_commitment: read_hash(row, 3)?,
+        commitment_tree_size: row.get(4)?,
+    })
+}
+
+/// The indexed events of any number of contracts.
+pub struct ContractEventIndex {
+    conn: Mutex<Connection>,
+}
+
+impl ContractEventIndex {
+    /// Open the index at `path`, creating the database if it does not exist.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::init(Connection::open(path).map_err(storage_error)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory().map_err(storage_error)?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA).map_err(storage_error)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// The checkpoints of `contract`, newest first.
+    pub fn checkpoints(&self, contract: ContractId) -> Result<Vec<(u64, B256)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT eth_block, block_hash FROM checkpoints WHERE chain_id = ?1 AND address = ?2 ORDER BY eth_block DESC",
+            )
+            .map_err(storage_error)?;
+        let checkpoints = stmt
+            .query_map(
+                params![contract.chain_id, contract.address.as_slice()],
+                |row| Ok((row.get(0)?, read_hash(row, 1)?)),
+            )
+            .map_err(storage_error)?
+            .collect::<rusqlite::Result<_>>()
+            .map_err(storage_error);
+        checkpoints
+    }
+
+    /// Store the events of `contract` emitted up to the checkpointed block, which is then the
+    /// newest checkpoint.
+    pub fn apply(
+        &self,
+        contract: ContractId,
+        logs: &[ContractLog],
+        checkpoint: (u64, B256),
+    ) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(storage_error)?;
+        let chain_id = contract.chain_id;
+        let address = contract.address.as_slice();
+        for log in logs {
+            let (eth_block, log_index) = (log.eth_block_number, log.log_index);
+            match log.event {
+                ContractEvent::HeadUpdate {
+                    block_number,
+                    header_hash,
+                } => tx.execute(
+                    "INSERT OR REPLACE INTO head_updates (chain_id, address, eth_block, log_index, block_number, header_hash) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![chain_id, address, eth_block, log_index, block_number, header_hash.as_slice()],
+                ),
+                ContractEvent::HeaderRangeCommitmentStored(commitment) => tx.execute(
+                    &format!(
+                        "INSERT OR REPLACE INTO header_range_commitments (chain_id, address, eth_block, log_index, {}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                        COMMITMENT_COLUMNS
+                    ),
+                    params![
+                        chain_id,
+                        address,
+                        eth_block,
+                        log_index,
+                        commitment.start_block,
+                        commitment.end_block,
+                        commitment.data_commitment.as_slice(),
+                        commitment.state_commitment.as_slice(),
+                        commitment.commitment_tree_size
+                    ],
+                ),
+                ContractEvent::AuthoritySetStored {
+                    authority_set_id,
+                    authority_set_hash,
+                } => tx.execute(
+                    "INSERT OR REPLACE INTO authority_sets (chain_id, address, eth_block, log_index, authority_set_id, authority_set_hash) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![chain_id, address, eth_block, log_index, authority_set_id, authority_set_hash.as_slice()],
+                ),
+            }
+            .map_err(storage_error)?;
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO checkpoints (chain_id, address, eth_block, block_hash) VALUES (?1, ?2, ?3, ?4)",
+            params![chain_id, address, checkpoint.0, checkpoint.1.as_slice()],
+        )
+        .map_err(storage_error)?;
+        tx.execute(
+            "DELETE FROM checkpoints WHERE chain_id = ?1 AND address = ?2 AND eth_block NOT IN (SELECT eth_block FROM checkpoints WHERE chain_id = ?1 AND address = ?2 ORDER BY eth_block DESC LIMIT ?3)",
+            params![chain_id, address, KEPT_CHECKPOINTS],
+        )
+        .map_err(storage_error)?;
+        tx.commit().map_err(storage_error)
+    }
+
+    /// Drop the events and checkpoints of `contract` from Ethereum block `eth_block` on.
+    pub fn rollback(&self, contract: ContractId, eth_block: u64) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(storage_error)?;
+        for table in EVENT_TABLES.iter().chain(&["checkpoints"]) {
+            tx.execute(
+                &format!(
+                    "DELETE FROM {} WHERE chain_id = ?1 AND address = ?2 AND eth_block >= ?3",
+                    table
+                ),
+                params![contract.chain_id, contract.address.as_slice(), eth_block],
+            )
+            .map_err(storage_error)?;
+        }
+        tx.commit().map_err(storage_error)
+    }
+
+    /// The commitment of `contract` that covers Avail block `block_number`. If the range was
+    /// stored more than once, e.g. by the guardian, the latest commitment is returned.
+    pub fn commitment_for_block(
+        &self,
+        contract: ContractId,
+        block_number: u32,
+    ) -> Result<Option<HeaderRangeCommitment>> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                &format!(
+                    "SELECT {} FROM header_range_commitments WHERE chain_id = ?1 AND address = ?2 AND start_block < ?3 AND end_block >= ?3 ORDER BY eth_block DESC, log_index DESC LIMIT 1",
+                    COMMITMENT_COLUMNS
+                ),
+                params![contract.chain_id, contract.address.as_slice(), block_number],
+                read_commitment,
+            )
+            .optional()
+            .map_err(storage_error)
+    }
+
+    /// The commitments of `contract` that cover any of the Avail blocks in `blocks` and were
+    /// stored after `after`, ordered by end block and then by when they were stored.
+    pub fn commitments(
+        &self,
+        contract: ContractId,
+        blocks: Range<u32>,
+        after: Option<LogPosition>,
+    ) -> Result<Vec<HeaderRangeCommitment>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM header_range_commitments WHERE chain_id = ?1 AND address = ?2 AND start_block + 1 < ?4 AND end_block >= ?3 AND {} ORDER BY end_block, eth_block, log_index",
+                COMMITMENT_COLUMNS,
+                after_position(5)
+            ))
+            .map_err(storage_error)?;
+        let commitments = stmt
+            .query_map(
+                params![
+                    contract.chain_id,
+                    contract.address.as_slice(),
+                    blocks.start,
+                    blocks.end,
+                    after.map(|after| after.eth_block),
+                    after.map(|after| after.log_index)
+                ],
+                read_commitment,
+            )
+            .map_err(storage_error)?
+            .collect::<rusqlite::Result<_>>()
+            .map_err(storage_error);
+        commitments
+    }
+
+    /// The latest head of `contract`, as its block number and header hash.
+    pub fn latest_head(&self, contract: ContractId) -> Result<Option<(u32, B256)>> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT block_number, header_hash FROM head_updates WHERE chain_id = ?1 AND address = ?2 ORDER BY eth_block DESC, log_index DESC LIMIT 1",
+                params![contract.chain_id, contract.address.as_slice()],
+                |row| Ok((row.get(0)?, read_hash(row, 1)?)),
+            )
+            .optional()
+            .map_err(storage_error)
+    }
+
+    /// The position of the latest commitment or authority set event of `contract`.
+    pub fn latest_position(&self, contract: ContractId) -> Result<Option<LogPosition>> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT eth_block, log_index FROM header_range_commitments WHERE chain_id = ?1 AND address = ?2 UNION ALL SELECT eth_block, log_index FROM authority_sets WHERE chain_id = ?1 AND address = ?2 ORDER BY eth_block DESC, log_index DESC LIMIT 1",
+                params![contract.chain_id, contract.address.as_slice()],
+                |row| {
+                    Ok(LogPosition {
+                        eth_block: row.get(0)?,
+                        log_index: row.get(1)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(storage_error)
+    }
+
+    /// The header hash `contract` stored for Avail block `block_number`.
+    pub fn header_hash(&self, contract: ContractId, block_number: u32) -> Result<Option<B256>> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT header_hash FROM head_updates WHERE chain_id = ?1 AND address = ?2 AND block_number = ?3 ORDER BY eth_block DESC, log_index DESC LIMIT 1",
+                params![contract.chain_id, contract.address.as_slice(), block_number],
+                |row| read_hash(row, 0),
+            )
+            .optional()
+            .map_err(storage_error)
+    }
+
+    /// The authority sets `contract` stored after `after`, as their id and hash, ordered by id and
+    /// then by when they were stored.
+    pub fn authority_sets(
+        &self,
+        contract: ContractId,
+        after: Option<LogPosition>,
+    ) -> Result<Vec<(u64, B256)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT authority_set_id, authority_set_hash FROM authority_sets WHERE chain_id = ?1 AND address = ?2 AND {} ORDER BY authority_set_id, eth_block, log_index",
+                after_position(3)
+            ))
+            .map_err(storage_error)?;
+        let authority_sets = stmt
+            .query_map(
+                params![
+                    contract.chain_id,
+                    contract.address.as_slice(),
+                    after.map(|after| after.eth_block),
+                    after.map(|after| after.log_index)
+                ],
+                |row| Ok((row.get(0)?, read_hash(row, 1)?)),
+            )
+            .map_err(storage_error)?
+            .collect::<rusqlite::Result<_>>()
+            .map_err(storage_error);
+        authority_sets
+    }
+
+    /// The authority set hash `contract` stored for `authority_set_id`.
+    pub fn authority_set_hash(
+        &self,
+        contract: ContractId,
+        authority_set_id: u64,
+    ) -> Result<Option<B256>> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT authority_set_hash FROM authority_sets WHERE chain_id = ?1 AND address = ?2 AND authority_set_id = ?3 ORDER BY eth_block DESC, log_index DESC LIMIT 1",
+                params![contract.chain_id, contract.address.as_slice(), authority_set_id],
+                |row| read_hash(row, 0),
+            )
+            .optional()
+            .map_err(storage_error)
+    }
+}
+
+/// What a sync of a deployment indexed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncReport {
+    /// Ethereum blocks that were indexed, empty if there were no new confirmed blocks.
+    pub blocks: Range<u64>,
+    /// Number of events indexed.
+    pub events: usize,
+    /// The block from which events were dropped after a reorg, if any.
+    pub rolled_back_from: Option<u64>,
+}
+
+/// Hash of the confirmed Ethereum block `block_number`.
+async fn confirmed_block_hash(client: &ContractClient, block_number: u64) -> Result<B256> {
+    client
+        .block_hash(block_number)
+        .await?
+        .ok_or_else(|| FetchError::Ethereum(format!("Confirmed block {} not found", block_number)))
+}
+
+/// The logs in blocks `from_block..=to_block` and the hash of `to_block` they were read at. The
+/// hash is read before and after the logs, and the logs are fetched again if a reorg changed it in
+/// between, so the checkpoint always matches the indexed logs.
+async fn fetch_logs(
+    client: &ContractClient,
+    from_block: u64,
+    to_block: u64,
+) -> Result<(Vec<ContractLog>, B256)> {
+    let mut retries = 0;
+    loop {
+        let block_hash = confirmed_block_hash(client, to_block).await?;
+        let logs = client.events(from_block, to_block).await?;
+        if confirmed_block_hash(client, to_block).await? == block_hash {
+            return Ok((logs, block_hash));
+        }
+        if retries == MAX_REORG_RETRIES {
+            return Err(FetchError::Ethereum(format!(
+                "Ethereum block {} was reorged {} times while its logs were read",
+                to_block,
+                retries + 1
+            )));
+        }
+        retries += 1;
+        warn!(
+            "Ethereum block {} was reorged while its logs were read, fetching them again",
+            to_block
+        );
+    }
+}
+
+/// Index the events of `deployment` in the Ethereum blocks confirmed since the last sync.
+pub async fn sync(
+    client: &ContractClient,
+    index: &ContractEventIndex,
+    deployment: &Deployment,
+    confirmations: u64,
+) -> Result<SyncReport> {
+    let contract = deployment.id();
+    let mut next_block = deployment.cursor_start_block;
+    let mut rolled_back_from = None;
+
+    let checkpoints = index.checkpoints(contract)?;
+    if let Some(&(latest_checkpoint, _)) = checkpoints.first() {
+        let mut resume_after = None;
+        for (eth_block, block_hash) in checkpoints {
+            if client.block_hash(eth_block).await? == Some(block_hash) {
+                resume_after = Some(eth_block);
+                break;
+            }
+        }
+        next_block = match resume_after {
+            Some(eth_block) => eth_block + 1,
+            None => deployment.cursor_start_block,
+        };
+        if resume_after != Some(latest_checkpoint) {
+            warn!(
+                "Ethereum block {} of {}:{} was reorged, reindexing from block {}",
+                latest_checkpoint, contract.chain_id, contract.address, next_block
+            );
+            index.rollback(contract, next_block)?;
+            rolled_back_from = Some(next_block);
+        }
+    }
+
+    let confirmed = client.block_number().await?.saturating_sub(confirmations);
+    let mut report = SyncReport {
+        blocks: next_block..next_block,
+        events: 0,
+        rolled_back_from,
+    };
+    while next_block <= confirmed {
+        let to_block = (next_block + LOG_BATCH_BLOCKS - 1).min(confirmed);
+        let (logs, block_hash) = fetch_logs(client, next_block, to_block).await?;
+        index.apply(contract, &logs, (to_block, block_hash))?;
+        report.events += logs.len();
+        report.blocks.end = to_block + 1;
+        next_block = to_block + 1;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTRACT: ContractId = ContractId {
+        chain_id: 11155111,
+        address: Address::repeat_byte(0xaa),
+    };
+
+    fn log(eth_block_number: u64, event: ContractEvent) -> ContractLog {
+        ContractLog {
+            eth_block_number,
+            log_index: 0,
+            event,
+        }
+    }
+
+    fn commitment(start_block: u32, end_block: u32, data_commitment: u8) -> ContractEvent {
+        ContractEvent::HeaderRangeCommitmentStored(HeaderRangeCommitment {
+            start_block,
+            end_block,
+            data_commitment: B256::repeat_byte(data_commitment),
+            state_commitment: B256::ZERO,
+            commitment_tree_size: 256,
+        })
+    }
+
+    #[test]
+    fn test_index_lookups_and_rollback() {
+        let index = ContractEventIndex::open_in_memory().unwrap();
+        index
+            .apply(
+                CONTRACT,
+                &[
+                    log(10, commitment(100, 180, 1)),
+                    log(
+                        10,
+                        ContractEvent::HeadUpdate {
+                            block_number: 180,
+                            header_hash: B256::repeat_byte(0x18),
+                        },
+                    ),
+                    log(
+                        11,
+                        ContractEvent::AuthoritySetStored {
+                            authority_set_id: 7,
+                            authority_set_hash: B256::repeat_byte(7),
+                        },
+                    ),
+                ],
+                (15, B256::repeat_byte(15)),
+            )
+            .unwrap();
+        index
+            .apply(
+                CONTRACT,
+                &[log(20, commitment(180, 260, 2))],
+                (25, B256::repeat_byte(25)),
+            )
+            .unwrap();
+
+        let lookup = |block_number| {
+            index
+                .commitment_for_block(CONTRACT, block_number)
+                .unwrap()
+                .map(|commitment| commitment.end_block)
+        };
+        assert_eq!(lookup(100), None);
+        assert_eq!(lookup(101), Some(180));
+        assert_eq!(lookup(181), Some(260));
+        assert_eq!(lookup(261), None);
+        assert_eq!(
+            index.commitments(CONTRACT, 150..200, None).unwrap().len(),
+            2
+        );
+        assert_eq!(
+            index.commitments(CONTRACT, 181..200, None).unwrap().len(),
+            1
+        );
+        let position = |eth_block| {
+            Some(LogPosition {
+                eth_block,
+                log_index: 0,
+            })
+        };
+        assert_eq!(index.latest_position(CONTRACT).unwrap(), position(20));
+        assert_eq!(
+            index
+                .commitments(CONTRACT, 150..200, position(10))
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            index.latest_head(CONTRACT).unwrap(),
+            Some((180, B256::repeat_byte(0x18)))
+        );
+        assert_eq!(
+            index.header_hash(CONTRACT, 180).unwrap(),
+            Some(B256::repeat_byte(0x18))
+        );
+        assert_eq!(
+            index.authority_set_hash(CONTRACT, 7).unwrap(),
+            Some(B256::repeat_byte(7))
+        );
+        assert_eq!(
+            index.authority_sets(CONTRACT, position(10)).unwrap(),
+            vec![(7, B256::repeat_byte(7))]
+        );
+        assert!(index
+            .authority_sets(CONTRACT, position(11))
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            index.checkpoints(CONTRACT).unwrap(),
+            vec![(25, B256::repeat_byte(25)), (15, B256::repeat_byte(15))]
+        );
+
+        // A reorg after block 15 drops the second commitment.
+        index.rollback(CONTRACT, 16).unwrap();
+        assert_eq!(lookup(181), None);
+        assert_eq!(lookup(101), Some(180));
+        assert_eq!(index.checkpoints(CONTRACT).unwrap().len(), 1);
+
+        // A later commitment of the same range, e.g. by the guardian, takes precedence.
+        index
+            .apply(
+                CONTRACT,
+                &[log(30, commitment(100, 180, 3))],
+                (35, B256::repeat_byte(35)),
+            )
+            .unwrap();
+        let commitment = index.commitment_for_block(CONTRACT, 150).unwrap().unwrap();
+        assert_eq!(commitment.data_commitment, B256::repeat_byte(3));
+    }
+}
@@ -0,0 +1,266 @@
+//! Configuration and status of an indexer process that follows justifications on several Avail
+//! networks at once.
+//!
+//! The chains are listed in a JSON file:
+//!
+//! ```json
+//! {
+//!     "chains": [
+//!         {
+//!             "avail_chain_id": "turing",
+//!             "avail_urls": ["wss://turing-rpc.avail.so/ws"],
+//!             "store": { "kind": "dynamodb" }
+//!         },
+//!         {
+//!             "avail_chain_id": "hex",
+//!             "avail_urls": ["wss://rpc-hex-devnet.avail.tools/ws"],
+//!             "store": { "kind": "sqlite", "path": "hex.db", "format": "compact" },
+//!             "retention": { "keep_recent_blocks": 4320, "keep_every": 100 }
+//!         }
+//!     ]
+//! }
+//! ```
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::FetchError;
+use crate::input::{Result, RpcDataFetcher, RpcDataFetcherBuilder};
+use crate::retention::RetentionPolicy;
+use crate::store::StoreConfig;
+
+/// Chains indexed by one indexer process.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct IndexerConfig {
+    pub chains: Vec<ChainConfig>,
+}
+
+/// One Avail network to index.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ChainConfig {
+    pub avail_chain_id: String,
+    /// Websocket URLs of the Avail RPC endpoints. The first URL is preferred.
+    pub avail_urls: Vec<String>,
+    /// Number of endpoints that must agree on proof inputs. Defaults to 1.
+    #[serde(default)]
+    pub rpc_quorum: Option<usize>,
+    /// SQLite file caching finalized headers and authority sets.
+    #[serde(default)]
+    pub cache_path: Option<PathBuf>,
+    /// Store the justifications are written to. Defaults to the DynamoDB table.
+    #[serde(default)]
+    pub store: StoreConfig,
+    /// Which old justifications to prune. Without a policy every justification is kept.
+    #[serde(default)]
+    pub retention: Option<RetentionPolicy>,
+}
+
+impl IndexerConfig {
+    /// Read the configuration from the JSON file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| {
+            FetchError::Config(format!(
+                "Failed to open indexer config {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        let config: IndexerConfig = serde_json::from_reader(file).map_err(|e| {
+            FetchError::Config(format!("Invalid indexer config {}: {}", path.display(), e))
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.chains.is_empty() {
+            return Err(FetchError::Config(
+                "The indexer config lists no chains".to_string(),
+            ));
+        }
+        let mut avail_chain_ids = HashSet::new();
+        for chain in &self.chains {
+            if !avail_chain_ids.insert(chain.avail_chain_id.to_lowercase()) {
+                return Err(FetchError::Config(format!(
+                    "Chain {} is listed more than once",
+                    chain.avail_chain_id
+                )));
+            }
+            if chain.avail_urls.is_empty() {
+                return Err(FetchError::Config(format!(
+                    "Chain {} has no Avail URLs",
+                    chain.avail_chain_id
+                )));
+            }
+            if let Some(retention) = &chain.retention {
+                retention.validate()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The chain with id `avail_chain_id`.
+    pub fn chain(&self, avail_chain_id: &str) -> Option<&ChainConfig> {
+        self.chains
+            .iter()
+            .find(|chain| chain.avail_chain_id.eq_ignore_ascii_case(avail_chain_id))
+    }
+}
+
+impl ChainConfig {
+    /// Builder for a fetcher of this chain. Unlike `RpcDataFetcherBuilder::from_env`, nothing is
+    /// read from the environment, so chains do not pick up each other's settings.
+    pub fn fetcher_builder(&self) -> RpcDataFetcherBuilder {
+        let mut builder = RpcDataFetcher::builder()
+            .avail_urls(self.avail_urls.iter().cloned())
+            .avail_chain_id(self.avail_chain_id.clone());
+        if let Some(quorum) = self.rpc_quorum {
+            builder = builder.rpc_quorum(quorum);
+        }
+        if let Some(cache_path) = &self.cache_path {
+            builder = builder.cache_path(cache_path.clone());
+        }
+        builder
+    }
+}
+
+/// What the indexer task of a chain is doing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChainState {
+    /// Connecting to the Avail RPC, or filling the blocks finalized while disconnected.
+    #[default]
+    Connecting,
+    /// Subscribed to new justifications.
+    Listening,
+    /// Waiting to reconnect after a failure.
+    Disconnected,
+}
+
+/// Status of the indexer task of one chain.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainStatus {
+    pub state: ChainState,
+    /// Highest block whose justification was stored by this process.
+    pub last_indexed_block: Option<u32>,
+    /// Number of justifications stored by this process.
+    pub indexed: u64,
+    /// Number of justifications rejected because they failed verification.
+    pub rejected: u64,
+    /// Number of times the task reconnected after a failure.
+    pub reconnects: u64,
+    /// The most recent failure.
+    pub last_error: Option<String>,
+}
+
+/// Status of every chain in the process, keyed by lowercased Avail chain id. Cloning shares the
+/// status.
+#[derive(Debug, Clone, Default)]
+pub struct IndexerStatus {
+    chains: Arc<Mutex<BTreeMap<String, ChainStatus>>>,
+}
+
+impl IndexerStatus {
+    /// Update the status of `avail_chain_id`, starting from the default status if it has none.
+    pub fn update(&self, avail_chain_id: &str, update: impl FnOnce(&mut ChainStatus)) {
+        let mut chains = self.chains.lock().unwrap();
+        update(chains.entry(avail_chain_id.to_lowercase()).or_default());
+    }
+
+    pub fn get(&self, avail_chain_id: &str) -> Option<ChainStatus> {
+        self.chains
+            .lock()
+            .unwrap()
+            .get(&avail_chain_id.to_lowercase())
+            .cloned()
+    }
+
+    /// Status of every chain, ordered by chain id.
+    pub fn snapshot(&self) -> Vec<(String, ChainStatus)> {
+        self.chains
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(avail_chain_id, status)| (avail_chain_id.clone(), status.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{RecordFormat, StoreBackend};
+
+    #[test]
+    fn test_parse_config() {
+        let config: IndexerConfig = serde_json::from_str(
+            r#"{
+                "chains": [
+                    { "avail_chain_id": "turing", "avail_urls": ["wss://a", "wss://b"] },
+                    {
+                        "avail_chain_id": "hex",
+                        "avail_urls": ["wss://c"],
+                        "rpc_quorum": 2,
+                        "store": { "kind": "sqlite", "path": "hex.db", "format": "compact" },
+                        "retention": { "keep_recent_blocks": 4320, "keep_every": 100 }
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+
+        let turing = config.chain("Turing").unwrap();
+        assert_eq!(turing.store, StoreConfig::default());
+        let hex = config.chain("hex").unwrap();
+        assert_eq!(hex.rpc_quorum, Some(2));
+        assert_eq!(turing.retention, None);
+        assert_eq!(
+            hex.retention,
+            Some(RetentionPolicy {
+                keep_recent_blocks: 4320,
+                keep_every: 100
+            })
+        );
+        assert_eq!(
+            hex.store,
+            StoreConfig {
+                backend: StoreBackend::Sqlite {
+                    path: PathBuf::from("hex.db")
+                },
+                format: RecordFormat::Compact,
+            }
+        );
+    }
+
+    #[test]
+    fn test_reject_duplicate_chains() {
+        let config: IndexerConfig = serde_json::from_str(
+            r#"{
+                "chains": [
+                    { "avail_chain_id": "turing", "avail_urls": ["wss://a"] },
+                    { "avail_chain_id": "TURING", "avail_urls": ["wss://b"] }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert!(matches!(config.validate(), Err(FetchError::Config(_))));
+    }
+
+    #[test]
+    fn test_status_is_shared() {
+        let status = IndexerStatus::default();
+        let task_status = status.clone();
+        task_status.update("Turing", |status| {
+            status.state = ChainState::Listening;
+            status.last_indexed_block = Some(10);
+        });
+        assert_eq!(status.get("turing").unwrap().last_indexed_block, Some(10));
+        assert_eq!(status.snapshot().len(), 1);
+    }
+}
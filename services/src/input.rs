@@ -1,185 +1,497 @@
-use anyhow::Result;
 use sp1_vector_primitives::types::{
     CircuitJustification, HeaderRangeInputs, HeaderRotateData, Precommit, RotateInputs,
 };
 use sp1_vector_primitives::{
-    compute_authority_set_commitment, consts::HASH_SIZE, verify_encoded_validators,
+    check_justification, compute_authority_set_commitment, consts::HASH_SIZE,
+    header_range::check_header_range, rotate::check_rotate, verify_encoded_validators,
 };
-use sp_core::H256;
 use std::cmp::Ordering;
 use std::env;
-use subxt::backend::rpc::RpcSubscription;
-
-use crate::types::{EncodedFinalityProof, FinalityProof, GrandpaJustification};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::epochs::{Epoch, EpochIndex};
+use crate::error::FetchError;
+use crate::source::{AvailDataSource, EndpointPolicy, HeaderCache, RpcDataSource};
+use crate::store::{AuthoritySet, JustificationRecord, Prefer, StoreConfig, ValidationMetadata};
+use crate::subscription::{JustificationSubscription, DEFAULT_RECENT_JUSTIFICATIONS};
+use crate::types::GrandpaJustification;
 use alloy_primitives::{B256, B512};
 use avail_subxt::avail_client::AvailClient;
 use avail_subxt::config::substrate::DigestItem;
 use avail_subxt::primitives::Header;
-use avail_subxt::{api, RpcParams};
 use codec::{Compact, Decode, Encode};
-use futures::future::join_all;
-use sp_core::ed25519;
 use subxt::config::Header as SubxtHeader;
 
-/// An RPC data fetcher for fetching data for VectorX. The vectorx_query_url is only necessary when
-/// querying justifications.
-pub struct RpcDataFetcher {
-    pub client: AvailClient,
-    pub avail_chain_id: String,
-    pub vectorx_query_url: Option<String>,
+pub use crate::source::RetryPolicy;
+
+pub type Result<T, E = FetchError> = std::result::Result<T, E>;
+
+/// Default timeout for a single Avail RPC or query service request.
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How header ranges are fetched.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HeaderFetchMode {
+    /// Fetch headers by block number. Linkage is only checked once the whole range is fetched.
+    #[default]
+    ByNumber,
+    /// Walk parent hashes back from the justified target block, checking every header's hash and
+    /// number as it is fetched. Needs one request per header instead of two.
+    ParentWalk,
 }
 
-impl RpcDataFetcher {
-    pub async fn new() -> Self {
-        dotenv::dotenv().ok();
+impl std::str::FromStr for HeaderFetchMode {
+    type Err = FetchError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "by_number" => Ok(HeaderFetchMode::ByNumber),
+            "parent_walk" => Ok(HeaderFetchMode::ParentWalk),
+            _ => Err(FetchError::Config(format!(
+                "Unknown header fetch mode {}",
+                s
+            ))),
+        }
+    }
+}
 
-        let url = env::var("AVAIL_URL").expect("AVAIL_URL must be set");
-        let client = AvailClient::new(url.as_str()).await.unwrap();
-        let avail_chain_id = env::var("AVAIL_CHAIN_ID").expect("AVAIL_CHAIN_ID must be set");
-        let vectorx_query_url = env::var("VECTORX_QUERY_URL").ok();
-        RpcDataFetcher {
-            client,
-            avail_chain_id,
-            vectorx_query_url,
+/// Builder for `RpcDataFetcher`. At least one Avail URL and the chain id are required; the VectorX
+/// query URL or a justification store is only necessary when querying justifications.
+#[derive(Debug, Clone)]
+pub struct RpcDataFetcherBuilder {
+    avail_urls: Vec<String>,
+    avail_chain_id: Option<String>,
+    vectorx_query_url: Option<String>,
+    cache_path: Option<PathBuf>,
+    request_timeout: Duration,
+    retry_policy: RetryPolicy,
+    endpoint_policy: EndpointPolicy,
+    header_fetch_mode: HeaderFetchMode,
+    justification_store: Option<StoreConfig>,
+}
+
+impl Default for RpcDataFetcherBuilder {
+    fn default() -> Self {
+        Self {
+            avail_urls: Vec::new(),
+            avail_chain_id: None,
+            vectorx_query_url: None,
+            cache_path: None,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            retry_policy: RetryPolicy::default(),
+            endpoint_policy: EndpointPolicy::default(),
+            header_fetch_mode: HeaderFetchMode::default(),
+            justification_store: None,
         }
     }
+}
 
-    /// Gets a justification from the vectorx-query service, which reads the data from the AWS DB.
-    pub async fn get_justification(&self, block_number: u32) -> Result<GrandpaJustification> {
-        if self.vectorx_query_url.is_none() {
-            return Err(anyhow::anyhow!("VECTORX_QUERY_URL must be set"));
+impl RpcDataFetcherBuilder {
+    /// Websocket URL of the Avail RPC.
+    pub fn avail_url(mut self, avail_url: impl Into<String>) -> Self {
+        self.avail_urls = vec![avail_url.into()];
+        self
+    }
+
+    /// Websocket URLs of several Avail RPC endpoints to fail over between. The first URL is
+    /// preferred.
+    pub fn avail_urls<I: IntoIterator<Item = impl Into<String>>>(mut self, avail_urls: I) -> Self {
+        self.avail_urls = avail_urls.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Number of endpoints that must agree on the block hashes and authority sets of proof inputs.
+    pub fn rpc_quorum(mut self, quorum: usize) -> Self {
+        self.endpoint_policy.quorum = quorum;
+        self
+    }
+
+    /// Number of blocks an endpoint may lag behind the best endpoint before failing over.
+    pub fn max_endpoint_lag(mut self, max_lag: u32) -> Self {
+        self.endpoint_policy.max_lag = max_lag;
+        self
+    }
+
+    /// Avail chain id used to key stored justifications (e.g. hex, turing, mainnet).
+    pub fn avail_chain_id(mut self, avail_chain_id: impl Into<String>) -> Self {
+        self.avail_chain_id = Some(avail_chain_id.into());
+        self
+    }
+
+    /// Base URL of the VectorX query service used to read stored justifications.
+    pub fn vectorx_query_url(mut self, vectorx_query_url: impl Into<String>) -> Self {
+        self.vectorx_query_url = Some(vectorx_query_url.into());
+        self
+    }
+
+    /// Path of a SQLite database used to cache finalized headers, block hashes, authority sets
+    /// and the epoch index across runs. Nothing is persisted if unset.
+    pub fn cache_path(mut self, cache_path: impl Into<PathBuf>) -> Self {
+        self.cache_path = Some(cache_path.into());
+        self
+    }
+
+    /// Timeout for a single RPC or query service request.
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    /// Retry policy for failed RPC and query service requests.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// How header ranges for header range proofs are fetched.
+    pub fn header_fetch_mode(mut self, header_fetch_mode: HeaderFetchMode) -> Self {
+        self.header_fetch_mode = header_fetch_mode;
+        self
+    }
+
+    /// Read justifications from a `JustificationStore` instead of the VectorX query service.
+    pub fn justification_store(mut self, justification_store: StoreConfig) -> Self {
+        self.justification_store = Some(justification_store);
+        self
+    }
+
+    /// Fill any fields that are not set yet from the AVAIL_URL, AVAIL_CHAIN_ID, VECTORX_QUERY_URL
+    /// and AVAIL_CACHE_PATH environment variables. AVAIL_URL may be a comma-separated list of
+    /// endpoints. AVAIL_RPC_QUORUM sets the RPC quorum and AVAIL_HEADER_FETCH_MODE (`by_number`
+    /// or `parent_walk`) the header fetch mode if they are set. The justification store is read
+    /// with `StoreConfig::from_env`. Returns `FetchError::Config` if any of them is invalid.
+    pub fn from_env(mut self) -> Result<Self> {
+        dotenv::dotenv().ok();
+
+        if self.avail_urls.is_empty() {
+            if let Ok(avail_urls) = env::var("AVAIL_URL") {
+                self.avail_urls = avail_urls
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|url| !url.is_empty())
+                    .map(String::from)
+                    .collect();
+            }
+        }
+        if let Ok(quorum) = env::var("AVAIL_RPC_QUORUM") {
+            self.endpoint_policy.quorum = quorum
+                .parse()
+                .map_err(|e| FetchError::Config(format!("Invalid AVAIL_RPC_QUORUM: {}", e)))?;
+        }
+        if let Ok(header_fetch_mode) = env::var("AVAIL_HEADER_FETCH_MODE") {
+            self.header_fetch_mode = header_fetch_mode.parse()?;
         }
+        self.avail_chain_id = self
+            .avail_chain_id
+            .or_else(|| env::var("AVAIL_CHAIN_ID").ok());
+        self.vectorx_query_url = self
+            .vectorx_query_url
+            .or_else(|| env::var("VECTORX_QUERY_URL").ok());
+        self.cache_path = self
+            .cache_path
+            .or_else(|| env::var("AVAIL_CACHE_PATH").ok().map(PathBuf::from));
+        if self.justification_store.is_none() {
+            self.justification_store = StoreConfig::from_env()?;
+        }
+        Ok(self)
+    }
 
-        let base_justification_query_url = format!(
-            "{}/api/justification",
-            self.vectorx_query_url.as_ref().unwrap()
-        );
+    /// Connect to the Avail RPC and build the fetcher.
+    pub async fn build(self) -> Result<RpcDataFetcher> {
+        let avail_chain_id = self
+            .avail_chain_id
+            .ok_or_else(|| FetchError::Config("Avail chain id must be set".to_string()))?;
+
+        let cache = self
+            .cache_path
+            .as_ref()
+            .map(HeaderCache::open)
+            .transpose()?;
+        let epochs = match &self.cache_path {
+            Some(cache_path) => EpochIndex::open(cache_path, avail_chain_id.clone())?,
+            None => EpochIndex::in_memory(avail_chain_id.clone()),
+        };
+        let source = RpcDataSource::connect(
+            &self.avail_urls,
+            avail_chain_id.clone(),
+            self.vectorx_query_url,
+            self.request_timeout,
+            self.retry_policy,
+            self.endpoint_policy,
+            cache,
+        )
+        .await?;
+        let source = match &self.justification_store {
+            Some(justification_store) => {
+                source.with_justification_store(justification_store.open().await?)
+            }
+            None => source,
+        };
 
-        let request_url = format!(
-            "{}?availChainId={}&blockNumber={}",
-            base_justification_query_url, self.avail_chain_id, block_number
-        );
+        Ok(RpcDataFetcher {
+            source,
+            avail_chain_id,
+            epochs,
+            header_fetch_mode: self.header_fetch_mode,
+            justifications: OnceLock::new(),
+        })
+    }
+}
 
-        let response = reqwest::get(request_url).await?;
-        let json_response = response.json::<serde_json::Value>().await?;
-
-        // If the service does not have a justification associated with the block, return an error.
-        // The response will have the following form:
-        // {
-        //     "success": false
-        //     "error": "No justification found."
-        // }
-        let is_success = json_response.get("success").unwrap().as_bool().unwrap();
-        if !is_success {
-            return Err(anyhow::anyhow!(
-                "No justification found for the specified block number."
-            ));
+/// A data fetcher for fetching data for VectorX. Input construction is generic over the
+/// `AvailDataSource`; by default data is read from the live Avail RPC and the VectorX query
+/// service.
+pub struct RpcDataFetcher<S = RpcDataSource> {
+    pub source: S,
+    pub avail_chain_id: String,
+    pub epochs: EpochIndex,
+    pub header_fetch_mode: HeaderFetchMode,
+    /// Subscription to new justifications, started on first use.
+    justifications: OnceLock<JustificationSubscription>,
+}
+
+impl RpcDataFetcher {
+    pub fn builder() -> RpcDataFetcherBuilder {
+        RpcDataFetcherBuilder::default()
+    }
+
+    /// Build a fetcher configured from the AVAIL_URL, AVAIL_CHAIN_ID and VECTORX_QUERY_URL
+    /// environment variables.
+    pub async fn new() -> Result<Self> {
+        Self::builder().from_env()?.build().await
+    }
+
+    /// The underlying Avail client, e.g. for subscriptions.
+    pub fn client(&self) -> &AvailClient {
+        self.source.client()
+    }
+
+    /// The subscription to new justifications shared by every call on this fetcher. It is
+    /// started on first use and stops when the fetcher is dropped.
+    pub fn justification_subscription(&self) -> &JustificationSubscription {
+        self.justifications.get_or_init(|| {
+            self.source
+                .subscribe_justifications(DEFAULT_RECENT_JUSTIFICATIONS)
+        })
+    }
+
+    /// Get the latest justification data. Because Avail does not store the justification data for
+    /// all blocks, we can only generate a proof using the latest justification data or the justification data for a specific block.
+    /// Waits for the next justification after the latest one received by the subscription.
+    pub async fn get_latest_justification_data(&self) -> Result<(CircuitJustification, Header)> {
+        let justification = self.justification_subscription().next().await?;
+        self.justification_data_with_header(justification).await
+    }
+
+    /// Get the justification data of the first justification at or after `block_number` received
+    /// by the subscription, waiting for one if necessary.
+    pub async fn get_next_justification_data(
+        &self,
+        block_number: u32,
+    ) -> Result<(CircuitJustification, Header)> {
+        let justification = self
+            .justification_subscription()
+            .next_at_or_after(block_number)
+            .await?;
+        self.justification_data_with_header(justification).await
+    }
+
+    async fn justification_data_with_header(
+        &self,
+        justification: GrandpaJustification,
+    ) -> Result<(CircuitJustification, Header)> {
+        // Get the header corresponding to the justification.
+        let header = self
+            .source
+            .header_by_hash(B256::from(justification.commit.target_hash.0))
+            .await?
+            .ok_or(FetchError::BlockNotFound(
+                justification.commit.target_number,
+            ))?;
+        let block_number = header.number;
+        Ok((
+            self.compute_data_from_justification(justification, block_number)
+                .await?,
+            header,
+        ))
+    }
+}
+
+impl<S: AvailDataSource> RpcDataFetcher<S> {
+    /// Build a fetcher that reads chain data from `source`, with an in-memory epoch index.
+    pub fn with_source(source: S, avail_chain_id: impl Into<String>) -> Self {
+        let avail_chain_id = avail_chain_id.into();
+        Self {
+            source,
+            epochs: EpochIndex::in_memory(avail_chain_id.clone()),
+            avail_chain_id,
+            header_fetch_mode: HeaderFetchMode::default(),
+            justifications: OnceLock::new(),
         }
+    }
+
+    /// Gets a stored justification for the block. Returns `FetchError::MissingJustification` if
+    /// there is none.
+    pub async fn get_justification(&self, block_number: u32) -> Result<GrandpaJustification> {
+        self.source.justification(block_number).await
+    }
 
-        // If the service does have a justification, it should have the following form:
-        // {
-        //     "success": true,
-        //     "justification": {
-        //         "S": "<justification as string>"
-        //     }
-        // }
-        let justification_str = json_response
-            .get("justification")
-            .ok_or_else(|| anyhow::anyhow!("Justification field missing"))
-            .expect("Justification field should be present")
-            .get("S")
-            .ok_or_else(|| anyhow::anyhow!("Justification field should be a string"))
-            .expect("Justification field should be a string")
-            .as_str()
-            .ok_or_else(|| anyhow::anyhow!("Justification field should be a string"))
-            .expect("Justification field should be a string");
-        let justification_data: GrandpaJustification =
-            serde_json::from_str(justification_str).expect("Couldn't deserialize!");
-
-        Ok(justification_data)
+    /// The lowest or highest block in `start_block_number..=end_block_number` with a stored
+    /// justification, as preferred.
+    pub async fn nearest_justified_block(
+        &self,
+        start_block_number: u32,
+        end_block_number: u32,
+        prefer: Prefer,
+    ) -> Result<Option<u32>> {
+        self.source
+            .nearest_justified_block(start_block_number, end_block_number, prefer)
+            .await
     }
 
     /// Get the inputs for a header range proof. Optionally pass in the header range commitment tree size.
-    /// If not passed in, it will be set to the nearest power of 2.
+    /// If not passed in, it will be set to the nearest power of 2. The inputs are verified natively
+    /// before they are returned, so invalid inputs are never sent to the prover.
     pub async fn get_header_range_inputs(
         &self,
         trusted_block: u32,
         target_block: u32,
         header_range_commitment_tree_size: Option<u32>,
-    ) -> HeaderRangeInputs {
-        let trusted_header = self.get_header(trusted_block).await;
+    ) -> Result<HeaderRangeInputs> {
+        if target_block <= trusted_block {
+            return Err(FetchError::InvalidRequest(format!(
+                "Target block {} must be after trusted block {}",
+                target_block, trusted_block
+            )));
+        }
+
+        // Pre-flight: confirm both ends of the range with a quorum of sources.
+        self.source.cross_check(trusted_block).await?;
+        self.source.cross_check(target_block).await?;
+
+        let trusted_header = self.get_header(trusted_block).await?;
         let trusted_header_hash: alloy_primitives::FixedBytes<32> =
             B256::from_slice(&trusted_header.hash().0);
 
         let num_headers = target_block - trusted_block + 1;
         let merkle_tree_size: usize;
         if let Some(header_range_commitment_tree_size) = header_range_commitment_tree_size {
-            assert!(
-                header_range_commitment_tree_size >= num_headers
-                    && header_range_commitment_tree_size.is_power_of_two(),
-                "Header range commitment tree size must be greater than or equal to the number of headers and a power of two"
-            );
+            if header_range_commitment_tree_size < num_headers
+                || !header_range_commitment_tree_size.is_power_of_two()
+            {
+                return Err(FetchError::InvalidRequest(
+                    "Header range commitment tree size must be greater than or equal to the number of headers and a power of two".to_string(),
+                ));
+            }
             merkle_tree_size = header_range_commitment_tree_size as usize;
         } else {
             // NOTE: DANGEROUS. ONLY USED IN TESTING. IN PROD, FETCH FROM CONTRACT.
             merkle_tree_size = get_merkle_tree_size(num_headers);
         }
 
-        let headers = self
-            .get_block_headers_range(trusted_block, target_block)
-            .await;
-        let encoded_headers: Vec<Vec<u8>> = headers.iter().map(|header| header.encode()).collect();
+        let (target_justification, _) = self.get_justification_data_for_block(target_block).await?;
 
-        let (target_justification, _) = self
-            .get_justification_data_for_block(target_block)
-            .await
-            .expect("Failed to get justification data for target block.");
+        let headers = match self.header_fetch_mode {
+            HeaderFetchMode::ByNumber => {
+                self.get_block_headers_range(trusted_block, target_block)
+                    .await?
+            }
+            HeaderFetchMode::ParentWalk => {
+                let headers = self
+                    .get_block_headers_by_parent_walk(
+                        trusted_block,
+                        target_block,
+                        target_justification.block_hash,
+                    )
+                    .await?;
+                check_linked_header(&headers[0], trusted_block, trusted_header_hash)?;
+                headers
+            }
+        };
+        let encoded_headers: Vec<Vec<u8>> = headers.iter().map(|header| header.encode()).collect();
 
-        HeaderRangeInputs {
+        let header_range_inputs = HeaderRangeInputs {
             trusted_block,
             target_block,
             trusted_header_hash,
             merkle_tree_size,
             encoded_headers,
             target_justification,
-        }
+        };
+
+        // Pre-flight: run the checks of the SP1 Vector program natively.
+        check_header_range(&header_range_inputs)?;
+
+        Ok(header_range_inputs)
     }
 
-    pub async fn get_rotate_inputs(&self, authority_set_id: u64) -> RotateInputs {
+    /// Get the inputs for a rotate proof. The inputs are verified natively before they are
+    /// returned, so invalid inputs are never sent to the prover.
+    pub async fn get_rotate_inputs(&self, authority_set_id: u64) -> Result<RotateInputs> {
+        // Pre-flight: confirm the epoch end block with a quorum of sources.
+        let epoch = self
+            .get_epoch(authority_set_id)
+            .await?
+            .ok_or(FetchError::AuthoritySetStillActive(authority_set_id))?;
+        self.source.cross_check(epoch.end_block).await?;
+
         let justification = self
             .get_justification_data_epoch_end_block(authority_set_id)
-            .await;
+            .await?;
 
-        let header_rotate_data = self.get_header_rotate(authority_set_id).await;
+        let header_rotate_data = self.get_header_rotate(authority_set_id).await?;
 
-        RotateInputs {
+        let rotate_inputs = RotateInputs {
             justification,
             header_rotate_data,
-        }
+        };
+
+        // Pre-flight: run the checks of the SP1 Vector program natively.
+        check_rotate(&rotate_inputs)?;
+
+        Ok(rotate_inputs)
     }
 
     // This function returns the last block justified by target_authority_set_id. This block
     // also specifies the new authority set, which starts justifying after this block.
     // Returns 0 if curr_authority_set_id <= target_authority_set_id.
-    pub async fn last_justified_block(&self, target_authority_set_id: u64) -> u32 {
-        let mut low = 0;
-        let head_block = self.get_head().await;
-        let mut high = head_block.number;
+    pub async fn last_justified_block(&self, target_authority_set_id: u64) -> Result<u32> {
+        Ok(self
+            .get_epoch(target_authority_set_id)
+            .await?
+            .map_or(0, |epoch| epoch.end_block))
+    }
+
+    /// Get the epoch of `authority_set_id` from the epoch index. If it is not indexed yet, binary
+    /// search for its epoch end block between the neighbouring known epochs and add it to the
+    /// index. Returns `None` if the authority set is still active.
+    pub async fn get_epoch(&self, authority_set_id: u64) -> Result<Option<Epoch>> {
+        if let Some(epoch) = self.epochs.get(authority_set_id) {
+            return Ok(Some(epoch));
+        }
+
+        let (mut low, high) = self.epochs.search_bounds(authority_set_id);
+        let mut high = match high {
+            Some(high) => high,
+            None => self.get_head().await?.number,
+        };
         let mut epoch_end_block_number = 0;
 
         while low <= high {
             let mid = (low + high) / 2;
-            let mid_authority_set_id = self.get_authority_set_id(mid).await;
+            let mid_authority_set_id = self.get_authority_set_id(mid).await?;
 
-            match mid_authority_set_id.cmp(&(target_authority_set_id + 1)) {
+            match mid_authority_set_id.cmp(&(authority_set_id + 1)) {
                 Ordering::Equal => {
                     if mid == 0 {
                         // Special case: there is no block "mid - 1", just return the found block.
                         epoch_end_block_number = mid;
                         break;
                     }
-                    let prev_authority_set_id = self.get_authority_set_id(mid - 1).await;
-                    if prev_authority_set_id == target_authority_set_id {
+                    let prev_authority_set_id = self.get_authority_set_id(mid - 1).await?;
+                    if prev_authority_set_id == authority_set_id {
                         epoch_end_block_number = mid;
                         break;
                     } else {
@@ -190,17 +502,52 @@ impl RpcDataFetcher {
                 Ordering::Greater => high = mid - 1,
             }
         }
-        epoch_end_block_number
+        if epoch_end_block_number == 0 {
+            return Ok(None);
+        }
+
+        let epoch = Epoch {
+            authority_set_id,
+            end_block: epoch_end_block_number,
+            new_authority_set_hash: self
+                .compute_authority_set_hash_for_block(epoch_end_block_number)
+                .await?,
+        };
+        self.epochs.insert(epoch)?;
+        Ok(Some(epoch))
     }
 
-    pub async fn get_block_hash(&self, block_number: u32) -> B256 {
-        let block_hash = self
-            .client
-            .legacy_rpc()
-            .chain_get_block_hash(Some(block_number.into()))
-            .await;
+    pub async fn get_block_hash(&self, block_number: u32) -> Result<B256> {
+        self.source.block_hash(block_number).await
+    }
 
-        B256::from(block_hash.unwrap().unwrap().0)
+    /// Fetch the headers of `start_block_number..=end_block_number` by walking parent hashes back
+    /// from `end_block_hash`. Every header is checked against the hash and number its child
+    /// commits to while fetching, so a stale or forked header is reported at the first block that
+    /// does not link.
+    pub async fn get_block_headers_by_parent_walk(
+        &self,
+        start_block_number: u32,
+        end_block_number: u32,
+        end_block_hash: B256,
+    ) -> Result<Vec<Header>> {
+        let mut headers = Vec::with_capacity((end_block_number - start_block_number + 1) as usize);
+        let mut block_hash = end_block_hash;
+        for block_number in (start_block_number..=end_block_number).rev() {
+            let header = self
+                .source
+                .header_by_hash(block_hash)
+                .await?
+                .ok_or_else(|| FetchError::HeaderLinkage {
+                    block_number,
+                    reason: format!("no header with hash {}", block_hash),
+                })?;
+            check_linked_header(&header, block_number, block_hash)?;
+            block_hash = B256::from(header.parent_hash.0);
+            headers.push(header);
+        }
+        headers.reverse();
+        Ok(headers)
     }
 
     /// This function returns a vector of headers for a given range of block numbers, inclusive of the start and end block numbers.
@@ -208,114 +555,61 @@ impl RpcDataFetcher {
         &self,
         start_block_number: u32,
         end_block_number: u32,
-    ) -> Vec<Header> {
-        // Fetch the headers in batches of MAX_CONCURRENT_WS_REQUESTS. The WS connection will error if there
-        // are too many concurrent requests with Rpc(ClientError(MaxSlotsExceeded)).
-        const MAX_CONCURRENT_WS_REQUESTS: usize = 200;
-        let mut headers = Vec::new();
-        let mut curr_block = start_block_number;
-        while curr_block <= end_block_number {
-            let end_block = std::cmp::min(
-                curr_block + MAX_CONCURRENT_WS_REQUESTS as u32 - 1,
-                end_block_number,
-            );
-            let header_futures: Vec<_> = (curr_block..end_block + 1)
-                .map(|block_number| self.get_header(block_number))
-                .collect();
-
-            // Await all futures concurrently
-            let headers_batch: Vec<Header> = join_all(header_futures)
-                .await
-                .into_iter()
-                .collect::<Vec<_>>();
-
-            headers.extend_from_slice(&headers_batch);
-            curr_block += MAX_CONCURRENT_WS_REQUESTS as u32;
-        }
-        headers
+    ) -> Result<Vec<Header>> {
+        self.source
+            .headers(start_block_number, end_block_number)
+            .await
     }
 
-    pub async fn get_header(&self, block_number: u32) -> Header {
-        let block_hash = self.get_block_hash(block_number).await;
-        let header_result = self
-            .client
-            .legacy_rpc()
-            .chain_get_header(Some(H256::from(block_hash.0)))
-            .await;
-        header_result.unwrap().unwrap()
+    pub async fn get_header(&self, block_number: u32) -> Result<Header> {
+        self.source.header(block_number).await
     }
 
-    pub async fn get_head(&self) -> Header {
-        let head_block_hash = self
-            .client
-            .legacy_rpc()
-            .chain_get_finalized_head()
-            .await
-            .unwrap();
-        let header = self
-            .client
-            .legacy_rpc()
-            .chain_get_header(Some(head_block_hash))
-            .await;
-        header.unwrap().unwrap()
+    /// The header with hash `block_hash`, if the node knows the block.
+    pub async fn get_header_by_hash(&self, block_hash: B256) -> Result<Option<Header>> {
+        self.source.header_by_hash(block_hash).await
     }
 
-    pub async fn get_authority_set_id(&self, block_number: u32) -> u64 {
-        let block_hash = self.get_block_hash(block_number).await;
+    pub async fn get_head(&self) -> Result<Header> {
+        self.source.finalized_head().await
+    }
 
-        let set_id_key = api::storage().grandpa().current_set_id();
-        self.client
-            .storage()
-            .at(H256::from(block_hash.0))
-            .fetch(&set_id_key)
-            .await
-            .unwrap()
-            .unwrap()
+    pub async fn get_authority_set_id(&self, block_number: u32) -> Result<u64> {
+        self.source.authority_set_id(block_number).await
     }
 
-    // This function returns the authorities (as AffinePoint and public key bytes) for a given block number
-    // by fetching the "authorities_bytes" from storage and decoding the bytes to a VersionedAuthorityList.
+    // This function returns the authorities (as public key bytes) for a given block number.
     // Note: The authorities returned by this function attest to block_number + 1.
-    pub async fn get_authorities(&self, block_number: u32) -> Vec<B256> {
-        let block_hash = self.get_block_hash(block_number).await;
-
-        let grandpa_authorities = self
-            .client
-            .runtime_api()
-            .at(H256::from(block_hash.0))
-            .call_raw::<Vec<(ed25519::Public, u64)>>("GrandpaApi_grandpa_authorities", None)
-            .await
-            .unwrap();
-
-        let mut authorities: Vec<B256> = Vec::new();
-        for (pub_key, weight) in grandpa_authorities {
-            authorities.push(B256::from(pub_key.0));
-            let expected_weight = 1;
-            // Assert the LE representation of the weight of each validator is 1.
-            assert_eq!(
-                weight, expected_weight,
-                "The weight of the authority is not 1!"
-            );
-        }
-
-        authorities
+    pub async fn get_authorities(&self, block_number: u32) -> Result<Vec<B256>> {
+        self.source.authorities(block_number).await
     }
 
     /// Gets the authority set id and authority set hash that are defined in block_number. This authority set
     /// attests to block_number + 1.
-    pub async fn get_authority_set_data_for_block(&self, block_number: u32) -> (u64, B256) {
-        let authority_set_id = self.get_authority_set_id(block_number).await;
+    pub async fn get_authority_set_data_for_block(&self, block_number: u32) -> Result<(u64, B256)> {
+        let authority_set_id = self.get_authority_set_id(block_number).await?;
         let authority_set_hash = self
             .compute_authority_set_hash_for_block(block_number)
-            .await;
-        (authority_set_id, authority_set_hash)
+            .await?;
+        Ok((authority_set_id, authority_set_hash))
     }
 
     /// Computes the authority_set_hash for a given block number. Note: This is the authority set hash
     /// that validates the next block after the given block number.
-    pub async fn compute_authority_set_hash_for_block(&self, block_number: u32) -> B256 {
-        let authorities = self.get_authorities(block_number).await;
-        compute_authority_set_commitment(&authorities)
+    pub async fn compute_authority_set_hash_for_block(&self, block_number: u32) -> Result<B256> {
+        let authorities = self.get_authorities(block_number).await?;
+        Ok(compute_authority_set_commitment(&authorities))
+    }
+
+    /// The authority set that justifies `block_number`, i.e. the one defined at the block before it.
+    async fn resolve_authority_set(&self, block_number: u32) -> Result<AuthoritySet> {
+        let authority_set_id = self.get_authority_set_id(block_number - 1).await?;
+        let authorities = self.get_authorities(block_number - 1).await?;
+        Ok(AuthoritySet {
+            authority_set_id,
+            authority_set_hash: compute_authority_set_commitment(&authorities),
+            authorities,
+        })
     }
 
     /// Get the justification data necessary for the circuit using GrandpaJustification and the block number.
@@ -323,69 +617,115 @@ impl RpcDataFetcher {
         &self,
         justification: GrandpaJustification,
         block_number: u32,
-    ) -> CircuitJustification {
-        // Get the authority set id that attested to block_number.
-        let authority_set_id = self.get_authority_set_id(block_number - 1).await;
-
-        // Get the authority set for the block number.
-        let authorities = self.get_authorities(block_number - 1).await;
+    ) -> Result<CircuitJustification> {
+        let authority_set = self.resolve_authority_set(block_number).await?;
+        Ok(convert_justification_and_valset_to_circuit(
+            justification,
+            authority_set.authorities,
+            authority_set.authority_set_id,
+        ))
+    }
 
-        convert_justification_and_valset_to_circuit(justification, authorities, authority_set_id)
+    /// Convert `justification` with the authority set that justifies its block and verify it
+    /// natively. Returns `FetchError::Verification` if the justification is invalid, and
+    /// otherwise a record with the validation metadata and the resolved authority set.
+    pub async fn validate_justification(
+        &self,
+        justification: GrandpaJustification,
+    ) -> Result<JustificationRecord> {
+        let block_number = justification.commit.target_number;
+        if block_number == 0 {
+            return Err(FetchError::InvalidRequest(
+                "The genesis block has no justification".to_string(),
+            ));
+        }
+        let authority_set = self.resolve_authority_set(block_number).await?;
+        let circuit_justification = convert_justification_and_valset_to_circuit(
+            justification.clone(),
+            authority_set.authorities.clone(),
+            authority_set.authority_set_id,
+        );
+        let report = check_justification(&circuit_justification)?;
+        Ok(JustificationRecord {
+            justification,
+            metadata: Some(ValidationMetadata {
+                authority_set_id: report.authority_set_id,
+                signer_count: report.signers.len(),
+                validator_set_size: report.validator_set_size,
+            }),
+            authority_set: Some(authority_set),
+        })
     }
 
-    /// Get the justification for a block using the DB cache from the justification indexer.
+    /// Get the justification for a block using the DB cache from the justification indexer. Returns
+    /// `FetchError::MissingJustification` if the indexer has no justification for the block.
+    /// Compact records already hold the authority set of the block. It is used without further
+    /// RPC calls if the epoch index confirms it, and fetched from the chain otherwise.
     pub async fn get_justification_data_for_block(
         &self,
         block_number: u32,
-    ) -> Option<(CircuitJustification, Header)> {
-        let grandpa_justification = self.get_justification(block_number).await;
-
-        if grandpa_justification.is_err() {
-            return None;
-        }
-        let grandpa_justification = grandpa_justification.unwrap();
+    ) -> Result<(CircuitJustification, Header)> {
+        let record = self.source.justification_record(block_number).await?;
 
-        let header = self.get_header(block_number).await;
+        let header = self.get_header(block_number).await?;
 
         // Convert DB stored justification into CircuitJustification.
-        let circuit_justification = self
-            .compute_data_from_justification(grandpa_justification, block_number)
-            .await;
-        Some((circuit_justification, header))
+        let circuit_justification = match record.authority_set {
+            Some(authority_set)
+                if self.is_indexed_authority_set(
+                    &authority_set,
+                    record.metadata.as_ref(),
+                    block_number,
+                ) =>
+            {
+                convert_justification_and_valset_to_circuit(
+                    record.justification,
+                    authority_set.authorities,
+                    authority_set.authority_set_id,
+                )
+            }
+            _ => {
+                self.compute_data_from_justification(record.justification, block_number)
+                    .await?
+            }
+        };
+        Ok((circuit_justification, header))
     }
 
-    /// Get the latest justification data. Because Avail does not store the justification data for
-    /// all blocks, we can only generate a proof using the latest justification data or the justification data for a specific block.
-    pub async fn get_latest_justification_data(&self) -> (CircuitJustification, Header) {
-        let sub: Result<RpcSubscription<GrandpaJustification>, _> = self
-            .client
-            .rpc()
-            .subscribe(
-                "grandpa_subscribeJustifications",
-                RpcParams::new(),
-                "grandpa_unsubscribeJustifications",
-            )
-            .await;
-        let mut sub = sub.unwrap();
-
-        // Wait for new justification.
-        if let Some(Ok(justification)) = sub.next().await {
-            // Get the header corresponding to the new justification.
-            let header = self
-                .client
-                .legacy_rpc()
-                .chain_get_header(Some(justification.commit.target_hash))
-                .await
-                .unwrap()
-                .unwrap();
-            let block_number = header.number;
-            return (
-                self.compute_data_from_justification(justification, block_number)
-                    .await,
-                header,
-            );
+    /// Whether the stored `authority_set` justifies `block_number`, judged from the epoch index
+    /// and the validation `metadata` stored with it, without RPC calls. The epoch of the previous
+    /// authority set must be indexed, end before `block_number` and have enacted the stored hash.
+    /// The block must be in the epoch of the stored set, as indexed or as validated by the indexer.
+    /// The genesis authority set has no previous epoch, so it is never confirmed.
+    fn is_indexed_authority_set(
+        &self,
+        authority_set: &AuthoritySet,
+        metadata: Option<&ValidationMetadata>,
+        block_number: u32,
+    ) -> bool {
+        let authority_set_id = authority_set.authority_set_id;
+        if block_number == 0
+            || authority_set.authorities.is_empty()
+            || compute_authority_set_commitment(&authority_set.authorities)
+                != authority_set.authority_set_hash
+        {
+            return false;
+        }
+        let Some(previous) = authority_set_id
+            .checked_sub(1)
+            .and_then(|previous| self.epochs.get(previous))
+        else {
+            return false;
+        };
+        if previous.end_block >= block_number
+            || previous.new_authority_set_hash != authority_set.authority_set_hash
+        {
+            return false;
+        }
+        match self.epochs.get(authority_set_id) {
+            Some(epoch) => block_number <= epoch.end_block,
+            None => metadata.is_some_and(|metadata| metadata.authority_set_id == authority_set_id),
         }
-        panic!("No justification found")
     }
 
     /// Get the justification data for an epoch end block from the curr_authority_set_id to the next authority set id.
@@ -395,27 +735,17 @@ impl RpcDataFetcher {
     pub async fn get_justification_data_epoch_end_block(
         &self,
         curr_authority_set_id: u64,
-    ) -> CircuitJustification {
-        let epoch_end_block = self.last_justified_block(curr_authority_set_id).await;
+    ) -> Result<CircuitJustification> {
+        let epoch_end_block = self.last_justified_block(curr_authority_set_id).await?;
         if epoch_end_block == 0 {
-            panic!("Current authority set is still active!");
+            return Err(FetchError::AuthoritySetStillActive(curr_authority_set_id));
         }
 
-        // If epoch end block, use grandpa_proveFinality to get the justification.
-        let mut params = RpcParams::new();
-        let _ = params.push(epoch_end_block);
-
-        let encoded_finality_proof = self
-            .client
-            .rpc()
-            .request::<EncodedFinalityProof>("grandpa_proveFinality", params)
-            .await
-            .unwrap();
-
-        let finality_proof: FinalityProof =
-            Decode::decode(&mut encoded_finality_proof.0 .0.as_slice()).unwrap();
+        // If epoch end block, use the finality proof to get the justification.
+        let finality_proof = self.source.finality_proof(epoch_end_block).await?;
         let justification: GrandpaJustification =
-            Decode::decode(&mut finality_proof.justification.as_slice()).unwrap();
+            Decode::decode(&mut finality_proof.justification.as_slice())
+                .map_err(|e| FetchError::decode("justification", e))?;
 
         self.compute_data_from_justification(justification, epoch_end_block)
             .await
@@ -425,18 +755,18 @@ impl RpcDataFetcher {
     /// in the epoch end block. It returns the data necessary to prove the new authority set, which
     /// specifies the new authority set hash, the number of authorities, and the start and end
     /// position of the encoded new authority set in the header.
-    pub async fn get_header_rotate(&self, authority_set_id: u64) -> HeaderRotateData {
-        let epoch_end_block = self.last_justified_block(authority_set_id).await;
+    pub async fn get_header_rotate(&self, authority_set_id: u64) -> Result<HeaderRotateData> {
+        let epoch_end_block = self.last_justified_block(authority_set_id).await?;
         if epoch_end_block == 0 {
-            panic!("Current authority set is still active!");
+            return Err(FetchError::AuthoritySetStillActive(authority_set_id));
         }
 
-        let header = self.get_header(epoch_end_block).await;
+        let header = self.get_header(epoch_end_block).await?;
 
         let header_bytes = header.encode();
 
         // Fetch the new authority set specified in the epoch end block.
-        let new_authorities = self.get_authorities(epoch_end_block).await;
+        let new_authorities = self.get_authorities(epoch_end_block).await?;
 
         let num_authorities = new_authorities.len();
         let encoded_num_authorities_len = Compact(num_authorities as u32).encode().len();
@@ -454,14 +784,17 @@ impl RpcDataFetcher {
                     found_correct_log = true;
 
                     // Denotes that this is a `ScheduledChange` log.
-                    assert_eq!(value[0], 1);
+                    if value.first() != Some(&1) {
+                        return Err(FetchError::MissingConsensusLog(epoch_end_block));
+                    }
 
                     // The bytes after the prefix are the compact encoded number of authorities.
                     // Follows the encoding format: https://docs.substrate.io/reference/scale-codec/#fn-1
                     // If the number of authorities is <=63, the compact encoding is 1 byte.
                     // If the number of authorities is >63 & < 2^14, the compact encoding is 2 bytes.
                     let cursor = 1 + encoded_num_authorities_len;
-                    verify_encoded_validators(&value, cursor, &new_authorities);
+                    verify_encoded_validators(&value, cursor, &new_authorities)
+                        .map_err(|e| FetchError::decode("scheduled change consensus log", e))?;
 
                     break;
                 }
@@ -472,23 +805,19 @@ impl RpcDataFetcher {
             }
         }
 
-        // Panic if there is not a consensus log.
+        // Return an error if there is not a consensus log.
         if !found_correct_log {
-            panic!(
-                "Block: {:?} should be an epoch end block, but did not find corresponding consensus log!",
-                epoch_end_block
-            );
+            return Err(FetchError::MissingConsensusLog(epoch_end_block));
         }
 
-        HeaderRotateData {
+        Ok(HeaderRotateData {
             header_bytes,
             num_authorities: new_authorities.len(),
             pubkeys: new_authorities,
             consensus_log_position: position,
-        }
+        })
     }
 }
-
 /// Converts GrandpaJustification and validator set to CircuitJustification.
 pub fn convert_justification_and_valset_to_circuit(
     justification: GrandpaJustification,
@@ -526,6 +855,24 @@ pub fn convert_justification_and_valset_to_circuit(
     }
 }
 
+/// Check that `header` is block `block_number` with hash `block_hash`.
+fn check_linked_header(header: &Header, block_number: u32, block_hash: B256) -> Result<()> {
+    if header.number != block_number {
+        return Err(FetchError::HeaderLinkage {
+            block_number,
+            reason: format!("header has number {}", header.number),
+        });
+    }
+    let header_hash = B256::from(header.hash().0);
+    if header_hash != block_hash {
+        return Err(FetchError::HeaderLinkage {
+            block_number,
+            reason: format!("header hashes to {}, expected {}", header_hash, block_hash),
+        });
+    }
+    Ok(())
+}
+
 /// NOTE: ONLY USED IN TESTING. IN PROD, FETCH FROM CONTRACT.
 fn get_merkle_tree_size(num_headers: u32) -> usize {
     let mut size = 1;
@@ -537,12 +884,14 @@ fn get_merkle_tree_size(num_headers: u32) -> usize {
 
 #[cfg(test)]
 mod tests {
+    use crate::source::{Fixture, FixtureDataSource, RecordingDataSource};
     use crate::types::{Commit, Precommit, SignerMessage};
     use avail_subxt::config::Header;
     use avail_subxt::primitives::Header as DaHeader;
-    use ed25519::Public;
     use serde::{Deserialize, Serialize};
-    use sp1_vector_primitives::verify_justification;
+    use sp1_vector_primitives::{verify_justification, verify_justification_with_report};
+    use sp_core::ed25519::Public;
+    use sp_core::H256;
     use std::fs::File;
     use test_case::test_case;
 
@@ -551,16 +900,17 @@ mod tests {
     #[tokio::test]
     #[cfg_attr(feature = "ci", ignore)]
     async fn test_get_simple_justification_change_authority_set() {
-        let fetcher = RpcDataFetcher::new().await;
+        let fetcher = RpcDataFetcher::new().await.unwrap();
 
         // This is an block in the middle of an era.
         let block = 645570;
 
-        let authority_set_id = fetcher.get_authority_set_id(block - 1).await;
+        let authority_set_id = fetcher.get_authority_set_id(block - 1).await.unwrap();
         let authority_set_hash = fetcher
             .compute_authority_set_hash_for_block(block - 1)
-            .await;
-        let header = fetcher.get_header(block).await;
+            .await
+            .unwrap();
+        let header = fetcher.get_header(block).await.unwrap();
         let header_hash = header.hash();
 
         println!("authority_set_id {:?}", authority_set_id);
@@ -569,7 +919,8 @@ mod tests {
 
         let _ = fetcher
             .get_justification_data_epoch_end_block(authority_set_id)
-            .await;
+            .await
+            .unwrap();
     }
 
     #[tokio::test]
@@ -578,13 +929,16 @@ mod tests {
         dotenv::dotenv().ok();
         env_logger::init();
 
-        let fetcher = RpcDataFetcher::new().await;
+        let fetcher = RpcDataFetcher::new().await.unwrap();
 
         // A binary search given a target_authority_set_id, returns the last block justified by
         // target_authority_set_id. This block also specifies the new authority set,
         // target_authority_set_id + 1.
         let target_authority_set_id = 2;
-        let epoch_end_block_number = fetcher.last_justified_block(target_authority_set_id).await;
+        let epoch_end_block_number = fetcher
+            .last_justified_block(target_authority_set_id)
+            .await
+            .unwrap();
 
         // Verify that this is an epoch end block.
         assert_ne!(epoch_end_block_number, 0);
@@ -592,14 +946,21 @@ mod tests {
 
         let previous_authority_set_id = fetcher
             .get_authority_set_id(epoch_end_block_number - 1)
-            .await;
-        let new_authority_set_id = fetcher.get_authority_set_id(epoch_end_block_number).await;
+            .await
+            .unwrap();
+        let new_authority_set_id = fetcher
+            .get_authority_set_id(epoch_end_block_number)
+            .await
+            .unwrap();
 
         // Verify this is an epoch end block.
         assert_eq!(previous_authority_set_id + 1, new_authority_set_id);
         assert_eq!(previous_authority_set_id, target_authority_set_id);
 
-        let rotate_data = fetcher.get_header_rotate(new_authority_set_id).await;
+        let rotate_data = fetcher
+            .get_header_rotate(new_authority_set_id)
+            .await
+            .unwrap();
         let new_authority_set_hash = compute_authority_set_commitment(&rotate_data.pubkeys);
         println!("new authority set hash {:?}", new_authority_set_hash);
     }
@@ -687,4 +1048,210 @@ mod tests {
 
         verify_justification(&circuit_justification)
     }
+
+    #[test_case("test_assets/ancestry.json" => true; "Complex ancestry")]
+    #[test_case("test_assets/ancestry_missing_link_no_majority.json" => false; "Missing ancestor negative case")]
+    #[test_case("test_assets/ancestry_missing_link_works.json" => true; "Missing ancestor")]
+    fn test_justification_report(path: &str) -> bool {
+        let test_case_file = File::open(path).unwrap();
+        let validator_set_and_justification: ValidatorSetAndJustification =
+            serde_json::from_reader(test_case_file).unwrap();
+
+        let validator_set = validator_set_and_justification
+            .validator_set
+            .validator_set
+            .iter()
+            .map(|e| B256::from(e.0))
+            .collect::<Vec<_>>();
+        let circuit_justification = convert_justification_and_valset_to_circuit(
+            validator_set_and_justification.justification.into(),
+            validator_set,
+            validator_set_and_justification.validator_set.set_id,
+        );
+
+        let report = verify_justification_with_report(&circuit_justification);
+        assert!(report.invalid_signatures.is_empty());
+        assert_eq!(
+            report.signers.len() + report.absentees.len(),
+            report.validator_set_size
+        );
+        if !report.is_valid() {
+            assert!(!report.ancestry_failures.is_empty());
+            assert!(report.threshold_margin() < 0);
+        }
+        report.is_valid()
+    }
+
+    /// A chain of linked headers for blocks 0..=head, built from a header in the test assets.
+    fn test_headers(head: u32) -> Vec<DaHeader> {
+        let test_case_file = File::open("test_assets/ancestry.json").unwrap();
+        let validator_set_and_justification: ValidatorSetAndJustification =
+            serde_json::from_reader(test_case_file).unwrap();
+        let template = validator_set_and_justification
+            .justification
+            .votes_ancestries[0]
+            .clone();
+
+        let mut headers: Vec<DaHeader> = Vec::new();
+        for number in 0..=head {
+            let mut header = template.clone();
+            header.number = number;
+            header.parent_hash = headers.last().map(|h| h.hash()).unwrap_or_default();
+            headers.push(header);
+        }
+        headers
+    }
+
+    /// A fixture where authority set 0 justifies blocks up to 10, set 1 up to 25 and set 2 is
+    /// still active at the head, block 40.
+    fn test_fixture() -> Fixture {
+        let mut fixture = Fixture::default();
+        for header in test_headers(40) {
+            let set_id = match header.number {
+                0..=9 => 0,
+                10..=24 => 1,
+                _ => 2,
+            };
+            fixture.authority_set_ids.insert(header.number, set_id);
+            fixture
+                .authorities
+                .insert(header.number, vec![B256::repeat_byte(set_id as u8 + 1)]);
+            fixture.headers.insert(header.number, header);
+        }
+        fixture
+    }
+
+    #[tokio::test]
+    async fn test_stored_authority_set_is_checked_against_epoch_index() {
+        let fetcher = RpcDataFetcher::with_source(FixtureDataSource::new(test_fixture()), "test");
+        let authority_set = |authority_set_id: u64, authorities: Vec<B256>| AuthoritySet {
+            authority_set_id,
+            authority_set_hash: if authorities.is_empty() {
+                B256::ZERO
+            } else {
+                compute_authority_set_commitment(&authorities)
+            },
+            authorities,
+        };
+        let set_1 = authority_set(1, vec![B256::repeat_byte(2)]);
+        let set_2 = authority_set(2, vec![B256::repeat_byte(3)]);
+        let metadata = |authority_set_id| ValidationMetadata {
+            authority_set_id,
+            signer_count: 1,
+            validator_set_size: 1,
+        };
+
+        // Nothing is confirmed before the epochs are indexed.
+        assert!(!fetcher.is_indexed_authority_set(&set_1, None, 20));
+        fetcher.last_justified_block(0).await.unwrap();
+        fetcher.last_justified_block(1).await.unwrap();
+
+        assert!(fetcher.is_indexed_authority_set(&set_1, None, 20));
+        assert!(fetcher.is_indexed_authority_set(&set_1, None, 25));
+        // Set 2 is still active, so the block is only checked against the stored metadata.
+        assert!(!fetcher.is_indexed_authority_set(&set_2, None, 30));
+        assert!(fetcher.is_indexed_authority_set(&set_2, Some(&metadata(2)), 30));
+        assert!(!fetcher.is_indexed_authority_set(&set_2, Some(&metadata(1)), 30));
+
+        // Self-consistent sets with the wrong id, authorities or block are rejected.
+        assert!(!fetcher.is_indexed_authority_set(&set_1, Some(&metadata(1)), 30));
+        assert!(!fetcher.is_indexed_authority_set(&set_2, Some(&metadata(2)), 20));
+        let forged = authority_set(2, vec![B256::repeat_byte(4)]);
+        assert!(!fetcher.is_indexed_authority_set(&forged, Some(&metadata(2)), 30));
+        let genesis = authority_set(0, vec![B256::repeat_byte(1)]);
+        assert!(!fetcher.is_indexed_authority_set(&genesis, Some(&metadata(0)), 5));
+        assert!(!fetcher.is_indexed_authority_set(
+            &authority_set(2, vec![]),
+            Some(&metadata(2)),
+            30
+        ));
+        assert!(!fetcher.is_indexed_authority_set(&set_1, None, 0));
+    }
+
+    #[tokio::test]
+    async fn test_parent_walk_reports_first_unlinked_block() {
+        let mut fixture = test_fixture();
+        let head_hash = B256::from(fixture.headers[&30].hash().0);
+        let fetcher = RpcDataFetcher::with_source(FixtureDataSource::new(fixture.clone()), "test");
+        let headers = fetcher
+            .get_block_headers_by_parent_walk(20, 30, head_hash)
+            .await
+            .unwrap();
+        assert_eq!(
+            headers.iter().map(|h| h.number).collect::<Vec<_>>(),
+            (20..=30).collect::<Vec<_>>()
+        );
+
+        // A node serving a forked block 25 breaks the link from block 26.
+        fixture.headers.get_mut(&25).unwrap().state_root = H256::repeat_byte(0xff);
+        let fetcher = RpcDataFetcher::with_source(FixtureDataSource::new(fixture), "test");
+        match fetcher
+            .get_block_headers_by_parent_walk(20, 30, head_hash)
+            .await
+        {
+            Err(FetchError::HeaderLinkage { block_number, .. }) => assert_eq!(block_number, 25),
+            other => panic!("expected a linkage error, got {:?}", other.map(|h| h.len())),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_last_justified_block_from_fixture() {
+        let fetcher = RpcDataFetcher::with_source(FixtureDataSource::new(test_fixture()), "test");
+
+        assert_eq!(fetcher.get_head().await.unwrap().number, 40);
+        assert_eq!(fetcher.last_justified_block(0).await.unwrap(), 10);
+        assert_eq!(fetcher.last_justified_block(1).await.unwrap(), 25);
+        assert_eq!(fetcher.last_justified_block(2).await.unwrap(), 0);
+        assert_eq!(
+            fetcher.epochs.get(1),
+            Some(Epoch {
+                authority_set_id: 1,
+                end_block: 25,
+                new_authority_set_hash: compute_authority_set_commitment(&[B256::repeat_byte(3)]),
+            })
+        );
+        assert_eq!(fetcher.epochs.search_bounds(2), (26, None));
+        assert!(matches!(
+            fetcher.get_justification_data_epoch_end_block(2).await,
+            Err(FetchError::AuthoritySetStillActive(2))
+        ));
+        assert!(matches!(
+            fetcher.get_justification_data_for_block(30).await,
+            Err(FetchError::MissingJustification(30))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_recorded_fixture_replays() {
+        let path = env::temp_dir().join(format!("vectorx-fixture-{}.json", std::process::id()));
+        let recorder =
+            RecordingDataSource::open(FixtureDataSource::new(test_fixture()), &path).unwrap();
+        let fetcher = RpcDataFetcher::with_source(recorder, "test");
+        let recorded_headers = fetcher.get_block_headers_range(3, 7).await.unwrap();
+        let recorded_block = fetcher.last_justified_block(0).await.unwrap();
+        fetcher.source.persist().unwrap();
+
+        // Only the data read through the recorder is replayed.
+        let fetcher = RpcDataFetcher::with_source(FixtureDataSource::load(&path).unwrap(), "test");
+        std::fs::remove_file(&path).unwrap();
+        let replayed_headers = fetcher.get_block_headers_range(3, 7).await.unwrap();
+        assert_eq!(
+            recorded_headers
+                .iter()
+                .map(|h| h.hash())
+                .collect::<Vec<_>>(),
+            replayed_headers
+                .iter()
+                .map(|h| h.hash())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            fetcher.last_justified_block(0).await.unwrap(),
+            recorded_block
+        );
+        assert!(matches!(
+            fetcher.get_header(30).await,
+            Err(FetchError::BlockNotFound(30))
+        ));
+    }
 }
@@ -1,3 +1,16 @@
-pub mod aws;
+pub mod api;
+pub mod audit;
+pub mod backfill;
+pub mod contract;
+pub mod coverage;
+pub mod epochs;
+pub mod error;
+pub mod events;
+pub mod indexer;
 pub mod input;
+pub mod proofs;
+pub mod retention;
+pub mod source;
+pub mod store;
+pub mod subscription;
 pub mod types;
@@ -0,0 +1,410 @@
+//! Merkle proofs that the data root or state root of an Avail block is included in a commitment
+//! stored by the SP1Vector contract. Data root proofs have the JSON shape served by the `query`
+//! service.
+//!
+//! A data (state) commitment is the root of the Merkle tree over the data (state) roots of the
+//! blocks in a header range commitment, padded with zero leaves to the commitment tree size. The
+//! leaves are not hashed, and nodes are hashed with SHA-256.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use alloy_primitives::B256;
+use codec::Encode;
+use serde::{Deserialize, Serialize};
+use sp1_vector_primitives::header_range::decode_header;
+use sp1_vector_primitives::merkle::{get_merkle_branch, get_merkle_root_from_branch};
+use sp1_vector_primitives::VerificationError;
+
+use crate::contract::{CommitmentIndex, HeaderRangeCommitment};
+use crate::error::FetchError;
+use crate::input::{Result, RpcDataFetcher};
+use crate::source::{AvailDataSource, RpcDataSource};
+
+/// Number of header ranges whose roots are cached by default.
+pub const DEFAULT_CACHED_RANGES: usize = 32;
+
+/// Roots of a header range, by its start and end block.
+type CachedRoots = ((u32, u32), Arc<RangeRoots>);
+
+/// Proof that `data_root` is the leaf at `index` of the tree with root `data_commitment`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataRootProof {
+    pub block_number: u32,
+    /// Key of the commitment in the contract, see `HeaderRangeCommitment::range_hash`.
+    pub range_hash: B256,
+    pub data_commitment: B256,
+    /// Siblings on the path from the leaf to the root, starting with the sibling of the leaf.
+    pub merkle_branch: Vec<B256>,
+    /// Index of the leaf, `block_number - start_block - 1`.
+    pub index: u32,
+    pub total_leaves: u32,
+    pub data_root: B256,
+    pub block_hash: B256,
+}
+
+/// Proof that `state_root` is the leaf at `index` of the tree with root `state_commitment`. The
+/// state root can then be used to verify Avail storage proofs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StateRootProof {
+    pub block_number: u32,
+    /// Key of the commitment in the contract, see `HeaderRangeCommitment::range_hash`.
+    pub range_hash: B256,
+    pub state_commitment: B256,
+    /// Siblings on the path from the leaf to the root, starting with the sibling of the leaf.
+    pub merkle_branch: Vec<B256>,
+    /// Index of the leaf, `block_number - start_block - 1`.
+    pub index: u32,
+    pub total_leaves: u32,
+    pub state_root: B256,
+    pub block_hash: B256,
+}
+
+/// The index of the leaf of `block_number` and its Merkle branch in the tree over `leaves`, the
+/// data or state roots of the blocks the commitment covers, checked against `root`.
+fn merkle_branch(
+    commitment: &HeaderRangeCommitment,
+    leaves: &[B256],
+    root: B256,
+    what: &'static str,
+    block_number: u32,
+) -> Result<(usize, Vec<B256>)> {
+    if !commitment.contains(block_number) {
+        return Err(FetchError::InvalidRequest(format!(
+            "Block {} is not in the commitment of blocks {} to {}",
+            block_number,
+            commitment.start_block + 1,
+            commitment.end_block
+        )));
+    }
+    let num_leaves = (commitment.end_block - commitment.start_block) as usize;
+    if leaves.len() != num_leaves {
+        return Err(FetchError::InvalidRequest(format!(
+            "Expected {} {}s, found {}",
+            num_leaves,
+            what,
+            leaves.len()
+        )));
+    }
+    let tree_size = commitment.commitment_tree_size as usize;
+    if !tree_size.is_power_of_two() || tree_size < num_leaves {
+        return Err(VerificationError::InvalidMerkleTreeSize {
+            tree_size,
+            num_leaves,
+        }
+        .into());
+    }
+
+    let index = (block_number - commitment.start_block - 1) as usize;
+    let branch = get_merkle_branch(leaves, tree_size, index);
+    let found = get_merkle_root_from_branch(leaves[index], &branch, index);
+    if found != root {
+        return Err(FetchError::CommitmentMismatch {
+            what,
+            start_block: commitment.start_block,
+            end_block: commitment.end_block,
+            expected: root,
+            found,
+        });
+    }
+    Ok((index, branch))
+}
+
+/// Build the proof for `block_number` from the data roots of the blocks the commitment covers,
+/// and check it against the stored data commitment.
+pub fn data_root_proof(
+    commitment: &HeaderRangeCommitment,
+    data_roots: &[B256],
+    block_number: u32,
+    block_hash: B256,
+) -> Result<DataRootProof> {
+    let (index, merkle_branch) = merkle_branch(
+        commitment,
+        data_roots,
+        commitment.data_commitment,
+        "data root",
+        block_number,
+    )?;
+    Ok(DataRootProof {
+        block_number,
+        range_hash: commitment.range_hash(),
+        data_commitment: commitment.data_commitment,
+        merkle_branch,
+        index: index as u32,
+        total_leaves: commitment.commitment_tree_size,
+        data_root: data_roots[index],
+        block_hash,
+    })
+}
+
+/// Build the proof for `block_number` from the state roots of the blocks the commitment covers,
+/// and check it against the stored state commitment.
+pub fn state_root_proof(
+    commitment: &HeaderRangeCommitment,
+    state_roots: &[B256],
+    block_number: u32,
+    block_hash: B256,
+) -> Result<StateRootProof> {
+    let (index, merkle_branch) = merkle_branch(
+        commitment,
+        state_roots,
+        commitment.state_commitment,
+        "state root",
+        block_number,
+    )?;
+    Ok(StateRootProof {
+        block_number,
+        range_hash: commitment.range_hash(),
+        state_commitment: commitment.state_commitment,
+        merkle_branch,
+        index: index as u32,
+        total_leaves: commitment.commitment_tree_size,
+        state_root: state_roots[index],
+        block_hash,
+    })
+}
+
+/// The data and state roots of the blocks a commitment covers, in block order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeRoots {
+    pub data_roots: Vec<B256>,
+    pub state_roots: Vec<B256>,
+}
+
+/// An Avail block, by number or by hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockId {
+    Number(u32),
+    Hash(B256),
+}
+
+/// Builds data and state root proofs for one Avail chain. The roots of the most recently proven
+/// header ranges are cached, so proofs for other blocks in the same range do not refetch them.
+pub struct CommitmentProver<S = RpcDataSource> {
+    fetcher: RpcDataFetcher<S>,
+    capacity: usize,
+    /// Least recently used first.
+    roots: Mutex<VecDeque<CachedRoots>>,
+}
+
+impl<S: AvailDataSource> CommitmentProver<S> {
+    pub fn new(fetcher: RpcDataFetcher<S>) -> Self {
+        Self {
+            fetcher,
+            capacity: DEFAULT_CACHED_RANGES,
+            roots: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Cache the roots of up to `capacity` header ranges.
+    pub fn with_cache_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    pub fn fetcher(&self) -> &RpcDataFetcher<S> {
+        &self.fetcher
+    }
+
+    fn cached(&self, range: (u32, u32)) -> Option<Arc<RangeRoots>> {
+        let mut cache = self.roots.lock().unwrap();
+        let position = cache.iter().position(|(key, _)| *key == range)?;
+        let entry = cache.remove(position)?;
+        let roots = entry.1.clone();
+        cache.push_back(entry);
+        Some(roots)
+    }
+
+    fn insert(&self, range: (u32, u32), roots: Arc<RangeRoots>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut cache = self.roots.lock().unwrap();
+        if cache.iter().any(|(key, _)| *key == range) {
+            return;
+        }
+        if cache.len() == self.capacity {
+            cache.pop_front();
+        }
+        cache.push_back((range, roots));
+    }
+
+    /// The data and state roots of the blocks the commitment covers.
+    pub async fn roots(&self, commitment: &HeaderRangeCommitment) -> Result<Arc<RangeRoots>> {
+        let range = (commitment.start_block, commitment.end_block);
+        if let Some(roots) = self.cached(range) {
+            return Ok(roots);
+        }
+        let headers = self
+            .fetcher
+            .get_block_headers_range(commitment.start_block + 1, commitment.end_block)
+            .await?;
+        let mut roots = RangeRoots {
+            data_roots: Vec::with_capacity(headers.len()),
+            state_roots: Vec::with_capacity(headers.len()),
+        };
+        for header in &headers {
+            let decoded =
+                decode_header(&header.encode()).map_err(|e| FetchError::decode("header", e))?;
+            roots.data_roots.push(decoded.data_root);
+            roots.state_roots.push(decoded.state_root);
+        }
+        let roots = Arc::new(roots);
+        self.insert(range, roots.clone());
+        Ok(roots)
+    }
+
+    /// The number and hash of `block`.
+    pub async fn resolve(&self, block: BlockId) -> Result<(u32, B256)> {
+        match block {
+            BlockId::Number(block_number) => Ok((
+                block_number,
+                self.fetcher.get_block_hash(block_number).await?,
+            )),
+            BlockId::Hash(block_hash) => {
+                let header = self
+                    .fetcher
+                    .get_header_by_hash(block_hash)
+                    .await?
+                    .ok_or_else(|| {
+                        FetchError::InvalidRequest(format!("Unknown block hash {}", block_hash))
+                    })?;
+                Ok((header.number, block_hash))
+            }
+        }
+    }
+
+    /// The number and hash of `block`, the commitment of a contract that covers it and the roots
+    /// of that commitment, or `None` if the contract has not committed to the block yet.
+    async fn committed_roots(
+        &self,
+        commitments: &CommitmentIndex,
+        block: BlockId,
+    ) -> Result<Option<(u32, B256, HeaderRangeCommitment, Arc<RangeRoots>)>> {
+        let (block_number, block_hash) = self.resolve(block).await?;
+        let Some(commitment) = commitments.commitment_for_block(block_number).await? else {
+            return Ok(None);
+        };
+        let roots = self.roots(&commitment).await?;
+        Ok(Some((block_number, block_hash, commitment, roots)))
+    }
+
+    /// The proof for `block` against the data commitments of a contract, or `None` if the
+    /// contract has not committed to the block yet.
+    pub async fn prove_data_root(
+        &self,
+        commitments: &CommitmentIndex,
+        block: BlockId,
+    ) -> Result<Option<DataRootProof>> {
+        let Some((block_number, block_hash, commitment, roots)) =
+            self.committed_roots(commitments, block).await?
+        else {
+            return Ok(None);
+        };
+        data_root_proof(&commitment, &roots.data_roots, block_number, block_hash).map(Some)
+    }
+
+    /// The proof for `block` against the state commitments of a contract, or `None` if the
+    /// contract has not committed to the block yet.
+    pub async fn prove_state_root(
+        &self,
+        commitments: &CommitmentIndex,
+        block: BlockId,
+    ) -> Result<Option<StateRootProof>> {
+        let Some((block_number, block_hash, commitment, roots)) =
+            self.committed_roots(commitments, block).await?
+        else {
+            return Ok(None);
+        };
+        state_root_proof(&commitment, &roots.state_roots, block_number, block_hash).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commitment_root(leaves: &[B256]) -> B256 {
+        get_merkle_root_from_branch(leaves[0], &get_merkle_branch(leaves, 8, 0), 0)
+    }
+
+    #[test]
+    fn test_data_root_proof() {
+        let data_roots: Vec<B256> = (1..=5u8).map(B256::repeat_byte).collect();
+        let commitment = HeaderRangeCommitment {
+            start_block: 100,
+            end_block: 105,
+            data_commitment: commitment_root(&data_roots),
+            state_commitment: B256::ZERO,
+            commitment_tree_size: 8,
+        };
+
+        let proof = data_root_proof(&commitment, &data_roots, 103, B256::repeat_byte(9)).unwrap();
+        assert_eq!(proof.index, 2);
+        assert_eq!(proof.data_root, data_roots[2]);
+        assert_eq!(proof.merkle_branch.len(), 3);
+        assert_eq!(proof.range_hash, commitment.range_hash());
+        let json = serde_json::to_value(&proof).unwrap();
+        for key in [
+            "blockNumber",
+            "rangeHash",
+            "dataCommitment",
+            "merkleBranch",
+            "index",
+            "totalLeaves",
+            "dataRoot",
+            "blockHash",
+        ] {
+            assert!(json.get(key).is_some(), "missing {}", key);
+        }
+        assert_eq!(json["totalLeaves"], 8);
+
+        assert!(matches!(
+            data_root_proof(&commitment, &data_roots, 100, B256::ZERO),
+            Err(FetchError::InvalidRequest(_))
+        ));
+        let mut wrong_roots = data_roots.clone();
+        wrong_roots[4] = B256::ZERO;
+        assert!(matches!(
+            data_root_proof(&commitment, &wrong_roots, 101, B256::ZERO),
+            Err(FetchError::CommitmentMismatch {
+                what: "data root",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_state_root_proof() {
+        let state_roots: Vec<B256> = (11..=13u8).map(B256::repeat_byte).collect();
+        let commitment = HeaderRangeCommitment {
+            start_block: 100,
+            end_block: 103,
+            data_commitment: B256::ZERO,
+            state_commitment: commitment_root(&state_roots),
+            commitment_tree_size: 8,
+        };
+
+        let proof = state_root_proof(&commitment, &state_roots, 103, B256::ZERO).unwrap();
+        assert_eq!(proof.index, 2);
+        assert_eq!(proof.state_root, state_roots[2]);
+        assert_eq!(
+            get_merkle_root_from_branch(proof.state_root, &proof.merkle_branch, 2),
+            proof.state_commitment
+        );
+        let json = serde_json::to_value(&proof).unwrap();
+        assert!(json.get("stateCommitment").is_some());
+        assert!(json.get("stateRoot").is_some());
+
+        // The data roots do not match the data commitment.
+        assert!(matches!(
+            data_root_proof(&commitment, &state_roots, 103, B256::ZERO),
+            Err(FetchError::CommitmentMismatch {
+                what: "data root",
+                ..
+            })
+        ));
+    }
+}
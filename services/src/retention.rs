@@ -0,0 +1,242 @@
+//! Retention of stored justifications. The operator only needs justifications near the head of
+//! the SP1Vector contract and the epoch end justifications that rotate proofs are built from, so
+//! older justifications can be thinned out instead of kept for every block forever.
+
+use std::env;
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use crate::coverage::{epoch_ends, EpochEnd, IntervalSet};
+use crate::error::FetchError;
+use crate::input::{Result, RpcDataFetcher};
+use crate::source::AvailDataSource;
+use crate::store::JustificationStore;
+
+/// Number of blocks whose stored justifications are read at a time while pruning, rounded up to a
+/// multiple of `RetentionPolicy::keep_every`.
+const PRUNE_CHUNK_BLOCKS: u64 = 10_000;
+
+/// Which stored justifications to keep. Every justification within `keep_recent_blocks` of the
+/// latest stored one is kept. Of the older ones, epoch end justifications are kept, and otherwise
+/// only the lowest justified block in each aligned run of `keep_every` blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// Age in blocks after which justifications are thinned out. Avail finalizes a block every
+    /// 20 seconds, so a day is 4320 blocks.
+    pub keep_recent_blocks: u32,
+    /// Keep one older justification in every `keep_every` blocks.
+    pub keep_every: u32,
+}
+
+impl RetentionPolicy {
+    /// Read the policy from JUSTIFICATION_KEEP_RECENT_BLOCKS and JUSTIFICATION_KEEP_EVERY. Returns
+    /// `None` if neither is set, so every justification is kept.
+    pub fn from_env() -> Result<Option<Self>> {
+        let var = |name: &str| {
+            env::var(name)
+                .ok()
+                .map(|value| {
+                    value
+                        .parse::<u32>()
+                        .map_err(|e| FetchError::Config(format!("Invalid {}: {}", name, e)))
+                })
+                .transpose()
+        };
+        let policy = match (
+            var("JUSTIFICATION_KEEP_RECENT_BLOCKS")?,
+            var("JUSTIFICATION_KEEP_EVERY")?,
+        ) {
+            (None, None) => return Ok(None),
+            (Some(keep_recent_blocks), Some(keep_every)) => RetentionPolicy {
+                keep_recent_blocks,
+                keep_every,
+            },
+            _ => {
+                return Err(FetchError::Config(
+                    "JUSTIFICATION_KEEP_RECENT_BLOCKS and JUSTIFICATION_KEEP_EVERY must be set together"
+                        .to_string(),
+                ))
+            }
+        };
+        policy.validate()?;
+        Ok(Some(policy))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.keep_every == 0 {
+            return Err(FetchError::Config(
+                "keep_every of a retention policy must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// First block that is kept regardless of its age, given the latest stored block. The latest
+    /// stored block is always kept.
+    pub fn cutoff(&self, latest_block: u32) -> u32 {
+        latest_block.saturating_sub(self.keep_recent_blocks)
+    }
+
+    /// The blocks in `block_numbers` that the policy drops. `block_numbers` must be ascending and
+    /// older than the cutoff, and `epoch_ends` ordered by block number.
+    pub fn expired(&self, block_numbers: &[u32], epoch_ends: &[EpochEnd]) -> Vec<u32> {
+        let mut expired = Vec::new();
+        let mut kept_run = None;
+        for &block_number in block_numbers {
+            let run = block_number / self.keep_every;
+            if kept_run != Some(run) {
+                kept_run = Some(run);
+                continue;
+            }
+            if epoch_ends
+                .binary_search_by_key(&block_number, |epoch_end| epoch_end.block_number)
+                .is_ok()
+            {
+                continue;
+            }
+            expired.push(block_number);
+        }
+        expired
+    }
+}
+
+/// What a pruning run deleted, or would delete on a dry run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PruneReport {
+    pub avail_chain_id: String,
+    /// Blocks that were considered, i.e. those older than the cutoff.
+    pub blocks: Range<u32>,
+    pub dry_run: bool,
+    /// Number of stored justifications in `blocks` that are kept.
+    pub kept: u64,
+    /// Blocks whose justification was deleted, or would be on a dry run.
+    pub deleted: IntervalSet,
+}
+
+/// Apply `policy` to the justifications of `avail_chain_id` from `start_block` up to the cutoff,
+/// reading the epoch end blocks from `fetcher`. `start_block` should be a multiple of
+/// `policy.keep_every`, e.g. the end of the previous run rounded down, or an older justification
+/// may be kept in the run of blocks it splits. On a dry run nothing is deleted.
+pub async fn prune<S: AvailDataSource>(
+    fetcher: &RpcDataFetcher<S>,
+    store: &dyn JustificationStore,
+    avail_chain_id: &str,
+    policy: &RetentionPolicy,
+    start_block: u32,
+    dry_run: bool,
+) -> Result<PruneReport> {
+    let cutoff = match store.latest(avail_chain_id).await? {
+        Some(latest) => policy.cutoff(latest.block_number()).max(start_block),
+        None => start_block,
+    };
+    let epoch_ends = if cutoff > start_block {
+        epoch_ends(fetcher, start_block, cutoff - 1).await?
+    } else {
+        Vec::new()
+    };
+    prune_blocks(
+        store,
+        avail_chain_id,
+        policy,
+        start_block..cutoff,
+        &epoch_ends,
+        dry_run,
+    )
+    .await
+}
+
+/// Apply `policy` to the justifications of `avail_chain_id` in `blocks`, which must be older than
+/// the cutoff, keeping the justifications of `epoch_ends`.
+pub async fn prune_blocks(
+    store: &dyn JustificationStore,
+    avail_chain_id: &str,
+    policy: &RetentionPolicy,
+    blocks: Range<u32>,
+    epoch_ends: &[EpochEnd],
+    dry_run: bool,
+) -> Result<PruneReport> {
+    policy.validate()?;
+    let mut report = PruneReport {
+        avail_chain_id: avail_chain_id.to_string(),
+        blocks: blocks.clone(),
+        dry_run,
+        kept: 0,
+        deleted: IntervalSet::default(),
+    };
+    // Chunks end on multiples of `keep_every`, so no run of blocks is split between chunks.
+    let chunk_blocks = PRUNE_CHUNK_BLOCKS.next_multiple_of(policy.keep_every as u64);
+    let mut start = blocks.start as u64;
+    while start < blocks.end as u64 {
+        let end = ((start / chunk_blocks + 1) * chunk_blocks).min(blocks.end as u64);
+        let block_numbers = store
+            .block_numbers(avail_chain_id, start as u32, (end - 1) as u32)
+            .await?;
+        let expired = policy.expired(&block_numbers, epoch_ends);
+        if !dry_run && !expired.is_empty() {
+            store.delete(avail_chain_id, &expired).await?;
+        }
+        report.kept += (block_numbers.len() - expired.len()) as u64;
+        report.deleted.extend(expired);
+        start = end;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{test_record, SqliteStore};
+
+    const POLICY: RetentionPolicy = RetentionPolicy {
+        keep_recent_blocks: 20,
+        keep_every: 10,
+    };
+
+    #[test]
+    fn test_expired_keeps_one_per_run_and_epoch_ends() {
+        let epoch_ends = [EpochEnd {
+            authority_set_id: 3,
+            block_number: 17,
+        }];
+        assert_eq!(
+            POLICY.expired(&[3, 4, 9, 10, 12, 17, 19, 31], &epoch_ends),
+            vec![4, 9, 12, 19]
+        );
+        assert_eq!(POLICY.cutoff(100), 80);
+        assert_eq!(POLICY.cutoff(5), 0);
+    }
+
+    #[tokio::test]
+    async fn test_prune_blocks() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        for block_number in 1..=100 {
+            store
+                .put("turing", &test_record(block_number))
+                .await
+                .unwrap();
+        }
+        let epoch_ends = [EpochEnd {
+            authority_set_id: 1,
+            block_number: 45,
+        }];
+
+        let dry_run = prune_blocks(&store, "turing", &POLICY, 0..80, &epoch_ends, true)
+            .await
+            .unwrap();
+        assert_eq!(dry_run.kept, 9);
+        assert_eq!(dry_run.deleted.len(), 70);
+        assert_eq!(
+            store.block_numbers("turing", 0, 100).await.unwrap().len(),
+            100
+        );
+
+        let report = prune_blocks(&store, "turing", &POLICY, 0..80, &epoch_ends, false)
+            .await
+            .unwrap();
+        assert_eq!(report.deleted, dry_run.deleted);
+        let mut kept = vec![1, 10, 20, 30, 40, 45, 50, 60, 70];
+        kept.extend(80..=100);
+        assert_eq!(store.block_numbers("turing", 0, 100).await.unwrap(), kept);
+    }
+}
@@ -0,0 +1,175 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde_json::{json, Value};
+
+use crate::error::FetchError;
+use crate::input::Result;
+
+/// An additive-increase, multiplicative-decrease limit on the number of concurrent requests. The
+/// limit grows by one after every healthy round of requests and halves when the node reports
+/// that it is overloaded.
+#[derive(Debug)]
+pub struct AdaptiveConcurrency {
+    limit: AtomicUsize,
+    min: usize,
+    max: usize,
+}
+
+impl AdaptiveConcurrency {
+    pub fn new(initial: usize, min: usize, max: usize) -> Self {
+        Self {
+            limit: AtomicUsize::new(initial.clamp(min, max)),
+            min,
+            max,
+        }
+    }
+
+    /// Current number of requests that may be in flight.
+    pub fn limit(&self) -> usize {
+        self.limit.load(Ordering::Relaxed)
+    }
+
+    /// Record a round of requests without overload errors.
+    pub fn on_success(&self) {
+        let _ = self
+            .limit
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |limit| {
+                Some((limit + 1).min(self.max))
+            });
+    }
+
+    /// Record a slot or rate-limit error.
+    pub fn on_overload(&self) {
+        let _ = self
+            .limit
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |limit| {
+                Some((limit / 2).max(self.min))
+            });
+    }
+}
+
+/// The HTTP JSON-RPC URL served alongside a websocket RPC URL. Substrate nodes serve both on the
+/// same port.
+pub(crate) fn http_url(ws_url: &str) -> Option<String> {
+    if let Some(rest) = ws_url.strip_prefix("wss://") {
+        Some(format!("https://{}", rest))
+    } else if let Some(rest) = ws_url.strip_prefix("ws://") {
+        Some(format!("http://{}", rest))
+    } else if ws_url.starts_with("http://") || ws_url.starts_with("https://") {
+        Some(ws_url.to_string())
+    } else {
+        None
+    }
+}
+
+/// Call `method` once for every entry of `params` in a single JSON-RPC batch request, and return
+/// the results in the order of `params`.
+pub(crate) async fn batch_request(
+    http_client: &reqwest::Client,
+    url: &str,
+    method: &str,
+    params: &[Value],
+) -> Result<Vec<Value>> {
+    let body: Vec<Value> = params
+        .iter()
+        .enumerate()
+        .map(|(id, params)| json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+        .collect();
+
+    let response = http_client
+        .post(url)
+        .json(&body)
+        .send()
+        .await
+        .map_err(FetchError::rpc)?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(FetchError::Rpc(format!("HTTP {}", status)));
+    }
+    let response: Value = response
+        .json()
+        .await
+        .map_err(|e| FetchError::decode("JSON-RPC batch response", e))?;
+    parse_batch_response(response, params.len())
+}
+
+/// Order the results of a batch response by request id. Fails if any request returned an error or
+/// has no response.
+fn parse_batch_response(response: Value, num_requests: usize) -> Result<Vec<Value>> {
+    let Value::Array(responses) = response else {
+        let reason = response
+            .pointer("/error/message")
+            .and_then(Value::as_str)
+            .unwrap_or("response is not an array");
+        return Err(FetchError::Rpc(format!("batch request failed: {}", reason)));
+    };
+
+    let mut results = vec![None; num_requests];
+    for mut response in responses {
+        if let Some(error) = response.get("error") {
+            return Err(FetchError::Rpc(error.to_string()));
+        }
+        let id = response
+            .get("id")
+            .and_then(Value::as_u64)
+            .map(|id| id as usize)
+            .filter(|id| *id < num_requests)
+            .ok_or_else(|| FetchError::decode("JSON-RPC batch response", "invalid id"))?;
+        results[id] = Some(
+            response
+                .get_mut("result")
+                .map(Value::take)
+                .unwrap_or_default(),
+        );
+    }
+    results
+        .into_iter()
+        .map(|result| {
+            result.ok_or_else(|| FetchError::decode("JSON-RPC batch response", "missing response"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adaptive_concurrency() {
+        let concurrency = AdaptiveConcurrency::new(16, 1, 18);
+        concurrency.on_success();
+        concurrency.on_success();
+        concurrency.on_success();
+        assert_eq!(concurrency.limit(), 18);
+        concurrency.on_overload();
+        assert_eq!(concurrency.limit(), 9);
+        for _ in 0..10 {
+            concurrency.on_overload();
+        }
+        assert_eq!(concurrency.limit(), 1);
+    }
+
+    #[test]
+    fn test_parse_batch_response() {
+        let response = json!([
+            { "jsonrpc": "2.0", "id": 1, "result": "0x02" },
+            { "jsonrpc": "2.0", "id": 0, "result": "0x01" },
+        ]);
+        assert_eq!(
+            parse_batch_response(response, 2).unwrap(),
+            vec![json!("0x01"), json!("0x02")]
+        );
+
+        let missing = json!([{ "jsonrpc": "2.0", "id": 0, "result": "0x01" }]);
+        assert!(parse_batch_response(missing, 2).is_err());
+
+        let rejected = json!({ "jsonrpc": "2.0", "error": { "code": -32600, "message": "batch not supported" } });
+        assert!(parse_batch_response(rejected, 1).is_err());
+
+        assert_eq!(
+            http_url("wss://turing-rpc.avail.so/ws").as_deref(),
+            Some("https://turing-rpc.avail.so/ws")
+        );
+        assert_eq!(http_url("localhost:9944"), None);
+    }
+}
@@ -0,0 +1,229 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use alloy_primitives::B256;
+use avail_subxt::primitives::Header;
+use codec::{Decode, Encode};
+use rusqlite::{params, Connection, OptionalExtension};
+use subxt::config::Header as SubxtHeader;
+
+use crate::error::FetchError;
+use crate::input::Result;
+
+/// A persistent SQLite cache of finalized chain data, keyed by chain id and block number. Stores
+/// encoded headers, block hashes, authority set ids and authority lists.
+///
+/// Finalized data never changes, so entries are never invalidated. Callers must only insert data
+/// for finalized blocks.
+pub struct HeaderCache {
+    conn: Mutex<Connection>,
+}
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS block_hashes (
+    chain_id TEXT NOT NULL,
+    block_number INTEGER NOT NULL,
+    block_hash BLOB NOT NULL,
+    PRIMARY KEY (chain_id, block_number)
+);
+CREATE TABLE IF NOT EXISTS headers (
+    chain_id TEXT NOT NULL,
+    block_number INTEGER NOT NULL,
+    encoded_header BLOB NOT NULL,
+    PRIMARY KEY (chain_id, block_number)
+);
+CREATE TABLE IF NOT EXISTS authority_set_ids (
+    chain_id TEXT NOT NULL,
+    block_number INTEGER NOT NULL,
+    authority_set_id INTEGER NOT NULL,
+    PRIMARY KEY (chain_id, block_number)
+);
+CREATE TABLE IF NOT EXISTS authorities (
+    chain_id TEXT NOT NULL,
+    block_number INTEGER NOT NULL,
+    pubkeys BLOB NOT NULL,
+    PRIMARY KEY (chain_id, block_number)
+);
+";
+
+fn storage_error(error: rusqlite::Error) -> FetchError {
+    FetchError::Storage(error.to_string())
+}
+
+impl HeaderCache {
+    /// Open the cache at `path`, creating the database if it does not exist.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::init(Connection::open(path).map_err(storage_error)?)
+    }
+
+    /// An in-memory cache that is dropped with the process.
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory().map_err(storage_error)?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA).map_err(storage_error)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Run `f` against the underlying connection, e.g. to maintain additional tables in the same
+    /// database.
+    pub(crate) fn with_connection<T>(
+        &self,
+        f: impl FnOnce(&Connection) -> rusqlite::Result<T>,
+    ) -> Result<T> {
+        f(&self.conn.lock().unwrap()).map_err(storage_error)
+    }
+
+    pub fn block_hash(&self, chain_id: &str, block_number: u32) -> Result<Option<B256>> {
+        let block_hash: Option<Vec<u8>> = self.with_connection(|conn| {
+            conn.query_row(
+                "SELECT block_hash FROM block_hashes WHERE chain_id = ?1 AND block_number = ?2",
+                params![chain_id, block_number],
+                |row| row.get(0),
+            )
+            .optional()
+        })?;
+        block_hash
+            .map(|block_hash| {
+                B256::try_from(block_hash.as_slice())
+                    .map_err(|e| FetchError::decode("cached block hash", e))
+            })
+            .transpose()
+    }
+
+    pub fn put_block_hash(
+        &self,
+        chain_id: &str,
+        block_number: u32,
+        block_hash: B256,
+    ) -> Result<()> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO block_hashes (chain_id, block_number, block_hash) VALUES (?1, ?2, ?3)",
+                params![chain_id, block_number, block_hash.as_slice()],
+            )
+        })?;
+        Ok(())
+    }
+
+    pub fn header(&self, chain_id: &str, block_number: u32) -> Result<Option<Header>> {
+        let encoded_header: Option<Vec<u8>> = self.with_connection(|conn| {
+            conn.query_row(
+                "SELECT encoded_header FROM headers WHERE chain_id = ?1 AND block_number = ?2",
+                params![chain_id, block_number],
+                |row| row.get(0),
+            )
+            .optional()
+        })?;
+        encoded_header
+            .map(|encoded_header| {
+                Header::decode(&mut encoded_header.as_slice())
+                    .map_err(|e| FetchError::decode("cached header", e))
+            })
+            .transpose()
+    }
+
+    /// Insert a header along with its block hash.
+    pub fn put_header(&self, chain_id: &str, header: &Header) -> Result<()> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO headers (chain_id, block_number, encoded_header) VALUES (?1, ?2, ?3)",
+                params![chain_id, header.number, header.encode()],
+            )
+        })?;
+        self.put_block_hash(chain_id, header.number, B256::from(header.hash().0))
+    }
+
+    pub fn authority_set_id(&self, chain_id: &str, block_number: u32) -> Result<Option<u64>> {
+        self.with_connection(|conn| {
+            conn.query_row(
+                "SELECT authority_set_id FROM authority_set_ids WHERE chain_id = ?1 AND block_number = ?2",
+                params![chain_id, block_number],
+                |row| row.get(0),
+            )
+            .optional()
+        })
+    }
+
+    pub fn put_authority_set_id(
+        &self,
+        chain_id: &str,
+        block_number: u32,
+        authority_set_id: u64,
+    ) -> Result<()> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO authority_set_ids (chain_id, block_number, authority_set_id) VALUES (?1, ?2, ?3)",
+                params![chain_id, block_number, authority_set_id],
+            )
+        })?;
+        Ok(())
+    }
+
+    pub fn authorities(&self, chain_id: &str, block_number: u32) -> Result<Option<Vec<B256>>> {
+        let pubkeys: Option<Vec<u8>> = self.with_connection(|conn| {
+            conn.query_row(
+                "SELECT pubkeys FROM authorities WHERE chain_id = ?1 AND block_number = ?2",
+                params![chain_id, block_number],
+                |row| row.get(0),
+            )
+            .optional()
+        })?;
+        match pubkeys {
+            Some(pubkeys) if pubkeys.len() % 32 != 0 => Err(FetchError::decode(
+                "cached authorities",
+                format!("{} bytes is not a multiple of 32", pubkeys.len()),
+            )),
+            Some(pubkeys) => Ok(Some(pubkeys.chunks(32).map(B256::from_slice).collect())),
+            None => Ok(None),
+        }
+    }
+
+    pub fn put_authorities(
+        &self,
+        chain_id: &str,
+        block_number: u32,
+        authorities: &[B256],
+    ) -> Result<()> {
+        let pubkeys: Vec<u8> = authorities.iter().flat_map(|a| a.0).collect();
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO authorities (chain_id, block_number, pubkeys) VALUES (?1, ?2, ?3)",
+                params![chain_id, block_number, pubkeys],
+            )
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_is_keyed_by_chain_and_block() {
+        let cache = HeaderCache::open_in_memory().unwrap();
+        let authorities = vec![B256::repeat_byte(1), B256::repeat_byte(2)];
+
+        cache.put_authority_set_id("turing", 10, 3).unwrap();
+        cache.put_authorities("turing", 10, &authorities).unwrap();
+        cache
+            .put_block_hash("turing", 10, B256::repeat_byte(7))
+            .unwrap();
+
+        assert_eq!(cache.authority_set_id("turing", 10).unwrap(), Some(3));
+        assert_eq!(cache.authorities("turing", 10).unwrap(), Some(authorities));
+        assert_eq!(
+            cache.block_hash("turing", 10).unwrap(),
+            Some(B256::repeat_byte(7))
+        );
+
+        assert_eq!(cache.authority_set_id("turing", 11).unwrap(), None);
+        assert_eq!(cache.authority_set_id("mainnet", 10).unwrap(), None);
+        assert_eq!(cache.authorities("mainnet", 10).unwrap(), None);
+        assert_eq!(cache.header("turing", 10).unwrap(), None);
+    }
+}
@@ -0,0 +1,340 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use alloy_primitives::B256;
+use async_trait::async_trait;
+use avail_subxt::primitives::Header;
+use serde::{Deserialize, Serialize};
+use subxt::config::Header as SubxtHeader;
+
+use super::AvailDataSource;
+use crate::error::FetchError;
+use crate::input::Result;
+use crate::store::Prefer;
+use crate::types::{FinalityProof, GrandpaJustification};
+
+/// A snapshot of Avail chain data keyed by block number, stored as JSON.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Fixture {
+    /// Number of the latest finalized block. Defaults to the highest block with a header.
+    pub finalized_head: Option<u32>,
+    pub block_hashes: BTreeMap<u32, B256>,
+    pub headers: BTreeMap<u32, Header>,
+    pub authority_set_ids: BTreeMap<u32, u64>,
+    pub authorities: BTreeMap<u32, Vec<B256>>,
+    pub justifications: BTreeMap<u32, GrandpaJustification>,
+    pub finality_proofs: BTreeMap<u32, FinalityProof>,
+}
+
+impl Fixture {
+    /// Read a fixture from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(path.as_ref()).map_err(|e| storage_error(path.as_ref(), e))?;
+        serde_json::from_reader(BufReader::new(file)).map_err(|e| storage_error(path.as_ref(), e))
+    }
+
+    /// Write the fixture to a JSON file, replacing it if it exists.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let file = File::create(path.as_ref()).map_err(|e| storage_error(path.as_ref(), e))?;
+        serde_json::to_writer(BufWriter::new(file), self)
+            .map_err(|e| storage_error(path.as_ref(), e))
+    }
+
+    fn block_hash(&self, block_number: u32) -> Option<B256> {
+        self.block_hashes.get(&block_number).copied().or_else(|| {
+            self.headers
+                .get(&block_number)
+                .map(|header| B256::from(header.hash().0))
+        })
+    }
+
+    fn header_by_hash(&self, block_hash: B256) -> Option<Header> {
+        self.headers
+            .values()
+            .find(|header| B256::from(header.hash().0) == block_hash)
+            .cloned()
+    }
+
+    fn finalized_head(&self) -> Option<&Header> {
+        match self.finalized_head {
+            Some(block_number) => self.headers.get(&block_number),
+            None => self.headers.values().next_back(),
+        }
+    }
+}
+
+fn storage_error(path: &Path, error: impl std::fmt::Display) -> FetchError {
+    FetchError::Storage(format!("{}: {}", path.display(), error))
+}
+
+/// Replays chain data from a `Fixture`. Data that is not in the fixture is reported as
+/// `FetchError::BlockNotFound`, or `FetchError::MissingJustification` for justifications.
+#[derive(Debug, Clone, Default)]
+pub struct FixtureDataSource {
+    fixture: Fixture,
+}
+
+impl FixtureDataSource {
+    pub fn new(fixture: Fixture) -> Self {
+        Self { fixture }
+    }
+
+    /// Replay the fixture stored at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(Fixture::load(path)?))
+    }
+
+    pub fn fixture(&self) -> &Fixture {
+        &self.fixture
+    }
+}
+
+impl From<Fixture> for FixtureDataSource {
+    fn from(fixture: Fixture) -> Self {
+        Self::new(fixture)
+    }
+}
+
+#[async_trait]
+impl AvailDataSource for FixtureDataSource {
+    async fn block_hash(&self, block_number: u32) -> Result<B256> {
+        self.fixture
+            .block_hash(block_number)
+            .ok_or(FetchError::BlockNotFound(block_number))
+    }
+
+    async fn header(&self, block_number: u32) -> Result<Header> {
+        self.fixture
+            .headers
+            .get(&block_number)
+            .cloned()
+            .ok_or(FetchError::BlockNotFound(block_number))
+    }
+
+    async fn header_by_hash(&self, block_hash: B256) -> Result<Option<Header>> {
+        Ok(self.fixture.header_by_hash(block_hash))
+    }
+
+    async fn finalized_head(&self) -> Result<Header> {
+        self.fixture
+            .finalized_head()
+            .cloned()
+            .ok_or_else(|| FetchError::Storage("Fixture has no finalized head".to_string()))
+    }
+
+    async fn authority_set_id(&self, block_number: u32) -> Result<u64> {
+        self.fixture
+            .authority_set_ids
+            .get(&block_number)
+            .copied()
+            .ok_or(FetchError::BlockNotFound(block_number))
+    }
+
+    async fn authorities(&self, block_number: u32) -> Result<Vec<B256>> {
+        self.fixture
+            .authorities
+            .get(&block_number)
+            .cloned()
+            .ok_or(FetchError::BlockNotFound(block_number))
+    }
+
+    async fn justification(&self, block_number: u32) -> Result<GrandpaJustification> {
+        self.fixture
+            .justifications
+            .get(&block_number)
+            .cloned()
+            .ok_or(FetchError::MissingJustification(block_number))
+    }
+
+    async fn nearest_justified_block(
+        &self,
+        start_block_number: u32,
+        end_block_number: u32,
+        prefer: Prefer,
+    ) -> Result<Option<u32>> {
+        if start_block_number > end_block_number {
+            return Ok(None);
+        }
+        let mut justified = self
+            .fixture
+            .justifications
+            .range(start_block_number..=end_block_number)
+            .map(|(&block_number, _)| block_number);
+        Ok(match prefer {
+            Prefer::Lowest => justified.next(),
+            Prefer::Highest => justified.next_back(),
+        })
+    }
+
+    /// Like `grandpa_proveFinality`, returns the proof of the first justified block at or after
+    /// `block_number`.
+    async fn finality_proof(&self, block_number: u32) -> Result<FinalityProof> {
+        self.fixture
+            .finality_proofs
+            .range(block_number..)
+            .next()
+            .map(|(_, proof)| proof.clone())
+            .ok_or(FetchError::BlockNotFound(block_number))
+    }
+}
+
+/// A cache in front of another source that records every response into a fixture file. Data that
+/// is already in the file is served without querying the inner source, so a recorded session can
+/// be replayed later with `FixtureDataSource`. The finalized head is always read from the inner
+/// source.
+pub struct RecordingDataSource<S> {
+    inner: S,
+    path: PathBuf,
+    fixture: Mutex<Fixture>,
+}
+
+impl<S: AvailDataSource> RecordingDataSource<S> {
+    /// Record into the fixture file at `path`, starting from its contents if it exists.
+    pub fn open(inner: S, path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let fixture = if path.exists() {
+            Fixture::load(&path)?
+        } else {
+            Fixture::default()
+        };
+        Ok(Self {
+            inner,
+            path,
+            fixture: Mutex::new(fixture),
+        })
+    }
+
+    /// A copy of everything recorded so far.
+    pub fn fixture(&self) -> Fixture {
+        self.fixture.lock().unwrap().clone()
+    }
+
+    /// Write everything recorded so far to the fixture file.
+    pub fn persist(&self) -> Result<()> {
+        self.fixture().save(&self.path)
+    }
+
+    fn cached<T>(&self, get: impl FnOnce(&Fixture) -> Option<T>) -> Option<T> {
+        get(&self.fixture.lock().unwrap())
+    }
+
+    fn record(&self, insert: impl FnOnce(&mut Fixture)) {
+        insert(&mut self.fixture.lock().unwrap())
+    }
+}
+
+#[async_trait]
+impl<S: AvailDataSource> AvailDataSource for RecordingDataSource<S> {
+    async fn block_hash(&self, block_number: u32) -> Result<B256> {
+        if let Some(block_hash) = self.cached(|f| f.block_hash(block_number)) {
+            return Ok(block_hash);
+        }
+        let block_hash = self.inner.block_hash(block_number).await?;
+        self.record(|f| {
+            f.block_hashes.insert(block_number, block_hash);
+        });
+        Ok(block_hash)
+    }
+
+    async fn header(&self, block_number: u32) -> Result<Header> {
+        if let Some(header) = self.cached(|f| f.headers.get(&block_number).cloned()) {
+            return Ok(header);
+        }
+        let header = self.inner.header(block_number).await?;
+        self.record(|f| {
+            f.headers.insert(block_number, header.clone());
+        });
+        Ok(header)
+    }
+
+    async fn header_by_hash(&self, block_hash: B256) -> Result<Option<Header>> {
+        if let Some(header) = self.cached(|f| f.header_by_hash(block_hash)) {
+            return Ok(Some(header));
+        }
+        let header = self.inner.header_by_hash(block_hash).await?;
+        if let Some(header) = &header {
+            self.record(|f| {
+                f.headers.insert(header.number, header.clone());
+            });
+        }
+        Ok(header)
+    }
+
+    async fn finalized_head(&self) -> Result<Header> {
+        let header = self.inner.finalized_head().await?;
+        self.record(|f| {
+            f.finalized_head = Some(header.number);
+            f.headers.insert(header.number, header.clone());
+        });
+        Ok(header)
+    }
+
+    async fn authority_set_id(&self, block_number: u32) -> Result<u64> {
+        if let Some(set_id) = self.cached(|f| f.authority_set_ids.get(&block_number).copied()) {
+            return Ok(set_id);
+        }
+        let set_id = self.inner.authority_set_id(block_number).await?;
+        self.record(|f| {
+            f.authority_set_ids.insert(block_number, set_id);
+        });
+        Ok(set_id)
+    }
+
+    async fn authorities(&self, block_number: u32) -> Result<Vec<B256>> {
+        if let Some(authorities) = self.cached(|f| f.authorities.get(&block_number).cloned()) {
+            return Ok(authorities);
+        }
+        let authorities = self.inner.authorities(block_number).await?;
+        self.record(|f| {
+            f.authorities.insert(block_number, authorities.clone());
+        });
+        Ok(authorities)
+    }
+
+    async fn justification(&self, block_number: u32) -> Result<GrandpaJustification> {
+        if let Some(justification) = self.cached(|f| f.justifications.get(&block_number).cloned()) {
+            return Ok(justification);
+        }
+        let justification = self.inner.justification(block_number).await?;
+        self.record(|f| {
+            f.justifications.insert(block_number, justification.clone());
+        });
+        Ok(justification)
+    }
+
+    /// Asks the inner source and records the justification it finds, so the fixture answers the
+    /// same query on replay.
+    async fn nearest_justified_block(
+        &self,
+        start_block_number: u32,
+        end_block_number: u32,
+        prefer: Prefer,
+    ) -> Result<Option<u32>> {
+        let block_number = self
+            .inner
+            .nearest_justified_block(start_block_number, end_block_number, prefer)
+            .await?;
+        if let Some(block_number) = block_number {
+            self.justification(block_number).await?;
+        }
+        Ok(block_number)
+    }
+
+    async fn finality_proof(&self, block_number: u32) -> Result<FinalityProof> {
+        if let Some(proof) = self.cached(|f| f.finality_proofs.get(&block_number).cloned()) {
+            return Ok(proof);
+        }
+        let proof = self.inner.finality_proof(block_number).await?;
+        self.record(|f| {
+            f.finality_proofs.insert(block_number, proof.clone());
+        });
+        Ok(proof)
+    }
+
+    async fn cross_check(&self, block_number: u32) -> Result<()> {
+        self.inner.cross_check(block_number).await
+    }
+}
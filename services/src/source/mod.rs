@@ -0,0 +1,132 @@
+//! Sources of Avail chain data. Input construction in `RpcDataFetcher` is generic over an
+//! `AvailDataSource`, so inputs can be built from the live RPC, a recorded fixture, or a cache in
+//! front of either.
+
+use alloy_primitives::B256;
+use async_trait::async_trait;
+use avail_subxt::primitives::Header;
+use futures::future::try_join_all;
+
+use crate::error::FetchError;
+use crate::input::Result;
+use crate::store::{JustificationRecord, Prefer};
+use crate::types::{FinalityProof, GrandpaJustification};
+
+mod batch;
+mod cache;
+mod fixture;
+mod rpc;
+
+pub use batch::AdaptiveConcurrency;
+pub use cache::HeaderCache;
+pub use fixture::{Fixture, FixtureDataSource, RecordingDataSource};
+pub use rpc::{EndpointPolicy, RetryPolicy, RpcDataSource};
+
+/// The chain data needed to build header range and rotate inputs.
+///
+/// Authority data queried at `block_number` is the state after executing that block, i.e. the
+/// authority set that attests to `block_number + 1`.
+#[async_trait]
+pub trait AvailDataSource: Send + Sync {
+    /// Hash of the finalized block `block_number`.
+    async fn block_hash(&self, block_number: u32) -> Result<B256>;
+
+    /// Header of the finalized block `block_number`.
+    async fn header(&self, block_number: u32) -> Result<Header>;
+
+    /// Header of the block with hash `block_hash`, fetched with a single request. Returns `None`
+    /// if the block is unknown.
+    async fn header_by_hash(&self, block_hash: B256) -> Result<Option<Header>>;
+
+    /// Headers of the finalized blocks `start_block_number..=end_block_number`, in order.
+    async fn headers(&self, start_block_number: u32, end_block_number: u32) -> Result<Vec<Header>> {
+        // Fetch the headers in batches of MAX_CONCURRENT_REQUESTS. The WS connection will error if
+        // there are too many concurrent requests with Rpc(ClientError(MaxSlotsExceeded)).
+        const MAX_CONCURRENT_REQUESTS: u32 = 200;
+        let mut headers = Vec::new();
+        let mut curr_block = start_block_number;
+        while curr_block <= end_block_number {
+            let end_block =
+                std::cmp::min(curr_block + MAX_CONCURRENT_REQUESTS - 1, end_block_number);
+            let header_futures: Vec<_> = (curr_block..end_block + 1)
+                .map(|block_number| self.header(block_number))
+                .collect();
+
+            // Await all futures concurrently
+            headers.extend(try_join_all(header_futures).await?);
+            curr_block += MAX_CONCURRENT_REQUESTS;
+        }
+        Ok(headers)
+    }
+
+    /// Header of the latest finalized block.
+    async fn finalized_head(&self) -> Result<Header>;
+
+    /// GRANDPA authority set id at `block_number`.
+    async fn authority_set_id(&self, block_number: u32) -> Result<u64>;
+
+    /// GRANDPA authorities at `block_number`.
+    async fn authorities(&self, block_number: u32) -> Result<Vec<B256>>;
+
+    /// Stored justification for `block_number`. Returns `FetchError::MissingJustification` if
+    /// there is none.
+    async fn justification(&self, block_number: u32) -> Result<GrandpaJustification>;
+
+    /// Stored justification for `block_number` with whatever the store kept alongside it. Sources
+    /// that only have the justification return a record without metadata or authority set.
+    async fn justification_record(&self, block_number: u32) -> Result<JustificationRecord> {
+        Ok(JustificationRecord {
+            justification: self.justification(block_number).await?,
+            metadata: None,
+            authority_set: None,
+        })
+    }
+
+    /// The lowest or highest block in `start_block_number..=end_block_number` with a stored
+    /// justification, as preferred. By default every block is tried in turn from the preferred
+    /// end, so sources backed by an index should override it.
+    async fn nearest_justified_block(
+        &self,
+        start_block_number: u32,
+        end_block_number: u32,
+        prefer: Prefer,
+    ) -> Result<Option<u32>> {
+        probe_justifications(self, start_block_number, end_block_number, prefer).await
+    }
+
+    /// GRANDPA finality proof for `block_number`. The proof justifies the first block at or after
+    /// `block_number` that the node has a justification for, e.g. the epoch end block. Returns
+    /// `FetchError::BlockNotFound` if the node has no such justification.
+    async fn finality_proof(&self, block_number: u32) -> Result<FinalityProof>;
+
+    /// Confirm the hash of `block_number` and the authority set that justifies it with
+    /// independent sources. Returns `FetchError::EndpointDisagreement` if they disagree. Sources
+    /// without independent replicas accept everything.
+    async fn cross_check(&self, _block_number: u32) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Find the nearest justified block by asking for the justification of each block in turn.
+async fn probe_justifications<S: AvailDataSource + ?Sized>(
+    source: &S,
+    start_block_number: u32,
+    end_block_number: u32,
+    prefer: Prefer,
+) -> Result<Option<u32>> {
+    let mut block_numbers = start_block_number..=end_block_number;
+    loop {
+        let block_number = match prefer {
+            Prefer::Lowest => block_numbers.next(),
+            Prefer::Highest => block_numbers.next_back(),
+        };
+        let Some(block_number) = block_number else {
+            return Ok(None);
+        };
+        match source.justification(block_number).await {
+            Ok(_) => return Ok(Some(block_number)),
+            Err(FetchError::MissingJustification(_)) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
@@ -0,0 +1,1046 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, AtomicU8, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use alloy_primitives::B256;
+use async_trait::async_trait;
+use avail_subxt::avail_client::AvailClient;
+use avail_subxt::primitives::Header;
+use avail_subxt::{api, RpcParams};
+use codec::Decode;
+use futures::future::{join_all, try_join_all};
+use futures::{StreamExt, TryStreamExt};
+use log::{info, warn};
+use serde_json::{json, Value};
+use sp_core::{ed25519, H256};
+use subxt::backend::rpc::{RpcClient, RpcSubscription};
+
+use super::batch::{batch_request, http_url};
+use super::{probe_justifications, AdaptiveConcurrency, AvailDataSource, HeaderCache};
+use crate::error::FetchError;
+use crate::input::Result;
+use crate::store::{JustificationRecord, JustificationStore, Prefer};
+use crate::subscription::JustificationSubscription;
+use crate::types::{EncodedFinalityProof, FinalityProof, GrandpaJustification};
+
+/// How failed requests to the Avail RPC, the query service and the justification store are
+/// retried. Only errors for which `FetchError::is_retryable` is true are retried.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Number of retries after the first attempt.
+    pub max_retries: u32,
+    /// Backoff before the first retry. Doubles after every retry.
+    pub initial_backoff: Duration,
+    /// Upper bound on the backoff between retries.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries.
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// Run `request` with a timeout on every attempt, retrying retryable errors with exponential
+    /// backoff.
+    pub async fn run<T, F, Fut>(&self, timeout: Duration, what: &str, request: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut backoff = self.initial_backoff;
+        let mut attempt = 0;
+        loop {
+            let result = match tokio::time::timeout(timeout, request()).await {
+                Ok(result) => result,
+                Err(_) => Err(FetchError::Timeout(timeout)),
+            };
+            match result {
+                Err(e) if e.is_retryable() && attempt < self.max_retries => {
+                    attempt += 1;
+                    warn!(
+                        "Request for {} failed (attempt {}/{}): {}. Retrying in {:?}.",
+                        what,
+                        attempt,
+                        self.max_retries + 1,
+                        e,
+                        backoff
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = std::cmp::min(backoff * 2, self.max_backoff);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// How requests are spread over multiple Avail RPC endpoints.
+#[derive(Debug, Clone, Copy)]
+pub struct EndpointPolicy {
+    /// Number of endpoints that must agree on block hashes and authority sets in
+    /// `AvailDataSource::cross_check`. 1 disables cross-checking.
+    pub quorum: usize,
+    /// Number of blocks an endpoint's finalized head may lag behind the best endpoint before
+    /// requests fail over away from it.
+    pub max_lag: u32,
+}
+
+impl Default for EndpointPolicy {
+    fn default() -> Self {
+        Self {
+            quorum: 1,
+            max_lag: 10,
+        }
+    }
+}
+
+struct Endpoint {
+    url: String,
+    /// HTTP URL for JSON-RPC batch requests.
+    http_url: Option<String>,
+    client: AvailClient,
+    /// Whether the endpoint accepts JSON-RPC batch requests.
+    batch_support: AtomicU8,
+}
+
+/// Number of blocks requested in one JSON-RPC batch.
+const HEADER_BATCH_SIZE: usize = 100;
+
+/// Whether an endpoint accepts JSON-RPC batch requests.
+const BATCH_SUPPORT_UNKNOWN: u8 = 0;
+const BATCH_SUPPORTED: u8 = 1;
+const BATCH_UNSUPPORTED: u8 = 2;
+
+/// Reads chain data from one or more Avail RPC endpoints and justifications from the VectorX query
+/// service. The query service URL is only necessary when querying justifications.
+///
+/// Requests go to a preferred endpoint. Retries of a failed request go to the next endpoint, and
+/// the endpoint that succeeds becomes the preferred one. Reading the finalized head queries every
+/// endpoint and moves away from a preferred endpoint that lags behind by more than
+/// `EndpointPolicy::max_lag` blocks.
+///
+/// Header ranges are fetched with JSON-RPC batch requests if the node supports them, with a
+/// concurrency that adapts to the node's slot and rate limits.
+///
+/// If a `HeaderCache` is configured, headers, block hashes, authority set ids and authorities of
+/// finalized blocks are read from the cache first and stored in it after they are fetched. Only
+/// headers fetched by number are cached.
+pub struct RpcDataSource {
+    endpoints: Vec<Endpoint>,
+    /// Index of the endpoint requests are sent to first.
+    preferred: AtomicUsize,
+    endpoint_policy: EndpointPolicy,
+    http_client: reqwest::Client,
+    avail_chain_id: String,
+    vectorx_query_url: Option<String>,
+    /// Store read for justifications instead of the VectorX query service, if set.
+    justification_store: Option<Arc<dyn JustificationStore>>,
+    request_timeout: Duration,
+    retry_policy: RetryPolicy,
+    cache: Option<HeaderCache>,
+    /// Number of the latest finalized block seen so far. Only data at or below it is cached.
+    finalized_number: AtomicU32,
+    /// Concurrency of header range requests.
+    concurrency: AdaptiveConcurrency,
+}
+
+impl RpcDataSource {
+    /// Connect to the Avail RPC endpoints in `avail_urls`. Each connection is retried with
+    /// `retry_policy`; endpoints that cannot be reached are skipped as long as enough remain to
+    /// meet the quorum.
+    pub async fn connect(
+        avail_urls: &[String],
+        avail_chain_id: String,
+        vectorx_query_url: Option<String>,
+        request_timeout: Duration,
+        retry_policy: RetryPolicy,
+        endpoint_policy: EndpointPolicy,
+        cache: Option<HeaderCache>,
+    ) -> Result<Self> {
+        if avail_urls.is_empty() {
+            return Err(FetchError::Config(
+                "At least one Avail URL must be set".to_string(),
+            ));
+        }
+        if endpoint_policy.quorum == 0 || endpoint_policy.quorum > avail_urls.len() {
+            return Err(FetchError::Config(format!(
+                "RPC quorum must be between 1 and the number of Avail URLs ({}), got {}",
+                avail_urls.len(),
+                endpoint_policy.quorum
+            )));
+        }
+
+        let connections = join_all(avail_urls.iter().map(|url| async move {
+            let client = retry_policy
+                .run(request_timeout, "Avail RPC connection", || async {
+                    AvailClient::new(url.as_str())
+                        .await
+                        .map_err(FetchError::rpc)
+                })
+                .await;
+            (url, client)
+        }))
+        .await;
+
+        let mut endpoints = Vec::new();
+        let mut last_error = None;
+        for (url, client) in connections {
+            match client {
+                Ok(client) => endpoints.push(Endpoint {
+                    url: url.clone(),
+                    http_url: http_url(url),
+                    client,
+                    batch_support: AtomicU8::new(BATCH_SUPPORT_UNKNOWN),
+                }),
+                Err(e) => {
+                    warn!("Failed to connect to Avail RPC {}: {}", url, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        if endpoints.len() < endpoint_policy.quorum {
+            return Err(last_error.unwrap_or_else(|| {
+                FetchError::Config("Not enough Avail RPC endpoints".to_string())
+            }));
+        }
+
+        let http_client = reqwest::Client::builder()
+            .timeout(request_timeout)
+            .build()
+            .map_err(|e| FetchError::Config(e.to_string()))?;
+
+        Ok(Self {
+            endpoints,
+            preferred: AtomicUsize::new(0),
+            endpoint_policy,
+            http_client,
+            avail_chain_id,
+            vectorx_query_url,
+            justification_store: None,
+            request_timeout,
+            retry_policy,
+            cache,
+            finalized_number: AtomicU32::new(0),
+            concurrency: AdaptiveConcurrency::new(16, 1, 200),
+        })
+    }
+
+    /// Read justifications from `justification_store` instead of the VectorX query service.
+    pub fn with_justification_store(
+        mut self,
+        justification_store: Arc<dyn JustificationStore>,
+    ) -> Self {
+        self.justification_store = Some(justification_store);
+        self
+    }
+
+    /// The client of the preferred endpoint, e.g. for subscriptions.
+    pub fn client(&self) -> &AvailClient {
+        &self.endpoints[self.preferred.load(Ordering::Relaxed)].client
+    }
+
+    /// Subscribe to new justifications, keeping the latest `capacity` of them. Every time the
+    /// subscription fails it moves on to the next endpoint, starting from the preferred one.
+    pub fn subscribe_justifications(&self, capacity: usize) -> JustificationSubscription {
+        let preferred = self.preferred.load(Ordering::Relaxed);
+        let clients: Vec<RpcClient> = (0..self.endpoints.len())
+            .map(|i| {
+                self.endpoints[(preferred + i) % self.endpoints.len()]
+                    .client
+                    .rpc()
+                    .clone()
+            })
+            .collect();
+        let attempts = AtomicUsize::new(0);
+        JustificationSubscription::spawn(
+            move || {
+                let client =
+                    clients[attempts.fetch_add(1, Ordering::Relaxed) % clients.len()].clone();
+                async move {
+                    let sub: RpcSubscription<GrandpaJustification> = client
+                        .subscribe(
+                            "grandpa_subscribeJustifications",
+                            RpcParams::new(),
+                            "grandpa_unsubscribeJustifications",
+                        )
+                        .await
+                        .map_err(FetchError::rpc)?;
+                    Ok(sub.map(|justification| justification.map_err(FetchError::rpc)))
+                }
+            },
+            capacity,
+            self.retry_policy,
+        )
+    }
+
+    /// Run a request with the configured timeout and retry policy.
+    pub(crate) async fn request<T, F, Fut>(&self, what: &str, request: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.retry_policy
+            .run(self.request_timeout, what, request)
+            .await
+    }
+
+    /// Run a request against the Avail RPC with the configured timeout and retry policy. Every
+    /// retry is sent to the next endpoint.
+    async fn rpc_request<'a, T, F, Fut>(&'a self, what: &str, request: F) -> Result<T>
+    where
+        F: Fn(&'a AvailClient) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let request = &request;
+        let attempt = &AtomicUsize::new(0);
+        self.retry_policy
+            .run(self.request_timeout, what, || async move {
+                let preferred = self.preferred.load(Ordering::Relaxed);
+                let index =
+                    (preferred + attempt.fetch_add(1, Ordering::Relaxed)) % self.endpoints.len();
+                let result = request(&self.endpoints[index].client).await;
+                if result.is_ok() && index != preferred {
+                    self.prefer(index);
+                }
+                result
+            })
+            .await
+    }
+
+    fn prefer(&self, index: usize) {
+        let previous = self.preferred.swap(index, Ordering::Relaxed);
+        if previous != index {
+            warn!(
+                "Failing over from Avail RPC {} to {}",
+                self.endpoints[previous].url, self.endpoints[index].url
+            );
+        }
+    }
+
+    /// Read `what` from the cache. Cache failures are logged and treated as misses.
+    fn cached<T>(
+        &self,
+        what: &str,
+        get: impl FnOnce(&HeaderCache, &str) -> Result<Option<T>>,
+    ) -> Option<T> {
+        let cache = self.cache.as_ref()?;
+        get(cache, &self.avail_chain_id)
+            .map_err(|e| warn!("Failed to read {} from the cache: {}", what, e))
+            .ok()
+            .flatten()
+    }
+
+    /// Store `what` for `block_number` in the cache if the block is finalized. Cache failures are
+    /// logged and ignored.
+    async fn cache_finalized(
+        &self,
+        what: &str,
+        block_number: u32,
+        put: impl FnOnce(&HeaderCache, &str) -> Result<()>,
+    ) {
+        let Some(cache) = self.cache.as_ref() else {
+            return;
+        };
+        if block_number > self.finalized_number.load(Ordering::Relaxed) {
+            // Refresh the finalized head; blocks above it may still be reorged.
+            match self.finalized_head().await {
+                Ok(head) if block_number <= head.number => {}
+                _ => return,
+            }
+        }
+        if let Err(e) = put(cache, &self.avail_chain_id) {
+            warn!("Failed to write {} to the cache: {}", what, e);
+        }
+    }
+
+    /// Fetch the headers of `block_numbers` in a single attempt, with a JSON-RPC batch request if
+    /// possible. Like `rpc_request`, the `attempt`th retry is sent to the `attempt`th endpoint after
+    /// the preferred one, and the endpoint that succeeds becomes the preferred one. Returns `None`
+    /// without fetching anything if the endpoint cannot take batch requests and `block_numbers`
+    /// has more than one block, so the caller can split the chunk.
+    async fn fetch_header_chunk(
+        &self,
+        block_numbers: &[u32],
+        attempt: u32,
+    ) -> Result<Option<Vec<Header>>> {
+        let preferred = self.preferred.load(Ordering::Relaxed);
+        let index = (preferred + attempt as usize) % self.endpoints.len();
+        let endpoint = &self.endpoints[index];
+        let fetch = async {
+            match &endpoint.http_url {
+                Some(http_url)
+                    if endpoint.batch_support.load(Ordering::Relaxed) != BATCH_UNSUPPORTED =>
+                {
+                    match self.fetch_header_batch(http_url, block_numbers).await {
+                        Ok(headers) => {
+                            endpoint
+                                .batch_support
+                                .store(BATCH_SUPPORTED, Ordering::Relaxed);
+                            return Ok(Some(headers));
+                        }
+                        Err(e)
+                            if endpoint.batch_support.load(Ordering::Relaxed)
+                                == BATCH_SUPPORT_UNKNOWN
+                                && !e.is_overload() =>
+                        {
+                            warn!(
+                                "JSON-RPC batch requests to {} failed, fetching headers one by one: {}",
+                                http_url, e
+                            );
+                            endpoint
+                                .batch_support
+                                .store(BATCH_UNSUPPORTED, Ordering::Relaxed);
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+                _ => {}
+            }
+            if block_numbers.len() > 1 {
+                return Ok(None);
+            }
+            futures::stream::iter(block_numbers.to_vec())
+                .map(|block_number| async move {
+                    let block_hash = fetch_block_hash(&endpoint.client, block_number)
+                        .await?
+                        .ok_or(FetchError::BlockNotFound(block_number))?;
+                    fetch_header(&endpoint.client, block_hash)
+                        .await?
+                        .ok_or(FetchError::BlockNotFound(block_number))
+                })
+                .buffer_unordered(self.concurrency.limit())
+                .try_collect()
+                .await
+                .map(Some)
+        };
+        let result = tokio::time::timeout(self.request_timeout, fetch)
+            .await
+            .unwrap_or(Err(FetchError::Timeout(self.request_timeout)));
+        if matches!(result, Ok(Some(_))) && index != preferred {
+            self.prefer(index);
+        }
+        result
+    }
+
+    /// Whether headers are fetched one by one from the preferred endpoint, because it has no HTTP
+    /// URL for batch requests or is known to reject them.
+    fn batch_unsupported(&self) -> bool {
+        let endpoint = &self.endpoints[self.preferred.load(Ordering::Relaxed)];
+        endpoint.http_url.is_none()
+            || endpoint.batch_support.load(Ordering::Relaxed) == BATCH_UNSUPPORTED
+    }
+
+    /// Fetch headers with one batch of chain_getBlockHash and one batch of chain_getHeader calls.
+    async fn fetch_header_batch(
+        &self,
+        http_url: &str,
+        block_numbers: &[u32],
+    ) -> Result<Vec<Header>> {
+        let params: Vec<Value> = block_numbers.iter().map(|n| json!([n])).collect();
+        let block_hashes =
+            batch_request(&self.http_client, http_url, "chain_getBlockHash", &params).await?;
+
+        let params = block_numbers
+            .iter()
+            .zip(block_hashes)
+            .map(|(&block_number, block_hash)| match block_hash {
+                Value::Null => Err(FetchError::BlockNotFound(block_number)),
+                block_hash => Ok(json!([block_hash])),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let headers =
+            batch_request(&self.http_client, http_url, "chain_getHeader", &params).await?;
+
+        block_numbers
+            .iter()
+            .zip(headers)
+            .map(|(&block_number, header)| {
+                serde_json::from_value::<Option<Header>>(header)
+                    .map_err(|e| FetchError::decode("header", e))?
+                    .filter(|header| header.number == block_number)
+                    .ok_or(FetchError::BlockNotFound(block_number))
+            })
+            .collect()
+    }
+
+    /// The block hash and the authority set that justifies the block, as seen by `endpoint`.
+    async fn endpoint_view(&self, endpoint: &Endpoint, block_number: u32) -> Result<EndpointView> {
+        let client = &endpoint.client;
+        self.retry_policy
+            .run(self.request_timeout, "cross-check", || async move {
+                let block_hash = fetch_block_hash(client, block_number)
+                    .await?
+                    .ok_or(FetchError::BlockNotFound(block_number))?;
+                let authority_set = match block_number.checked_sub(1) {
+                    Some(parent_number) => {
+                        let parent_hash = fetch_block_hash(client, parent_number)
+                            .await?
+                            .ok_or(FetchError::BlockNotFound(parent_number))?;
+                        let set_id = fetch_authority_set_id(client, parent_hash).await?;
+                        let authorities =
+                            fetch_authorities(client, parent_number, parent_hash).await?;
+                        Some((set_id, authorities))
+                    }
+                    None => None,
+                };
+                Ok(EndpointView {
+                    block_hash,
+                    authority_set,
+                })
+            })
+            .await
+    }
+}
+
+/// What an endpoint reports for a block in `AvailDataSource::cross_check`.
+#[derive(Debug, Clone, PartialEq)]
+struct EndpointView {
+    block_hash: H256,
+    authority_set: Option<(Option<u64>, Vec<B256>)>,
+}
+
+/// The first view that disagrees with the first one, and what it disagrees on.
+fn find_disagreement(views: &[EndpointView]) -> Option<(usize, &'static str)> {
+    let (reference, others) = views.split_first()?;
+    others.iter().enumerate().find_map(|(i, view)| {
+        if view.block_hash != reference.block_hash {
+            Some((i + 1, "block hash"))
+        } else if view.authority_set != reference.authority_set {
+            Some((i + 1, "authority set"))
+        } else {
+            None
+        }
+    })
+}
+
+/// Index of the endpoint to read from: the preferred endpoint, unless it lags behind the endpoint
+/// with the highest finalized head by more than `max_lag` blocks (or did not respond), in which
+/// case the endpoint with the highest finalized head.
+fn select_head(heads: &[Option<u32>], preferred: usize, max_lag: u32) -> Option<usize> {
+    let (best, best_number) = heads
+        .iter()
+        .enumerate()
+        .filter_map(|(i, head)| head.map(|number| (i, number)))
+        .max_by_key(|(i, number)| (*number, std::cmp::Reverse(*i)))?;
+    match heads.get(preferred).copied().flatten() {
+        Some(number) if number + max_lag >= best_number => Some(preferred),
+        _ => Some(best),
+    }
+}
+
+async fn fetch_block_hash(client: &AvailClient, block_number: u32) -> Result<Option<H256>> {
+    client
+        .legacy_rpc()
+        .chain_get_block_hash(Some(block_number.into()))
+        .await
+        .map_err(FetchError::rpc)
+}
+
+async fn fetch_header(client: &AvailClient, block_hash: H256) -> Result<Option<Header>> {
+    client
+        .legacy_rpc()
+        .chain_get_header(Some(block_hash))
+        .await
+        .map_err(FetchError::rpc)
+}
+
+async fn fetch_finalized_head(client: &AvailClient) -> Result<Header> {
+    let head_block_hash = client
+        .legacy_rpc()
+        .chain_get_finalized_head()
+        .await
+        .map_err(FetchError::rpc)?;
+    fetch_header(client, head_block_hash)
+        .await?
+        .ok_or_else(|| FetchError::Rpc("Finalized head header not found".to_string()))
+}
+
+async fn fetch_authority_set_id(client: &AvailClient, block_hash: H256) -> Result<Option<u64>> {
+    let set_id_key = api::storage().grandpa().current_set_id();
+    client
+        .storage()
+        .at(block_hash)
+        .fetch(&set_id_key)
+        .await
+        .map_err(FetchError::rpc)
+}
+
+// Fetches the "authorities_bytes" from storage and decodes the bytes to a VersionedAuthorityList.
+async fn fetch_authorities(
+    client: &AvailClient,
+    block_number: u32,
+    block_hash: H256,
+) -> Result<Vec<B256>> {
+    let grandpa_authorities = client
+        .runtime_api()
+        .at(block_hash)
+        .call_raw::<Vec<(ed25519::Public, u64)>>("GrandpaApi_grandpa_authorities", None)
+        .await
+        .map_err(FetchError::rpc)?;
+
+    let mut authorities: Vec<B256> = Vec::new();
+    for (pub_key, weight) in grandpa_authorities {
+        authorities.push(B256::from(pub_key.0));
+        let expected_weight = 1;
+        // Check the LE representation of the weight of each validator is 1.
+        if weight != expected_weight {
+            return Err(FetchError::InvalidAuthorityWeight {
+                block_number,
+                weight,
+            });
+        }
+    }
+    Ok(authorities)
+}
+
+#[async_trait]
+impl AvailDataSource for RpcDataSource {
+    async fn block_hash(&self, block_number: u32) -> Result<B256> {
+        if let Some(block_hash) =
+            self.cached("block hash", |c, chain| c.block_hash(chain, block_number))
+        {
+            return Ok(block_hash);
+        }
+        let block_hash = self
+            .rpc_request("block hash", |client| {
+                fetch_block_hash(client, block_number)
+            })
+            .await?
+            .map(|block_hash| B256::from(block_hash.0))
+            .ok_or(FetchError::BlockNotFound(block_number))?;
+        self.cache_finalized("block hash", block_number, |c, chain| {
+            c.put_block_hash(chain, block_number, block_hash)
+        })
+        .await;
+        Ok(block_hash)
+    }
+
+    async fn header(&self, block_number: u32) -> Result<Header> {
+        if let Some(header) = self.cached("header", |c, chain| c.header(chain, block_number)) {
+            return Ok(header);
+        }
+        let block_hash = self.block_hash(block_number).await?;
+        let header = self
+            .header_by_hash(block_hash)
+            .await?
+            .ok_or(FetchError::BlockNotFound(block_number))?;
+        self.cache_finalized("header", block_number, |c, chain| {
+            c.put_header(chain, &header)
+        })
+        .await;
+        Ok(header)
+    }
+
+    async fn header_by_hash(&self, block_hash: B256) -> Result<Option<Header>> {
+        // Not cached: the hash may be of a block on a stale fork, which must not replace the
+        // canonical header of its number.
+        self.rpc_request("header", |client| {
+            fetch_header(client, H256::from(block_hash.0))
+        })
+        .await
+    }
+
+    async fn headers(&self, start_block_number: u32, end_block_number: u32) -> Result<Vec<Header>> {
+        let started = Instant::now();
+        let mut headers: BTreeMap<u32, Header> = BTreeMap::new();
+        let mut missing = Vec::new();
+        for block_number in start_block_number..=end_block_number {
+            match self.cached("header", |c, chain| c.header(chain, block_number)) {
+                Some(header) => {
+                    headers.insert(block_number, header);
+                }
+                None => missing.push(block_number),
+            }
+        }
+        let num_cached = headers.len();
+
+        let mut chunk_size = if self.batch_unsupported() {
+            1
+        } else {
+            HEADER_BATCH_SIZE
+        };
+        // Chunks of blocks to fetch, with the number of times they have been retried.
+        let mut pending: VecDeque<(Vec<u32>, u32)> = missing
+            .chunks(chunk_size)
+            .map(|chunk| (chunk.to_vec(), 0))
+            .collect();
+        while !pending.is_empty() {
+            let wave_size = std::cmp::min(self.concurrency.limit(), pending.len());
+            let wave: Vec<(Vec<u32>, u32)> = pending.drain(..wave_size).collect();
+            let results =
+                join_all(wave.iter().map(|(block_numbers, retries)| {
+                    self.fetch_header_chunk(block_numbers, *retries)
+                }))
+                .await;
+
+            let mut overloaded = false;
+            let mut split = Vec::new();
+            for ((block_numbers, retries), result) in wave.into_iter().zip(results) {
+                match result {
+                    // The endpoint takes no batch requests, fetch the blocks one by one.
+                    Ok(None) => split.extend(block_numbers.into_iter().map(|n| (vec![n], retries))),
+                    Ok(Some(fetched)) => {
+                        for header in fetched {
+                            self.cache_finalized("header", header.number, |c, chain| {
+                                c.put_header(chain, &header)
+                            })
+                            .await;
+                            headers.insert(header.number, header);
+                        }
+                    }
+                    // Overload errors count against the retry budget too, so a node that keeps
+                    // rate limiting fails the request instead of stalling it.
+                    Err(e) if e.is_overload() && retries < self.retry_policy.max_retries => {
+                        overloaded = true;
+                        pending.push_back((block_numbers, retries + 1));
+                    }
+                    Err(e) if e.is_retryable() && retries < self.retry_policy.max_retries => {
+                        warn!("Failed to fetch headers, retrying: {}", e);
+                        pending.push_back((block_numbers, retries + 1));
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+
+            if overloaded {
+                self.concurrency.on_overload();
+                warn!(
+                    "Avail RPC is overloaded, reducing header request concurrency to {}",
+                    self.concurrency.limit()
+                );
+                tokio::time::sleep(self.retry_policy.initial_backoff).await;
+            } else {
+                self.concurrency.on_success();
+            }
+
+            // Fetch the remaining blocks one by one if the preferred endpoint takes no batch
+            // requests, starting with the chunks of this wave that were split. Chunks split after
+            // failing over to another endpoint are retried first.
+            if chunk_size > 1 && self.batch_unsupported() {
+                chunk_size = 1;
+                pending = split
+                    .into_iter()
+                    .chain(pending.into_iter().flat_map(|(block_numbers, retries)| {
+                        block_numbers.into_iter().map(move |n| (vec![n], retries))
+                    }))
+                    .collect();
+            } else {
+                for chunk in split.into_iter().rev() {
+                    pending.push_front(chunk);
+                }
+            }
+        }
+
+        let elapsed = started.elapsed();
+        let num_fetched = headers.len() - num_cached;
+        info!(
+            "Fetched {} headers ({} from cache) in {:.2?}: {:.0} headers/s, concurrency {}",
+            num_fetched,
+            num_cached,
+            elapsed,
+            num_fetched as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+            self.concurrency.limit()
+        );
+
+        (start_block_number..=end_block_number)
+            .map(|block_number| {
+                headers
+                    .remove(&block_number)
+                    .ok_or(FetchError::BlockNotFound(block_number))
+            })
+            .collect()
+    }
+
+    async fn finalized_head(&self) -> Result<Header> {
+        // Query every endpoint once, so lagging endpoints are noticed.
+        let heads = join_all(self.endpoints.iter().map(|endpoint| async move {
+            tokio::time::timeout(self.request_timeout, fetch_finalized_head(&endpoint.client))
+                .await
+                .unwrap_or(Err(FetchError::Timeout(self.request_timeout)))
+        }))
+        .await;
+        let numbers: Vec<Option<u32>> = heads
+            .iter()
+            .map(|head| head.as_ref().ok().map(|header| header.number))
+            .collect();
+
+        let preferred = self.preferred.load(Ordering::Relaxed);
+        let header = match select_head(&numbers, preferred, self.endpoint_policy.max_lag) {
+            // Return the head of the endpoint that serves the following reads.
+            Some(index) => {
+                if index != preferred {
+                    self.prefer(index);
+                }
+                heads.into_iter().nth(index).unwrap()?
+            }
+            // No endpoint responded, so fall back to the retry policy.
+            None => {
+                self.rpc_request("finalized head", fetch_finalized_head)
+                    .await?
+            }
+        };
+        self.finalized_number
+            .fetch_max(header.number, Ordering::Relaxed);
+        Ok(header)
+    }
+
+    async fn authority_set_id(&self, block_number: u32) -> Result<u64> {
+        if let Some(set_id) = self.cached("authority set id", |c, chain| {
+            c.authority_set_id(chain, block_number)
+        }) {
+            return Ok(set_id);
+        }
+        let block_hash = H256::from(self.block_hash(block_number).await?.0);
+
+        let set_id = self
+            .rpc_request("authority set id", |client| {
+                fetch_authority_set_id(client, block_hash)
+            })
+            .await?
+            .ok_or_else(|| {
+                FetchError::decode(
+                    "authority set id",
+                    format!("no current set id in storage at block {}", block_number),
+                )
+            })?;
+        self.cache_finalized("authority set id", block_number, |c, chain| {
+            c.put_authority_set_id(chain, block_number, set_id)
+        })
+        .await;
+        Ok(set_id)
+    }
+
+    async fn authorities(&self, block_number: u32) -> Result<Vec<B256>> {
+        if let Some(authorities) =
+            self.cached("authorities", |c, chain| c.authorities(chain, block_number))
+        {
+            return Ok(authorities);
+        }
+        let block_hash = H256::from(self.block_hash(block_number).await?.0);
+
+        let authorities = self
+            .rpc_request("authorities", |client| {
+                fetch_authorities(client, block_number, block_hash)
+            })
+            .await?;
+        self.cache_finalized("authorities", block_number, |c, chain| {
+            c.put_authorities(chain, block_number, &authorities)
+        })
+        .await;
+        Ok(authorities)
+    }
+
+    /// Reads the justification store if one is configured, keeping the authority set of compact
+    /// records. The VectorX query service only returns the justification.
+    async fn justification_record(&self, block_number: u32) -> Result<JustificationRecord> {
+        let Some(store) = &self.justification_store else {
+            return Ok(JustificationRecord {
+                justification: self.justification(block_number).await?,
+                metadata: None,
+                authority_set: None,
+            });
+        };
+        self.request("justification", || {
+            store.get(&self.avail_chain_id, block_number)
+        })
+        .await?
+        .ok_or(FetchError::MissingJustification(block_number))
+    }
+
+    /// Asks the justification store if one is configured. The vectorx-query service has no range
+    /// queries, so without a store every block is tried in turn.
+    async fn nearest_justified_block(
+        &self,
+        start_block_number: u32,
+        end_block_number: u32,
+        prefer: Prefer,
+    ) -> Result<Option<u32>> {
+        let Some(store) = &self.justification_store else {
+            return probe_justifications(self, start_block_number, end_block_number, prefer).await;
+        };
+        self.request("nearest justified block", || {
+            store.nearest_justified_block(
+                &self.avail_chain_id,
+                start_block_number,
+                end_block_number,
+                prefer,
+            )
+        })
+        .await
+    }
+
+    /// Gets a justification from the justification store if one is configured, or else from the
+    /// vectorx-query service, which reads the data from the AWS DB.
+    async fn justification(&self, block_number: u32) -> Result<GrandpaJustification> {
+        if self.justification_store.is_some() {
+            return self
+                .justification_record(block_number)
+                .await
+                .map(|record| record.justification);
+        }
+
+        let vectorx_query_url = self
+            .vectorx_query_url
+            .as_ref()
+            .ok_or_else(|| FetchError::Config("VECTORX_QUERY_URL must be set".to_string()))?;
+
+        let request_url = format!(
+            "{}/api/justification?availChainId={}&blockNumber={}",
+            vectorx_query_url, self.avail_chain_id, block_number
+        );
+
+        let json_response = self
+            .request("justification", || async {
+                let response = self
+                    .http_client
+                    .get(&request_url)
+                    .send()
+                    .await
+                    .map_err(|e| FetchError::Query(e.to_string()))?;
+                response
+                    .json::<serde_json::Value>()
+                    .await
+                    .map_err(|e| FetchError::decode("justification query response", e))
+            })
+            .await?;
+
+        // If the service does not have a justification associated with the block, return an error.
+        // The response will have the following form:
+        // {
+        //     "success": false
+        //     "error": "No justification found."
+        // }
+        let is_success = json_response
+            .get("success")
+            .and_then(|success| success.as_bool())
+            .ok_or_else(|| {
+                FetchError::decode("justification query response", "missing success field")
+            })?;
+        if !is_success {
+            return Err(FetchError::MissingJustification(block_number));
+        }
+
+        // If the service does have a justification, it should have the following form:
+        // {
+        //     "success": true,
+        //     "justification": {
+        //         "S": "<justification as string>"
+        //     }
+        // }
+        let justification_str = json_response
+            .get("justification")
+            .and_then(|justification| justification.get("S"))
+            .and_then(|justification| justification.as_str())
+            .ok_or_else(|| {
+                FetchError::decode(
+                    "justification query response",
+                    "justification field should be a string",
+                )
+            })?;
+        serde_json::from_str(justification_str).map_err(|e| FetchError::decode("justification", e))
+    }
+
+    /// Uses grandpa_proveFinality to get the finality proof of the epoch end block.
+    async fn finality_proof(&self, block_number: u32) -> Result<FinalityProof> {
+        let encoded_finality_proof = self
+            .rpc_request("finality proof", |client| async move {
+                let mut params = RpcParams::new();
+                params.push(block_number).map_err(FetchError::rpc)?;
+                client
+                    .rpc()
+                    .request::<Option<EncodedFinalityProof>>("grandpa_proveFinality", params)
+                    .await
+                    .map_err(FetchError::rpc)
+            })
+            .await?
+            .ok_or(FetchError::BlockNotFound(block_number))?;
+
+        Decode::decode(&mut encoded_finality_proof.0 .0.as_slice())
+            .map_err(|e| FetchError::decode("finality proof", e))
+    }
+
+    async fn cross_check(&self, block_number: u32) -> Result<()> {
+        let quorum = self.endpoint_policy.quorum;
+        if quorum <= 1 {
+            return Ok(());
+        }
+
+        let preferred = self.preferred.load(Ordering::Relaxed);
+        let endpoints: Vec<&Endpoint> = (0..quorum)
+            .map(|i| &self.endpoints[(preferred + i) % self.endpoints.len()])
+            .collect();
+        let views = try_join_all(
+            endpoints
+                .iter()
+                .map(|endpoint| self.endpoint_view(endpoint, block_number)),
+        )
+        .await?;
+
+        match find_disagreement(&views) {
+            Some((index, what)) => Err(FetchError::EndpointDisagreement {
+                what,
+                block_number,
+                endpoints: vec![endpoints[0].url.clone(), endpoints[index].url.clone()],
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_head_fails_over_from_lagging_endpoint() {
+        // The preferred endpoint is within the allowed lag.
+        assert_eq!(select_head(&[Some(95), Some(100)], 0, 10), Some(0));
+        // The preferred endpoint lags too far behind.
+        assert_eq!(select_head(&[Some(80), Some(100)], 0, 10), Some(1));
+        // The preferred endpoint did not respond.
+        assert_eq!(select_head(&[Some(100), None], 1, 10), Some(0));
+        assert_eq!(select_head(&[None, None], 0, 10), None);
+    }
+
+    #[test]
+    fn test_find_disagreement() {
+        let view = EndpointView {
+            block_hash: H256::repeat_byte(1),
+            authority_set: Some((Some(3), vec![B256::repeat_byte(2)])),
+        };
+        let forked = EndpointView {
+            block_hash: H256::repeat_byte(9),
+            ..view.clone()
+        };
+        let other_set = EndpointView {
+            authority_set: Some((Some(4), vec![B256::repeat_byte(2)])),
+            ..view.clone()
+        };
+
+        assert_eq!(find_disagreement(&[view.clone(), view.clone()]), None);
+        assert_eq!(
+            find_disagreement(&[view.clone(), view.clone(), forked]),
+            Some((2, "block hash"))
+        );
+        assert_eq!(
+            find_disagreement(&[view, other_set]),
+            Some((1, "authority set"))
+        );
+    }
+}
@@ -0,0 +1,449 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use aws_sdk_dynamodb::primitives::Blob;
+use aws_sdk_dynamodb::types::{AttributeValue, DeleteRequest, KeysAndAttributes, WriteRequest};
+use aws_sdk_dynamodb::Client;
+use log::info;
+
+use super::format::{decode_compact, decode_json, encode_compact, encode_json};
+use super::{
+    scan_nearest, storage_error, JustificationRecord, JustificationStore, Prefer, RecordFormat,
+    ValidationMetadata,
+};
+use crate::error::FetchError;
+use crate::input::Result;
+
+pub(super) const DEFAULT_TABLE: &str = "justifications-v2";
+
+/// Maximum number of keys in a DynamoDB BatchGetItem request.
+const MAX_BATCH_GET_KEYS: usize = 100;
+
+/// Maximum number of requests in a DynamoDB BatchWriteItem request.
+const MAX_BATCH_WRITE_REQUESTS: usize = 25;
+
+/// Justifications stored in a DynamoDB table keyed by `<chain id>-<block number>`. JSON records
+/// are in the `data` attribute read by the VectorX query service and compact records in the
+/// binary `compact` attribute, with the validation metadata in numeric attributes next to them.
+/// Readers that only understand `data` need `with_json_data` to read compact records.
+///
+/// The table has no sort key, so the highest stored block of each chain is tracked in an extra
+/// `<chain id>-latest` item. Records also carry `chain_id` and `block_number` attributes, so a
+/// global secondary index on them can answer nearest justified block queries.
+pub struct DynamoDbStore {
+    client: Client,
+    table: String,
+    format: RecordFormat,
+    index: Option<String>,
+    json_data: bool,
+}
+
+fn key(avail_chain_id: &str, block_number: u32) -> AttributeValue {
+    AttributeValue::S(format!("{}-{}", avail_chain_id, block_number).to_lowercase())
+}
+
+fn latest_key(avail_chain_id: &str) -> AttributeValue {
+    AttributeValue::S(format!("{}-latest", avail_chain_id).to_lowercase())
+}
+
+/// The block number in the `id` key of an item.
+fn block_number_from_key(item: &HashMap<String, AttributeValue>) -> Result<u32> {
+    item.get("id")
+        .and_then(|id| id.as_s().ok())
+        .and_then(|id| id.rsplit_once('-'))
+        .and_then(|(_, block_number)| block_number.parse().ok())
+        .ok_or_else(|| FetchError::decode("stored justification", "invalid id attribute"))
+}
+
+fn number<T: std::str::FromStr>(item: &HashMap<String, AttributeValue>, name: &str) -> Option<T> {
+    item.get(name)?.as_n().ok()?.parse().ok()
+}
+
+fn decode(item: &HashMap<String, AttributeValue>) -> Result<JustificationRecord> {
+    if let Some(compact) = item.get("compact").and_then(|data| data.as_b().ok()) {
+        return decode_compact(compact.as_ref());
+    }
+    let data = item
+        .get("data")
+        .and_then(|data| data.as_s().ok())
+        .ok_or_else(|| FetchError::decode("stored justification", "missing data attribute"))?;
+    let justification = decode_json(data)?;
+    let metadata = match (
+        number(item, "authority_set_id"),
+        number(item, "signer_count"),
+        number(item, "validator_set_size"),
+    ) {
+        (Some(authority_set_id), Some(signer_count), Some(validator_set_size)) => {
+            Some(ValidationMetadata {
+                authority_set_id,
+                signer_count,
+                validator_set_size,
+            })
+        }
+        _ => None,
+    };
+    Ok(JustificationRecord {
+        justification,
+        metadata,
+        authority_set: None,
+    })
+}
+
+impl DynamoDbStore {
+    /// Connect to `table` with the AWS configuration from the environment.
+    pub async fn new(table: impl Into<String>) -> Self {
+        let shared_config = aws_config::load_from_env().await;
+        Self {
+            client: Client::new(&shared_config),
+            table: table.into(),
+            format: RecordFormat::default(),
+            index: None,
+            json_data: false,
+        }
+    }
+
+    /// Write new records in `format`. Records in either format can be read.
+    pub fn with_format(mut self, format: RecordFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Also write the JSON `data` attribute on compact records, so readers that only understand
+    /// JSON records can read them. The JSON is ignored when reading, and makes compact items
+    /// larger than JSON ones.
+    pub fn with_json_data(mut self, json_data: bool) -> Self {
+        self.json_data = json_data;
+        self
+    }
+
+    /// Query the global secondary `index`, with partition key `chain_id` and numeric sort key
+    /// `block_number`, for the nearest justified block and the stored block numbers. Without an
+    /// index every key in the range is requested. Records written before the attributes were
+    /// added are not in the index.
+    pub fn with_index(mut self, index: impl Into<String>) -> Self {
+        self.index = Some(index.into());
+        self
+    }
+
+    /// The items of the blocks in `start_block_number..=end_block_number` that exist, with only
+    /// the `projection` attributes if one is given. Every key in the range is requested.
+    async fn batch_get(
+        &self,
+        avail_chain_id: &str,
+        start_block_number: u32,
+        end_block_number: u32,
+        projection: Option<&str>,
+    ) -> Result<Vec<HashMap<String, AttributeValue>>> {
+        let block_numbers: Vec<u32> = (start_block_number..=end_block_number).collect();
+        let mut items = Vec::new();
+        for chunk in block_numbers.chunks(MAX_BATCH_GET_KEYS) {
+            let keys = chunk
+                .iter()
+                .map(|&block_number| {
+                    HashMap::from([("id".to_string(), key(avail_chain_id, block_number))])
+                })
+                .collect();
+            let mut request = Some(
+                KeysAndAttributes::builder()
+                    .set_keys(Some(keys))
+                    .set_projection_expression(projection.map(str::to_string))
+                    .build()
+                    .map_err(storage_error)?,
+            );
+            // Keys that DynamoDB did not process are returned and must be requested again.
+            while let Some(keys) = request.take() {
+                let resp = self
+                    .client
+                    .batch_get_item()
+                    .request_items(&self.table, keys)
+                    .send()
+                    .await
+                    .map_err(storage_error)?;
+                if let Some(responses) = resp.responses.and_then(|mut r| r.remove(&self.table)) {
+                    items.extend(responses);
+                }
+                request = resp
+                    .unprocessed_keys
+                    .and_then(|mut keys| keys.remove(&self.table))
+                    .filter(|keys| !keys.keys.is_empty());
+            }
+        }
+        Ok(items)
+    }
+
+    /// Record `block_number` as the latest stored block of the chain, unless a later block is
+    /// already recorded.
+    async fn advance_latest(&self, avail_chain_id: &str, block_number: u32) -> Result<()> {
+        let result = self
+            .client
+            .update_item()
+            .table_name(&self.table)
+            .key("id", latest_key(avail_chain_id))
+            .update_expression("SET block_number = :n")
+            .condition_expression("attribute_not_exists(block_number) OR block_number < :n")
+            .expression_attribute_values(":n", AttributeValue::N(block_number.to_string()))
+            .send()
+            .await;
+        match result {
+            Ok(_) => Ok(()),
+            Err(e)
+                if e.as_service_error()
+                    .is_some_and(|e| e.is_conditional_check_failed_exception()) =>
+            {
+                Ok(())
+            }
+            Err(e) => Err(storage_error(e)),
+        }
+    }
+}
+
+#[async_trait]
+impl JustificationStore for DynamoDbStore {
+    async fn put(&self, avail_chain_id: &str, record: &JustificationRecord) -> Result<()> {
+        let block_number = record.block_number();
+
+        info!("Adding justification for block number: {:?}", block_number);
+
+        let mut request = self
+            .client
+            .put_item()
+            .table_name(&self.table)
+            .item("id", key(avail_chain_id, block_number))
+            .item("chain_id", AttributeValue::S(avail_chain_id.to_lowercase()))
+            .item("block_number", AttributeValue::N(block_number.to_string()));
+        if self.format == RecordFormat::Compact {
+            request = request.item(
+                "compact",
+                AttributeValue::B(Blob::new(encode_compact(record)?)),
+            );
+        }
+        if self.format == RecordFormat::Json || self.json_data {
+            request = request.item("data", AttributeValue::S(encode_json(record)?));
+        }
+        if let Some(metadata) = &record.metadata {
+            request = request
+                .item(
+                    "authority_set_id",
+                    AttributeValue::N(metadata.authority_set_id.to_string()),
+                )
+                .item(
+                    "signer_count",
+                    AttributeValue::N(metadata.signer_count.to_string()),
+                )
+                .item(
+                    "validator_set_size",
+                    AttributeValue::N(metadata.validator_set_size.to_string()),
+                );
+        }
+        request.send().await.map_err(storage_error)?;
+        self.advance_latest(avail_chain_id, block_number).await
+    }
+
+    async fn get(
+        &self,
+        avail_chain_id: &str,
+        block_number: u32,
+    ) -> Result<Option<JustificationRecord>> {
+        let resp = self
+            .client
+            .get_item()
+            .table_name(&self.table)
+            .key("id", key(avail_chain_id, block_number))
+            .send()
+            .await
+            .map_err(storage_error)?;
+        resp.item.as_ref().map(decode).transpose()
+    }
+
+    async fn range(
+        &self,
+        avail_chain_id: &str,
+        start_block_number: u32,
+        end_block_number: u32,
+    ) -> Result<Vec<JustificationRecord>> {
+        let mut justifications = self
+            .batch_get(avail_chain_id, start_block_number, end_block_number, None)
+            .await?
+            .iter()
+            .map(decode)
+            .collect::<Result<Vec<_>>>()?;
+        justifications.sort_by_key(JustificationRecord::block_number);
+        Ok(justifications)
+    }
+
+    async fn latest(&self, avail_chain_id: &str) -> Result<Option<JustificationRecord>> {
+        let resp = self
+            .client
+            .get_item()
+            .table_name(&self.table)
+            .key("id", latest_key(avail_chain_id))
+            .send()
+            .await
+            .map_err(storage_error)?;
+        let block_number = resp
+            .item
+            .as_ref()
+            .and_then(|item| item.get("block_number"))
+            .and_then(|block_number| block_number.as_n().ok())
+            .map(|block_number| {
+                block_number
+                    .parse::<u32>()
+                    .map_err(|e| FetchError::decode("latest justification block number", e))
+            })
+            .transpose()?;
+        match block_number {
+            Some(block_number) => self.get(avail_chain_id, block_number).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Deletes the items but not the `<chain id>-latest` item, so the latest stored block must not
+    /// be deleted.
+    async fn delete(&self, avail_chain_id: &str, block_numbers: &[u32]) -> Result<()> {
+        for chunk in block_numbers.chunks(MAX_BATCH_WRITE_REQUESTS) {
+            let mut requests = chunk
+                .iter()
+                .map(|&block_number| {
+                    Ok(WriteRequest::builder()
+                        .delete_request(
+                            DeleteRequest::builder()
+                                .key("id", key(avail_chain_id, block_number))
+                                .build()
+                                .map_err(storage_error)?,
+                        )
+                        .build())
+                })
+                .collect::<Result<Vec<_>>>()?;
+            // Requests that DynamoDB did not process are returned and must be sent again.
+            while !requests.is_empty() {
+                let resp = self
+                    .client
+                    .batch_write_item()
+                    .request_items(&self.table, requests)
+                    .send()
+                    .await
+                    .map_err(storage_error)?;
+                requests = resp
+                    .unprocessed_items
+                    .and_then(|mut items| items.remove(&self.table))
+                    .unwrap_or_default();
+            }
+        }
+        Ok(())
+    }
+
+    /// Queries the `index` for the block numbers, a page at a time. Without an index every key in
+    /// the range is requested, so the cost scales with the size of the range rather than with the
+    /// number of stored justifications.
+    async fn block_numbers(
+        &self,
+        avail_chain_id: &str,
+        start_block_number: u32,
+        end_block_number: u32,
+    ) -> Result<Vec<u32>> {
+        if start_block_number > end_block_number {
+            return Ok(Vec::new());
+        }
+        let Some(index) = &self.index else {
+            let mut block_numbers = self
+                .batch_get(
+                    avail_chain_id,
+                    start_block_number,
+                    end_block_number,
+                    Some("id"),
+                )
+                .await?
+                .iter()
+                .map(block_number_from_key)
+                .collect::<Result<Vec<_>>>()?;
+            block_numbers.sort_unstable();
+            return Ok(block_numbers);
+        };
+        let mut block_numbers = Vec::new();
+        let mut start_key = None;
+        loop {
+            let resp = self
+                .client
+                .query()
+                .table_name(&self.table)
+                .index_name(index)
+                .key_condition_expression(
+                    "chain_id = :chain AND block_number BETWEEN :start AND :end",
+                )
+                .expression_attribute_values(
+                    ":chain",
+                    AttributeValue::S(avail_chain_id.to_lowercase()),
+                )
+                .expression_attribute_values(
+                    ":start",
+                    AttributeValue::N(start_block_number.to_string()),
+                )
+                .expression_attribute_values(
+                    ":end",
+                    AttributeValue::N(end_block_number.to_string()),
+                )
+                .projection_expression("block_number")
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await
+                .map_err(storage_error)?;
+            for item in resp.items.unwrap_or_default() {
+                block_numbers.push(number(&item, "block_number").ok_or_else(|| {
+                    FetchError::decode("stored justification", "missing block_number attribute")
+                })?);
+            }
+            start_key = resp.last_evaluated_key.filter(|key| !key.is_empty());
+            if start_key.is_none() {
+                return Ok(block_numbers);
+            }
+        }
+    }
+
+    async fn nearest_justified_block(
+        &self,
+        avail_chain_id: &str,
+        start_block_number: u32,
+        end_block_number: u32,
+        prefer: Prefer,
+    ) -> Result<Option<u32>> {
+        let Some(index) = &self.index else {
+            return scan_nearest(
+                self,
+                avail_chain_id,
+                start_block_number,
+                end_block_number,
+                prefer,
+            )
+            .await;
+        };
+        if start_block_number > end_block_number {
+            return Ok(None);
+        }
+        let resp = self
+            .client
+            .query()
+            .table_name(&self.table)
+            .index_name(index)
+            .key_condition_expression("chain_id = :chain AND block_number BETWEEN :start AND :end")
+            .expression_attribute_values(":chain", AttributeValue::S(avail_chain_id.to_lowercase()))
+            .expression_attribute_values(
+                ":start",
+                AttributeValue::N(start_block_number.to_string()),
+            )
+            .expression_attribute_values(":end", AttributeValue::N(end_block_number.to_string()))
+            .scan_index_forward(prefer == Prefer::Lowest)
+            .limit(1)
+            .send()
+            .await
+            .map_err(storage_error)?;
+        resp.items
+            .unwrap_or_default()
+            .first()
+            .map(|item| {
+                number(item, "block_number").ok_or_else(|| {
+                    FetchError::decode("stored justification", "missing block_number attribute")
+                })
+            })
+            .transpose()
+    }
+}
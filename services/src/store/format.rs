@@ -0,0 +1,139 @@
+//! Encodings of stored justification records.
+//!
+//! JSON records hold the `serde_json` encoding of the justification, as written by earlier
+//! versions of the indexer. Compact records are a version byte followed by the zlib-compressed
+//! SCALE encoding of the justification, its validation metadata and the authority set that
+//! justifies it. Readers accept both, so a store can switch formats without migrating old records.
+
+use std::io::{Read, Write};
+
+use alloy_primitives::B256;
+use codec::{Decode, Encode};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use serde::Deserialize;
+
+use super::{storage_error, AuthoritySet, JustificationRecord, ValidationMetadata};
+use crate::error::FetchError;
+use crate::input::Result;
+use crate::types::GrandpaJustification;
+
+/// How new records are encoded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordFormat {
+    /// The JSON encoding of the justification. Readable by the VectorX query service.
+    #[default]
+    Json,
+    /// Compressed SCALE encoding of the justification together with its authority set.
+    Compact,
+}
+
+impl std::str::FromStr for RecordFormat {
+    type Err = FetchError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "json" => Ok(RecordFormat::Json),
+            "compact" => Ok(RecordFormat::Compact),
+            _ => Err(FetchError::Config(format!(
+                "Unknown justification record format {}",
+                s
+            ))),
+        }
+    }
+}
+
+const COMPACT_VERSION: u8 = 1;
+
+#[derive(Encode, Decode)]
+struct CompactMetadata {
+    authority_set_id: u64,
+    signer_count: u32,
+    validator_set_size: u32,
+}
+
+#[derive(Encode, Decode)]
+struct CompactAuthoritySet {
+    authority_set_id: u64,
+    authority_set_hash: [u8; 32],
+    authorities: Vec<[u8; 32]>,
+}
+
+#[derive(Encode, Decode)]
+struct CompactRecord {
+    justification: GrandpaJustification,
+    metadata: Option<CompactMetadata>,
+    authority_set: Option<CompactAuthoritySet>,
+}
+
+pub(super) fn encode_json(record: &JustificationRecord) -> Result<String> {
+    serde_json::to_string(&record.justification).map_err(storage_error)
+}
+
+pub(super) fn decode_json(data: &str) -> Result<GrandpaJustification> {
+    serde_json::from_str(data).map_err(|e| FetchError::decode("stored justification", e))
+}
+
+pub(super) fn encode_compact(record: &JustificationRecord) -> Result<Vec<u8>> {
+    let compact = CompactRecord {
+        justification: record.justification.clone(),
+        metadata: record.metadata.map(|metadata| CompactMetadata {
+            authority_set_id: metadata.authority_set_id,
+            signer_count: metadata.signer_count as u32,
+            validator_set_size: metadata.validator_set_size as u32,
+        }),
+        authority_set: record
+            .authority_set
+            .as_ref()
+            .map(|authority_set| CompactAuthoritySet {
+                authority_set_id: authority_set.authority_set_id,
+                authority_set_hash: authority_set.authority_set_hash.0,
+                authorities: authority_set.authorities.iter().map(|a| a.0).collect(),
+            }),
+    };
+
+    let mut encoder = ZlibEncoder::new(vec![COMPACT_VERSION], Compression::default());
+    encoder
+        .write_all(&compact.encode())
+        .map_err(storage_error)?;
+    encoder.finish().map_err(storage_error)
+}
+
+pub(super) fn decode_compact(data: &[u8]) -> Result<JustificationRecord> {
+    let compressed = match data.split_first() {
+        Some((&COMPACT_VERSION, compressed)) => compressed,
+        Some((version, _)) => {
+            return Err(FetchError::decode(
+                "stored justification",
+                format!("unknown compact record version {}", version),
+            ))
+        }
+        None => return Err(FetchError::decode("stored justification", "empty record")),
+    };
+    let mut encoded = Vec::new();
+    ZlibDecoder::new(compressed)
+        .read_to_end(&mut encoded)
+        .map_err(|e| FetchError::decode("stored justification", e))?;
+    let compact = CompactRecord::decode(&mut encoded.as_slice())
+        .map_err(|e| FetchError::decode("stored justification", e))?;
+
+    Ok(JustificationRecord {
+        justification: compact.justification,
+        metadata: compact.metadata.map(|metadata| ValidationMetadata {
+            authority_set_id: metadata.authority_set_id,
+            signer_count: metadata.signer_count as usize,
+            validator_set_size: metadata.validator_set_size as usize,
+        }),
+        authority_set: compact.authority_set.map(|authority_set| AuthoritySet {
+            authority_set_id: authority_set.authority_set_id,
+            authority_set_hash: B256::from(authority_set.authority_set_hash),
+            authorities: authority_set
+                .authorities
+                .into_iter()
+                .map(B256::from)
+                .collect(),
+        }),
+    })
+}
@@ -0,0 +1,253 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+
+use super::format::{decode_compact, decode_json, encode_compact, encode_json};
+use super::{
+    storage_error, JustificationRecord, JustificationStore, Prefer, RecordFormat,
+    ValidationMetadata,
+};
+use crate::error::FetchError;
+use crate::input::Result;
+
+/// Justification records stored as files in a directory, at `<root>/<chain id>/<block number>.json`
+/// for JSON records and `<root>/<chain id>/<block number>.bin` for compact records. The validation
+/// metadata of a JSON record is stored next to it in `<block number>.metadata.json`.
+///
+/// There is no index: range, nearest justified block and latest queries list the directory of the
+/// chain on every call. The store is meant for tests and small local data sets, use the SQLite or
+/// DynamoDB store for a full chain.
+pub struct DirectoryStore {
+    root: PathBuf,
+    format: RecordFormat,
+}
+
+fn extension(format: RecordFormat) -> &'static str {
+    match format {
+        RecordFormat::Json => "json",
+        RecordFormat::Compact => "bin",
+    }
+}
+
+fn other_format(format: RecordFormat) -> RecordFormat {
+    match format {
+        RecordFormat::Json => RecordFormat::Compact,
+        RecordFormat::Compact => RecordFormat::Json,
+    }
+}
+
+impl DirectoryStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            format: RecordFormat::default(),
+        }
+    }
+
+    /// Write new records in `format`. Records in either format can be read.
+    pub fn with_format(mut self, format: RecordFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    fn chain_dir(&self, avail_chain_id: &str) -> PathBuf {
+        self.root.join(avail_chain_id.to_lowercase())
+    }
+
+    fn path(&self, avail_chain_id: &str, block_number: u32, format: RecordFormat) -> PathBuf {
+        self.chain_dir(avail_chain_id)
+            .join(format!("{}.{}", block_number, extension(format)))
+    }
+
+    fn metadata_path(&self, avail_chain_id: &str, block_number: u32) -> PathBuf {
+        self.chain_dir(avail_chain_id)
+            .join(format!("{}.metadata.json", block_number))
+    }
+
+    /// The record for `block_number`, preferring the format the store writes if the block has a
+    /// record in both.
+    fn read(&self, avail_chain_id: &str, block_number: u32) -> Result<Option<JustificationRecord>> {
+        for format in [self.format, other_format(self.format)] {
+            let Some(data) = read(&self.path(avail_chain_id, block_number, format))? else {
+                continue;
+            };
+            let record = match format {
+                RecordFormat::Json => JustificationRecord {
+                    justification: decode_json(
+                        std::str::from_utf8(&data)
+                            .map_err(|e| FetchError::decode("stored justification", e))?,
+                    )?,
+                    metadata: read(&self.metadata_path(avail_chain_id, block_number))?
+                        .map(|data| serde_json::from_slice::<ValidationMetadata>(&data))
+                        .transpose()
+                        .map_err(|e| FetchError::decode("stored validation metadata", e))?,
+                    authority_set: None,
+                },
+                RecordFormat::Compact => decode_compact(&data)?,
+            };
+            return Ok(Some(record));
+        }
+        Ok(None)
+    }
+
+    /// Block numbers of the justifications stored for the chain, in ascending order.
+    fn stored_block_numbers(&self, avail_chain_id: &str) -> Result<Vec<u32>> {
+        let entries = match fs::read_dir(self.chain_dir(avail_chain_id)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(storage_error(e)),
+        };
+        let mut block_numbers = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(storage_error)?.path();
+            if path
+                .extension()
+                .is_some_and(|ext| ext == "json" || ext == "bin")
+            {
+                if let Some(block_number) = path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .and_then(|stem| stem.parse().ok())
+                {
+                    block_numbers.push(block_number);
+                }
+            }
+        }
+        block_numbers.sort_unstable();
+        block_numbers.dedup();
+        Ok(block_numbers)
+    }
+}
+
+/// The contents of the file at `path`, if there is one.
+fn read(path: &Path) -> Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(storage_error(e)),
+    }
+}
+
+/// Write `data` to `path`. The data is written to a temporary file first, so readers never see
+/// a partially written file.
+fn write(path: &Path, data: &[u8]) -> Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    fs::write(&tmp_path, data).map_err(storage_error)?;
+    fs::rename(&tmp_path, path).map_err(storage_error)
+}
+
+/// Remove the file at `path`, if there is one.
+fn remove(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(storage_error(e)),
+        _ => Ok(()),
+    }
+}
+
+#[async_trait]
+impl JustificationStore for DirectoryStore {
+    async fn put(&self, avail_chain_id: &str, record: &JustificationRecord) -> Result<()> {
+        let block_number = record.block_number();
+        fs::create_dir_all(self.chain_dir(avail_chain_id)).map_err(storage_error)?;
+        let metadata_path = self.metadata_path(avail_chain_id, block_number);
+        match self.format {
+            RecordFormat::Json => {
+                // Write the metadata first, so a new record is never read without its metadata. The
+                // two files are not replaced atomically: if a block is rewritten, a crash in
+                // between pairs the old record with the new metadata until it is written again.
+                match &record.metadata {
+                    Some(metadata) => write(
+                        &metadata_path,
+                        &serde_json::to_vec(metadata).map_err(storage_error)?,
+                    )?,
+                    None => remove(&metadata_path)?,
+                }
+                write(
+                    &self.path(avail_chain_id, block_number, RecordFormat::Json),
+                    encode_json(record)?.as_bytes(),
+                )?;
+            }
+            RecordFormat::Compact => {
+                write(
+                    &self.path(avail_chain_id, block_number, RecordFormat::Compact),
+                    &encode_compact(record)?,
+                )?;
+                remove(&metadata_path)?;
+            }
+        }
+
+        // Remove the record in the other format, so a rewritten block is not read from it.
+        remove(&self.path(avail_chain_id, block_number, other_format(self.format)))
+    }
+
+    async fn get(
+        &self,
+        avail_chain_id: &str,
+        block_number: u32,
+    ) -> Result<Option<JustificationRecord>> {
+        self.read(avail_chain_id, block_number)
+    }
+
+    async fn range(
+        &self,
+        avail_chain_id: &str,
+        start_block_number: u32,
+        end_block_number: u32,
+    ) -> Result<Vec<JustificationRecord>> {
+        let mut justifications = Vec::new();
+        for block_number in self.stored_block_numbers(avail_chain_id)? {
+            if (start_block_number..=end_block_number).contains(&block_number) {
+                justifications.extend(self.read(avail_chain_id, block_number)?);
+            }
+        }
+        Ok(justifications)
+    }
+
+    async fn delete(&self, avail_chain_id: &str, block_numbers: &[u32]) -> Result<()> {
+        for &block_number in block_numbers {
+            for format in [RecordFormat::Json, RecordFormat::Compact] {
+                remove(&self.path(avail_chain_id, block_number, format))?;
+            }
+            remove(&self.metadata_path(avail_chain_id, block_number))?;
+        }
+        Ok(())
+    }
+
+    async fn nearest_justified_block(
+        &self,
+        avail_chain_id: &str,
+        start_block_number: u32,
+        end_block_number: u32,
+        prefer: Prefer,
+    ) -> Result<Option<u32>> {
+        let block_numbers = self
+            .block_numbers(avail_chain_id, start_block_number, end_block_number)
+            .await?;
+        Ok(match prefer {
+            Prefer::Lowest => block_numbers.first().copied(),
+            Prefer::Highest => block_numbers.last().copied(),
+        })
+    }
+
+    async fn block_numbers(
+        &self,
+        avail_chain_id: &str,
+        start_block_number: u32,
+        end_block_number: u32,
+    ) -> Result<Vec<u32>> {
+        let mut block_numbers = self.stored_block_numbers(avail_chain_id)?;
+        block_numbers
+            .retain(|block_number| (start_block_number..=end_block_number).contains(block_number));
+        Ok(block_numbers)
+    }
+
+    async fn latest(&self, avail_chain_id: &str) -> Result<Option<JustificationRecord>> {
+        match self.stored_block_numbers(avail_chain_id)?.last() {
+            Some(&block_number) => self.read(avail_chain_id, block_number),
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,592 @@
+//! Storage for GRANDPA justifications indexed from the Avail RPC. The indexer writes to a
+//! `JustificationStore` and `RpcDataFetcher` can read from the same store directly, so a
+//! self-hosted deployment needs neither AWS nor the VectorX query service.
+
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use alloy_primitives::B256;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::api::JustificationApiClient;
+use crate::coverage::IntervalSet;
+use crate::error::FetchError;
+use crate::input::{Result, RetryPolicy};
+use crate::types::GrandpaJustification;
+
+mod dynamodb;
+mod format;
+mod fs;
+mod sqlite;
+
+pub use dynamodb::DynamoDbStore;
+pub use format::RecordFormat;
+pub use fs::DirectoryStore;
+pub use sqlite::SqliteStore;
+
+/// Result of verifying a justification natively against the authority set of its block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationMetadata {
+    /// Id of the authority set the justification was verified against.
+    pub authority_set_id: u64,
+    /// Number of validators whose precommits count towards the justification.
+    pub signer_count: usize,
+    /// Size of the authority set.
+    pub validator_set_size: usize,
+}
+
+/// The authority set that justifies a block, resolved from the block before it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthoritySet {
+    pub authority_set_id: u64,
+    /// `compute_authority_set_commitment` of `authorities`.
+    pub authority_set_hash: B256,
+    pub authorities: Vec<B256>,
+}
+
+/// A stored justification.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JustificationRecord {
+    pub justification: GrandpaJustification,
+    /// `None` for justifications stored without being validated.
+    pub metadata: Option<ValidationMetadata>,
+    /// `None` if the store did not keep it. JSON records in SQLite and DynamoDB only hold the
+    /// justification and its metadata.
+    #[serde(default)]
+    pub authority_set: Option<AuthoritySet>,
+}
+
+impl JustificationRecord {
+    pub fn block_number(&self) -> u32 {
+        self.justification.commit.target_number
+    }
+}
+
+/// A store of justifications keyed by Avail chain id and the number of the justified block.
+#[async_trait]
+pub trait JustificationStore: Send + Sync {
+    /// Store a justification, replacing any stored justification for the same block.
+    async fn put(&self, avail_chain_id: &str, record: &JustificationRecord) -> Result<()>;
+
+    /// The justification for `block_number`, if one is stored.
+    async fn get(
+        &self,
+        avail_chain_id: &str,
+        block_number: u32,
+    ) -> Result<Option<JustificationRecord>>;
+
+    /// Stored justifications for blocks in `start_block_number..=end_block_number`, ordered by
+    /// block number. Blocks without a justification are skipped.
+    async fn range(
+        &self,
+        avail_chain_id: &str,
+        start_block_number: u32,
+        end_block_number: u32,
+    ) -> Result<Vec<JustificationRecord>>;
+
+    /// The stored justification with the highest block number.
+    async fn latest(&self, avail_chain_id: &str) -> Result<Option<JustificationRecord>>;
+
+    /// Delete the stored justifications for `block_numbers`. Blocks without one are skipped.
+    async fn delete(&self, avail_chain_id: &str, block_numbers: &[u32]) -> Result<()>;
+
+    /// Block numbers of the stored justifications in `start_block_number..=end_block_number`, in
+    /// ascending order. By default the records in the range are read.
+    async fn block_numbers(
+        &self,
+        avail_chain_id: &str,
+        start_block_number: u32,
+        end_block_number: u32,
+    ) -> Result<Vec<u32>> {
+        Ok(self
+            .range(avail_chain_id, start_block_number, end_block_number)
+            .await?
+            .iter()
+            .map(JustificationRecord::block_number)
+            .collect())
+    }
+
+    /// The blocks in `start_block_number..=end_block_number` with a stored justification. By
+    /// default built from `block_numbers`.
+    async fn justified_blocks(
+        &self,
+        avail_chain_id: &str,
+        start_block_number: u32,
+        end_block_number: u32,
+    ) -> Result<IntervalSet> {
+        Ok(self
+            .block_numbers(avail_chain_id, start_block_number, end_block_number)
+            .await?
+            .into_iter()
+            .collect())
+    }
+
+    /// The lowest or highest block in `start_block_number..=end_block_number` with a stored
+    /// justification, as preferred. By default the block numbers are read in chunks from the
+    /// preferred end of the range until one is found.
+    async fn nearest_justified_block(
+        &self,
+        avail_chain_id: &str,
+        start_block_number: u32,
+        end_block_number: u32,
+        prefer: Prefer,
+    ) -> Result<Option<u32>> {
+        scan_nearest(
+            self,
+            avail_chain_id,
+            start_block_number,
+            end_block_number,
+            prefer,
+        )
+        .await
+    }
+}
+
+/// Find the nearest justified block by reading the block numbers in chunks of
+/// `NEAREST_CHUNK_SIZE` from the preferred end of the range.
+pub(super) async fn scan_nearest<S: JustificationStore + ?Sized>(
+    store: &S,
+    avail_chain_id: &str,
+    start_block_number: u32,
+    end_block_number: u32,
+    prefer: Prefer,
+) -> Result<Option<u32>> {
+    if start_block_number > end_block_number {
+        return Ok(None);
+    }
+    let mut chunk = match prefer {
+        Prefer::Lowest => {
+            start_block_number
+                ..=start_block_number
+                    .saturating_add(NEAREST_CHUNK_SIZE - 1)
+                    .min(end_block_number)
+        }
+        Prefer::Highest => {
+            end_block_number
+                .saturating_sub(NEAREST_CHUNK_SIZE - 1)
+                .max(start_block_number)..=end_block_number
+        }
+    };
+    loop {
+        let block_numbers = store
+            .block_numbers(avail_chain_id, *chunk.start(), *chunk.end())
+            .await?;
+        let nearest = match prefer {
+            Prefer::Lowest => block_numbers.first(),
+            Prefer::Highest => block_numbers.last(),
+        };
+        if let Some(&block_number) = nearest {
+            return Ok(Some(block_number));
+        }
+        chunk = match prefer {
+            Prefer::Lowest if *chunk.end() < end_block_number => {
+                let start = *chunk.end() + 1;
+                start
+                    ..=start
+                        .saturating_add(NEAREST_CHUNK_SIZE - 1)
+                        .min(end_block_number)
+            }
+            Prefer::Highest if *chunk.start() > start_block_number => {
+                let end = *chunk.start() - 1;
+                end.saturating_sub(NEAREST_CHUNK_SIZE - 1)
+                    .max(start_block_number)..=end
+            }
+            _ => return Ok(None),
+        };
+    }
+}
+
+/// Number of blocks read at a time by the default `JustificationStore::nearest_justified_block`.
+const NEAREST_CHUNK_SIZE: u32 = 100;
+
+/// Which end of a range `nearest_justified_block` searches from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Prefer {
+    /// The justified block with the lowest number.
+    #[default]
+    Lowest,
+    /// The justified block with the highest number.
+    Highest,
+}
+
+/// Which `JustificationStore` backend to use. In configuration files the backend is selected by
+/// `kind`, with the same names as JUSTIFICATION_STORE.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "kind")]
+pub enum StoreBackend {
+    /// A DynamoDB table, configured with the standard AWS environment variables.
+    #[serde(rename = "dynamodb")]
+    DynamoDb {
+        #[serde(default = "default_table")]
+        table: String,
+        /// Global secondary index on `chain_id` and `block_number`, used to find the nearest
+        /// justified block with one query and to list the stored blocks for coverage and pruning.
+        /// Without it, both request every block in the range.
+        #[serde(default)]
+        index: Option<String>,
+        /// Also write the JSON `data` attribute on compact records, for readers of the table that
+        /// only understand JSON records such as the VectorX query service. Such items are larger
+        /// than JSON records, so only set it while those readers still read the table directly.
+        #[serde(default)]
+        json_data: bool,
+    },
+    /// A local SQLite database.
+    #[serde(rename = "sqlite")]
+    Sqlite { path: PathBuf },
+    /// A directory with one file per justification. Not indexed, so only suited to tests and
+    /// small data sets.
+    #[serde(rename = "fs")]
+    Directory { path: PathBuf },
+    /// The justification API of a remote indexer. Read-only.
+    #[serde(rename = "http")]
+    Http { url: String },
+}
+
+fn default_table() -> String {
+    dynamodb::DEFAULT_TABLE.to_string()
+}
+
+impl Default for StoreBackend {
+    fn default() -> Self {
+        StoreBackend::DynamoDb {
+            table: default_table(),
+            index: None,
+            json_data: false,
+        }
+    }
+}
+
+/// The `JustificationStore` backend and the format it writes records in, e.g.
+/// `{"kind": "sqlite", "path": "justifications.db", "format": "compact"}` in configuration files.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct StoreConfig {
+    #[serde(flatten)]
+    pub backend: StoreBackend,
+    #[serde(default)]
+    pub format: RecordFormat,
+}
+
+impl StoreConfig {
+    /// Read the store from JUSTIFICATION_STORE (`dynamodb`, `sqlite`, `fs` or `http`). The SQLite
+    /// and directory stores are located at JUSTIFICATION_STORE_PATH, the indexer API at
+    /// JUSTIFICATION_STORE_URL, and the DynamoDB table can be overridden with JUSTIFICATION_TABLE.
+    /// JUSTIFICATION_INDEX names the DynamoDB index used for nearest justified block queries, and
+    /// JUSTIFICATION_JSON_DATA=true keeps the JSON `data` attribute on compact DynamoDB records.
+    /// New records are written in JUSTIFICATION_FORMAT (`json` or `compact`), JSON by default.
+    /// Returns `None` if JUSTIFICATION_STORE is unset.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(kind) = env::var("JUSTIFICATION_STORE") else {
+            return Ok(None);
+        };
+        let path = || {
+            env::var("JUSTIFICATION_STORE_PATH")
+                .map(PathBuf::from)
+                .map_err(|_| {
+                    FetchError::Config(format!(
+                        "JUSTIFICATION_STORE_PATH must be set for the {} justification store",
+                        kind
+                    ))
+                })
+        };
+        let backend = match kind.as_str() {
+            "dynamodb" => StoreBackend::DynamoDb {
+                table: env::var("JUSTIFICATION_TABLE").unwrap_or_else(|_| default_table()),
+                index: env::var("JUSTIFICATION_INDEX").ok(),
+                json_data: match env::var("JUSTIFICATION_JSON_DATA") {
+                    Ok(json_data) => json_data.parse().map_err(|_| {
+                        FetchError::Config(format!(
+                            "Invalid JUSTIFICATION_JSON_DATA {}, expected true or false",
+                            json_data
+                        ))
+                    })?,
+                    Err(_) => false,
+                },
+            },
+            "sqlite" => StoreBackend::Sqlite { path: path()? },
+            "fs" => StoreBackend::Directory { path: path()? },
+            "http" => StoreBackend::Http {
+                url: env::var("JUSTIFICATION_STORE_URL").map_err(|_| {
+                    FetchError::Config(
+                        "JUSTIFICATION_STORE_URL must be set for the http justification store"
+                            .to_string(),
+                    )
+                })?,
+            },
+            _ => {
+                return Err(FetchError::Config(format!(
+                    "Unknown justification store {}",
+                    kind
+                )))
+            }
+        };
+        let format = match env::var("JUSTIFICATION_FORMAT") {
+            Ok(format) => format.parse()?,
+            Err(_) => RecordFormat::default(),
+        };
+        Ok(Some(StoreConfig { backend, format }))
+    }
+
+    /// Open the configured store.
+    pub async fn open(&self) -> Result<Arc<dyn JustificationStore>> {
+        Ok(match &self.backend {
+            StoreBackend::DynamoDb {
+                table,
+                index,
+                json_data,
+            } => {
+                let mut store = DynamoDbStore::new(table.clone())
+                    .await
+                    .with_format(self.format)
+                    .with_json_data(*json_data);
+                if let Some(index) = index {
+                    store = store.with_index(index.clone());
+                }
+                Arc::new(store)
+            }
+            StoreBackend::Sqlite { path } => {
+                Arc::new(SqliteStore::open(path)?.with_format(self.format))
+            }
+            StoreBackend::Directory { path } => {
+                Arc::new(DirectoryStore::new(path).with_format(self.format))
+            }
+            StoreBackend::Http { url } => Arc::new(JustificationApiClient::new(url.clone())),
+        })
+    }
+}
+
+/// Timeout for a single write to a justification store.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Write a justification, retrying failed writes with `retry_policy`.
+pub async fn put_with_retry(
+    store: &dyn JustificationStore,
+    avail_chain_id: &str,
+    record: &JustificationRecord,
+    retry_policy: &RetryPolicy,
+) -> Result<()> {
+    let what = format!(
+        "[{}] store write of block {}",
+        avail_chain_id,
+        record.block_number()
+    );
+    retry_policy
+        .run(WRITE_TIMEOUT, &what, || store.put(avail_chain_id, record))
+        .await
+}
+
+pub(crate) fn storage_error(error: impl std::fmt::Display) -> FetchError {
+    FetchError::Storage(error.to_string())
+}
+
+/// A justification of `block_number` without precommits, for tests.
+#[cfg(test)]
+pub(crate) fn test_justification(block_number: u32) -> GrandpaJustification {
+    GrandpaJustification {
+        round: 1,
+        commit: crate::types::Commit {
+            target_hash: sp_core::H256::repeat_byte(block_number as u8),
+            target_number: block_number,
+            precommits: Vec::new(),
+        },
+        votes_ancestries: Vec::new(),
+    }
+}
+
+/// An unvalidated record of `test_justification(block_number)`, for tests.
+#[cfg(test)]
+pub(crate) fn test_record(block_number: u32) -> JustificationRecord {
+    JustificationRecord {
+        justification: test_justification(block_number),
+        metadata: None,
+        authority_set: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_core::H256;
+
+    fn record(block_number: u32) -> JustificationRecord {
+        JustificationRecord {
+            // Block 10 was stored before justifications were validated.
+            metadata: (block_number != 10).then_some(ValidationMetadata {
+                authority_set_id: 1,
+                signer_count: 7,
+                validator_set_size: 10,
+            }),
+            authority_set: (block_number != 10).then(|| AuthoritySet {
+                authority_set_id: 1,
+                authority_set_hash: B256::repeat_byte(2),
+                authorities: vec![B256::repeat_byte(3); 10],
+            }),
+            ..test_record(block_number)
+        }
+    }
+
+    async fn check_store(store: &dyn JustificationStore) {
+        for block_number in [12, 10, 15] {
+            store.put("turing", &record(block_number)).await.unwrap();
+        }
+        store.put("mainnet", &record(20)).await.unwrap();
+
+        let stored = store.get("turing", 12).await.unwrap().unwrap();
+        assert_eq!(
+            stored.justification.commit.target_hash,
+            H256::repeat_byte(12)
+        );
+        assert_eq!(stored.metadata, record(12).metadata);
+        let unvalidated = store.get("turing", 10).await.unwrap().unwrap();
+        assert_eq!(unvalidated.metadata, None);
+        assert!(store.get("turing", 11).await.unwrap().is_none());
+        assert!(store.get("mainnet", 12).await.unwrap().is_none());
+
+        let range = store.range("turing", 11, 15).await.unwrap();
+        assert_eq!(
+            range
+                .iter()
+                .map(JustificationRecord::block_number)
+                .collect::<Vec<_>>(),
+            vec![12, 15]
+        );
+
+        assert_eq!(
+            store.block_numbers("turing", 0, 14).await.unwrap(),
+            vec![10, 12]
+        );
+
+        for (prefer, nearest) in [(Prefer::Lowest, Some(10)), (Prefer::Highest, Some(15))] {
+            assert_eq!(
+                store
+                    .nearest_justified_block("turing", 0, 300, prefer)
+                    .await
+                    .unwrap(),
+                nearest
+            );
+        }
+        assert_eq!(
+            store
+                .nearest_justified_block("turing", 11, 14, Prefer::Highest)
+                .await
+                .unwrap(),
+            Some(12)
+        );
+        assert!(store
+            .nearest_justified_block("turing", 16, 30, Prefer::Lowest)
+            .await
+            .unwrap()
+            .is_none());
+
+        let latest = store.latest("turing").await.unwrap().unwrap();
+        assert_eq!(latest.block_number(), 15);
+        assert!(store.latest("hex").await.unwrap().is_none());
+
+        store.delete("turing", &[10, 11, 12]).await.unwrap();
+        assert_eq!(
+            store.block_numbers("turing", 0, 20).await.unwrap(),
+            vec![15]
+        );
+        assert!(store.get("mainnet", 20).await.unwrap().is_some());
+    }
+
+    /// Implements only the required methods, so the default methods are used.
+    struct RangeOnly(SqliteStore);
+
+    #[async_trait]
+    impl JustificationStore for RangeOnly {
+        async fn put(&self, avail_chain_id: &str, record: &JustificationRecord) -> Result<()> {
+            self.0.put(avail_chain_id, record).await
+        }
+
+        async fn get(
+            &self,
+            avail_chain_id: &str,
+            block_number: u32,
+        ) -> Result<Option<JustificationRecord>> {
+            self.0.get(avail_chain_id, block_number).await
+        }
+
+        async fn range(
+            &self,
+            avail_chain_id: &str,
+            start_block_number: u32,
+            end_block_number: u32,
+        ) -> Result<Vec<JustificationRecord>> {
+            self.0
+                .range(avail_chain_id, start_block_number, end_block_number)
+                .await
+        }
+
+        async fn latest(&self, avail_chain_id: &str) -> Result<Option<JustificationRecord>> {
+            self.0.latest(avail_chain_id).await
+        }
+
+        async fn delete(&self, avail_chain_id: &str, block_numbers: &[u32]) -> Result<()> {
+            self.0.delete(avail_chain_id, block_numbers).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_local_stores() {
+        check_store(&RangeOnly(SqliteStore::open_in_memory().unwrap())).await;
+        for format in [RecordFormat::Json, RecordFormat::Compact] {
+            check_store(&SqliteStore::open_in_memory().unwrap().with_format(format)).await;
+
+            let dir = env::temp_dir().join(format!(
+                "vectorx-justifications-{}-{:?}",
+                std::process::id(),
+                format
+            ));
+            check_store(&DirectoryStore::new(&dir).with_format(format)).await;
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compact_store_reads_json_records() {
+        let dir = env::temp_dir().join(format!(
+            "vectorx-justifications-{}-migration",
+            std::process::id()
+        ));
+        DirectoryStore::new(&dir)
+            .put("turing", &record(10))
+            .await
+            .unwrap();
+        // JSON records hold only the justification, like in the other stores.
+        assert_eq!(
+            std::fs::read(dir.join("turing").join("10.json")).unwrap(),
+            serde_json::to_vec(&record(10).justification).unwrap()
+        );
+
+        let store = DirectoryStore::new(&dir).with_format(RecordFormat::Compact);
+        store.put("turing", &record(12)).await.unwrap();
+        let old = store.get("turing", 10).await.unwrap().unwrap();
+        assert_eq!(old.block_number(), 10);
+        assert_eq!(old.authority_set, None);
+        let new = store.get("turing", 12).await.unwrap().unwrap();
+        assert_eq!(new.authority_set, record(12).authority_set);
+        assert_eq!(
+            store
+                .range("turing", 0, 20)
+                .await
+                .unwrap()
+                .iter()
+                .map(JustificationRecord::block_number)
+                .collect::<Vec<_>>(),
+            vec![10, 12]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let compact = format::encode_compact(&record(12)).unwrap();
+        let json = serde_json::to_vec(&record(12).justification).unwrap();
+        assert!(compact.len() < json.len());
+        assert_eq!(
+            format::decode_compact(&compact).unwrap().metadata,
+            record(12).metadata
+        );
+    }
+}
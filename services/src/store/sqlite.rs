@@ -0,0 +1,271 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use rusqlite::types::Value;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use super::format::{decode_compact, decode_json, encode_compact, encode_json};
+use super::{
+    storage_error, JustificationRecord, JustificationStore, Prefer, RecordFormat,
+    ValidationMetadata,
+};
+use crate::error::FetchError;
+use crate::input::Result;
+
+/// Justifications stored in a local SQLite database, with their validation metadata in separate
+/// columns. `data` holds JSON records as text and compact records as blobs.
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+    format: RecordFormat,
+}
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS justifications (
+    chain_id TEXT NOT NULL,
+    block_number INTEGER NOT NULL,
+    data TEXT NOT NULL,
+    authority_set_id INTEGER,
+    signer_count INTEGER,
+    validator_set_size INTEGER,
+    PRIMARY KEY (chain_id, block_number)
+);
+";
+
+const COLUMNS: &str = "data, authority_set_id, signer_count, validator_set_size";
+
+type RawRecord = (Value, Option<u64>, Option<usize>, Option<usize>);
+
+fn read_row(row: &Row) -> rusqlite::Result<RawRecord> {
+    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+}
+
+fn decode(
+    (data, authority_set_id, signer_count, validator_set_size): RawRecord,
+) -> Result<JustificationRecord> {
+    let justification = match data {
+        Value::Text(data) => decode_json(&data)?,
+        Value::Blob(data) => return decode_compact(&data),
+        _ => {
+            return Err(FetchError::decode(
+                "stored justification",
+                "data is neither text nor a blob",
+            ))
+        }
+    };
+    let metadata = match (authority_set_id, signer_count, validator_set_size) {
+        (Some(authority_set_id), Some(signer_count), Some(validator_set_size)) => {
+            Some(ValidationMetadata {
+                authority_set_id,
+                signer_count,
+                validator_set_size,
+            })
+        }
+        _ => None,
+    };
+    Ok(JustificationRecord {
+        justification,
+        metadata,
+        authority_set: None,
+    })
+}
+
+impl SqliteStore {
+    /// Open the store at `path`, creating the database if it does not exist.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::init(Connection::open(path).map_err(storage_error)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory().map_err(storage_error)?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA).map_err(storage_error)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            format: RecordFormat::default(),
+        })
+    }
+
+    /// Write new records in `format`. Records in either format can be read.
+    pub fn with_format(mut self, format: RecordFormat) -> Self {
+        self.format = format;
+        self
+    }
+}
+
+#[async_trait]
+impl JustificationStore for SqliteStore {
+    async fn put(&self, avail_chain_id: &str, record: &JustificationRecord) -> Result<()> {
+        let data = match self.format {
+            RecordFormat::Json => Value::Text(encode_json(record)?),
+            RecordFormat::Compact => Value::Blob(encode_compact(record)?),
+        };
+        let metadata = record.metadata.as_ref();
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                &format!(
+                    "INSERT OR REPLACE INTO justifications (chain_id, block_number, {}) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    COLUMNS
+                ),
+                params![
+                    avail_chain_id.to_lowercase(),
+                    record.block_number(),
+                    data,
+                    metadata.map(|m| m.authority_set_id),
+                    metadata.map(|m| m.signer_count),
+                    metadata.map(|m| m.validator_set_size)
+                ],
+            )
+            .map_err(storage_error)?;
+        Ok(())
+    }
+
+    async fn get(
+        &self,
+        avail_chain_id: &str,
+        block_number: u32,
+    ) -> Result<Option<JustificationRecord>> {
+        let row = self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                &format!(
+                    "SELECT {} FROM justifications WHERE chain_id = ?1 AND block_number = ?2",
+                    COLUMNS
+                ),
+                params![avail_chain_id.to_lowercase(), block_number],
+                read_row,
+            )
+            .optional()
+            .map_err(storage_error)?;
+        row.map(decode).transpose()
+    }
+
+    async fn range(
+        &self,
+        avail_chain_id: &str,
+        start_block_number: u32,
+        end_block_number: u32,
+    ) -> Result<Vec<JustificationRecord>> {
+        let rows: Vec<RawRecord> = {
+            let conn = self.conn.lock().unwrap();
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT {} FROM justifications WHERE chain_id = ?1 AND block_number BETWEEN ?2 AND ?3 ORDER BY block_number",
+                    COLUMNS
+                ))
+                .map_err(storage_error)?;
+            let rows = stmt
+                .query_map(
+                    params![
+                        avail_chain_id.to_lowercase(),
+                        start_block_number,
+                        end_block_number
+                    ],
+                    read_row,
+                )
+                .map_err(storage_error)?;
+            rows.collect::<rusqlite::Result<_>>()
+                .map_err(storage_error)?
+        };
+        rows.into_iter().map(decode).collect()
+    }
+
+    async fn delete(&self, avail_chain_id: &str, block_numbers: &[u32]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(storage_error)?;
+        {
+            let mut stmt = tx
+                .prepare_cached(
+                    "DELETE FROM justifications WHERE chain_id = ?1 AND block_number = ?2",
+                )
+                .map_err(storage_error)?;
+            let avail_chain_id = avail_chain_id.to_lowercase();
+            for &block_number in block_numbers {
+                stmt.execute(params![avail_chain_id, block_number])
+                    .map_err(storage_error)?;
+            }
+        }
+        tx.commit().map_err(storage_error)
+    }
+
+    async fn nearest_justified_block(
+        &self,
+        avail_chain_id: &str,
+        start_block_number: u32,
+        end_block_number: u32,
+        prefer: Prefer,
+    ) -> Result<Option<u32>> {
+        let order = match prefer {
+            Prefer::Lowest => "ASC",
+            Prefer::Highest => "DESC",
+        };
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                &format!(
+                    "SELECT block_number FROM justifications WHERE chain_id = ?1 AND block_number BETWEEN ?2 AND ?3 ORDER BY block_number {} LIMIT 1",
+                    order
+                ),
+                params![
+                    avail_chain_id.to_lowercase(),
+                    start_block_number,
+                    end_block_number
+                ],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(storage_error)
+    }
+
+    async fn block_numbers(
+        &self,
+        avail_chain_id: &str,
+        start_block_number: u32,
+        end_block_number: u32,
+    ) -> Result<Vec<u32>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT block_number FROM justifications WHERE chain_id = ?1 AND block_number BETWEEN ?2 AND ?3 ORDER BY block_number",
+            )
+            .map_err(storage_error)?;
+        let block_numbers = stmt
+            .query_map(
+                params![
+                    avail_chain_id.to_lowercase(),
+                    start_block_number,
+                    end_block_number
+                ],
+                |row| row.get(0),
+            )
+            .map_err(storage_error)?
+            .collect::<rusqlite::Result<_>>()
+            .map_err(storage_error);
+        block_numbers
+    }
+
+    async fn latest(&self, avail_chain_id: &str) -> Result<Option<JustificationRecord>> {
+        let row = self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                &format!(
+                    "SELECT {} FROM justifications WHERE chain_id = ?1 ORDER BY block_number DESC LIMIT 1",
+                    COLUMNS
+                ),
+                params![avail_chain_id.to_lowercase()],
+                read_row,
+            )
+            .optional()
+            .map_err(storage_error)?;
+        row.map(decode).transpose()
+    }
+}
@@ -0,0 +1,218 @@
+//! A long-lived subscription to new GRANDPA justifications, shared by everything that waits for
+//! one. Justifications are kept in a ring buffer and fanned out over a broadcast channel, so a
+//! caller asking for the next justification does not open a subscription of its own.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use futures::{Stream, StreamExt};
+use log::{debug, warn};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+use crate::error::FetchError;
+use crate::input::{Result, RetryPolicy};
+use crate::types::GrandpaJustification;
+
+/// Number of recent justifications kept by default.
+pub const DEFAULT_RECENT_JUSTIFICATIONS: usize = 64;
+
+struct Shared {
+    recent: Mutex<VecDeque<GrandpaJustification>>,
+    capacity: usize,
+    sender: broadcast::Sender<GrandpaJustification>,
+}
+
+impl Shared {
+    fn push(&self, justification: GrandpaJustification) {
+        {
+            let mut recent = self.recent.lock().unwrap();
+            if recent.back().is_some_and(|latest| {
+                latest.commit.target_number == justification.commit.target_number
+            }) {
+                return;
+            }
+            if recent.len() == self.capacity {
+                recent.pop_front();
+            }
+            recent.push_back(justification.clone());
+        }
+        // Nobody may be waiting, in which case the justification is only buffered.
+        let _ = self.sender.send(justification);
+    }
+
+    /// The first buffered justification at or after `block_number`.
+    fn find(&self, block_number: u32) -> Option<GrandpaJustification> {
+        self.recent
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|justification| justification.commit.target_number >= block_number)
+            .cloned()
+    }
+}
+
+/// A subscription to new justifications that resubscribes, with backoff, whenever the stream
+/// fails or ends. The subscription task stops when this is dropped.
+pub struct JustificationSubscription {
+    shared: Arc<Shared>,
+    task: JoinHandle<()>,
+}
+
+impl JustificationSubscription {
+    /// Follow the streams returned by `subscribe`, keeping the latest `capacity` justifications.
+    /// `subscribe` is called again whenever the previous stream fails or ends.
+    pub fn spawn<F, Fut, St>(subscribe: F, capacity: usize, retry_policy: RetryPolicy) -> Self
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<St>> + Send + 'static,
+        St: Stream<Item = Result<GrandpaJustification>> + Send + Unpin + 'static,
+    {
+        let capacity = capacity.max(1);
+        let shared = Arc::new(Shared {
+            recent: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            sender: broadcast::channel(capacity).0,
+        });
+        let task = tokio::spawn(follow(subscribe, shared.clone(), retry_policy));
+        Self { shared, task }
+    }
+
+    /// The buffered justifications, oldest first.
+    pub fn recent(&self) -> Vec<GrandpaJustification> {
+        self.shared.recent.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// The most recently received justification.
+    pub fn latest(&self) -> Option<GrandpaJustification> {
+        self.shared.recent.lock().unwrap().back().cloned()
+    }
+
+    /// The first justification at or after `block_number`, from the buffer if one was already
+    /// received, or else the first new one that is.
+    pub async fn next_at_or_after(&self, block_number: u32) -> Result<GrandpaJustification> {
+        // Subscribe before reading the buffer, so nothing received in between is missed.
+        let mut receiver = self.shared.sender.subscribe();
+        if let Some(justification) = self.shared.find(block_number) {
+            return Ok(justification);
+        }
+        loop {
+            match receiver.recv().await {
+                Ok(justification) if justification.commit.target_number >= block_number => {
+                    return Ok(justification)
+                }
+                Ok(_) => {}
+                // The missed justifications are still in the buffer unless it overflowed too.
+                Err(RecvError::Lagged(_)) => {
+                    if let Some(justification) = self.shared.find(block_number) {
+                        return Ok(justification);
+                    }
+                }
+                Err(RecvError::Closed) => {
+                    return Err(FetchError::Rpc(
+                        "Justification subscription stopped".to_string(),
+                    ))
+                }
+            }
+        }
+    }
+
+    /// The next justification received after the latest buffered one.
+    pub async fn next(&self) -> Result<GrandpaJustification> {
+        let block_number = self
+            .latest()
+            .map_or(0, |latest| latest.commit.target_number.saturating_add(1));
+        self.next_at_or_after(block_number).await
+    }
+}
+
+impl Drop for JustificationSubscription {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn follow<F, Fut, St>(subscribe: F, shared: Arc<Shared>, retry_policy: RetryPolicy)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<St>>,
+    St: Stream<Item = Result<GrandpaJustification>> + Unpin,
+{
+    let mut backoff = retry_policy.initial_backoff;
+    loop {
+        let error = match subscribe().await {
+            Ok(mut stream) => loop {
+                match stream.next().await {
+                    Some(Ok(justification)) => {
+                        debug!(
+                            "New justification from block {}",
+                            justification.commit.target_number
+                        );
+                        backoff = retry_policy.initial_backoff;
+                        shared.push(justification);
+                    }
+                    Some(Err(e)) => break e.to_string(),
+                    None => break "the subscription ended".to_string(),
+                }
+            },
+            Err(e) => e.to_string(),
+        };
+        warn!(
+            "Justification subscription failed: {}. Resubscribing in {:?}.",
+            error, backoff
+        );
+        tokio::time::sleep(backoff).await;
+        backoff = std::cmp::min(backoff * 2, retry_policy.max_backoff);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::store::test_justification;
+    use futures::channel::mpsc;
+
+    #[tokio::test]
+    async fn test_waits_for_justification_at_or_after_block() {
+        let (sender, receiver) = mpsc::unbounded();
+        let receiver = Mutex::new(Some(receiver));
+        let subscription = JustificationSubscription::spawn(
+            move || {
+                let stream = receiver.lock().unwrap().take();
+                async move { stream.ok_or_else(|| FetchError::Rpc("already subscribed".to_string())) }
+            },
+            2,
+            RetryPolicy::default(),
+        );
+        for block_number in [5, 6, 7] {
+            sender
+                .unbounded_send(Ok(test_justification(block_number)))
+                .unwrap();
+        }
+        let waiting = tokio::spawn(async move {
+            let next = subscription.next_at_or_after(10).await.unwrap();
+            (subscription, next)
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        sender.unbounded_send(Ok(test_justification(9))).unwrap();
+        sender.unbounded_send(Ok(test_justification(12))).unwrap();
+        let (subscription, next) = waiting.await.unwrap();
+        assert_eq!(next.commit.target_number, 12);
+
+        // Only the latest two are buffered.
+        assert_eq!(
+            subscription
+                .recent()
+                .iter()
+                .map(|justification| justification.commit.target_number)
+                .collect::<Vec<_>>(),
+            vec![9, 12]
+        );
+        let buffered = subscription.next_at_or_after(8).await.unwrap();
+        assert_eq!(buffered.commit.target_number, 9);
+    }
+}
@@ -12,7 +12,7 @@ pub struct Precommit {
     pub target_number: u32,
 }
 
-#[derive(Clone, Debug, Decode, Serialize, Deserialize)]
+#[derive(Clone, Debug, Decode, Encode, Serialize, Deserialize)]
 pub struct SignedPrecommit {
     pub precommit: Precommit,
     /// The signature on the message.
@@ -21,7 +21,7 @@ pub struct SignedPrecommit {
     pub id: EdPublic,
 }
 
-#[derive(Clone, Debug, Decode, Serialize, Deserialize)]
+#[derive(Clone, Debug, Decode, Encode, Serialize, Deserialize)]
 pub struct Commit {
     pub target_hash: H256,
     /// The target block's number.
@@ -30,7 +30,7 @@ pub struct Commit {
     pub precommits: Vec<SignedPrecommit>,
 }
 
-#[derive(Clone, Debug, Decode, Serialize, Deserialize)]
+#[derive(Clone, Debug, Decode, Encode, Serialize, Deserialize)]
 pub struct GrandpaJustification {
     pub round: u64,
     pub commit: Commit,
@@ -47,7 +47,7 @@ pub enum SignerMessage {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct EncodedFinalityProof(pub Bytes);
 
-#[derive(Debug, PartialEq, Encode, Decode, Clone, Deserialize)]
+#[derive(Debug, PartialEq, Encode, Decode, Clone, Serialize, Deserialize)]
 pub struct FinalityProof {
     /// The hash of block F for which justification is provided.
     pub block: H256,